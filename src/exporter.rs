@@ -4,11 +4,12 @@ use geojson::{Feature, GeoJson, Geometry, JsonObject, JsonValue};
 use osm_xml::Id;
 use serde_json::Map;
 
-use crate::{graph::Graph, importer::{GraphNode, GraphWay}};
+use crate::{graph::{GraphRead, IDIntoUSize, NodeID}, importer::GraphNode, routing::{isochrone::Isochrone, matrix::DistanceMatrix}};
+use crate::transit::{multimodal::{Itinerary, ItineraryLeg, Place}, timetable::{StopEvent, StopID, Timetable, TripID}};
 
-pub fn export_geojson(graph: &Graph<GraphNode, GraphWay>, export_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+pub fn export_geojson<G: GraphRead<Node = GraphNode>>(graph: &G, export_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
     // let json = JsonObject::new();
-    let mut features = Vec::with_capacity(graph.nodes().count() + graph.edges().count());
+    let mut features = Vec::with_capacity(graph.node_count() + graph.edge_count());
     
     for id in graph.nodes() {
         let node = graph.get_node(id); 
@@ -59,3 +60,296 @@ pub fn export_geojson(graph: &Graph<GraphNode, GraphWay>, export_path: &Path) ->
     std::fs::write(export_path, collection.to_string())?;
     Ok(())
 }
+
+/// Matrix layout with target IDs in the header and a row per source, unreachable targets are left empty.
+pub fn export_matrix_csv(matrix: &DistanceMatrix, export_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut csv = String::from("source");
+    for target in matrix.targets() {
+        csv += &format!(",{target}");
+    }
+    csv.push('\n');
+
+    for (idx, source) in matrix.sources().iter().enumerate() {
+        csv += &source.to_string();
+        for cost in matrix.row(idx) {
+            csv.push(',');
+            if cost.is_finite() {
+                csv += &cost.to_string();
+            }
+        }
+        csv.push('\n');
+    }
+
+    std::fs::write(export_path, csv)?;
+    Ok(())
+}
+
+/// `{"sources": [...], "targets": [...], "costs": [[...], ...]}` with `null` for unreachable targets.
+pub fn export_matrix_json(matrix: &DistanceMatrix, export_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let ids = |ids: &[NodeID]| JsonValue::Array(ids.iter().map(|id| JsonValue::from(id.as_usize())).collect());
+    let rows = (0..matrix.sources().len())
+        .map(|idx| JsonValue::Array(matrix.row(idx).iter().map(|cost| if cost.is_finite() { JsonValue::from(*cost) } else { JsonValue::Null }).collect()))
+        .collect();
+
+    let mut json = Map::new();
+    json.insert("sources".to_string(), ids(matrix.sources()));
+    json.insert("targets".to_string(), ids(matrix.targets()));
+    json.insert("costs".to_string(), JsonValue::Array(rows));
+
+    std::fs::write(export_path, JsonValue::Object(json).to_string())?;
+    Ok(())
+}
+
+/// A polygon feature per isochrone with its `limit` as a property, isochrones without a polygon are left out.
+pub fn export_isochrones_geojson(isochrones: &[Isochrone], export_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let features = isochrones.iter()
+        .filter(|isochrone| !isochrone.polygon.is_empty())
+        .map(|isochrone| {
+            let ring = isochrone.polygon.iter().map(|position| position.to_vec()).collect();
+
+            let mut props = Map::new();
+            props.insert("limit".to_string(), JsonValue::from(isochrone.limit));
+
+            Feature {
+                bbox: None,
+                geometry: Some(Geometry::new(geojson::Value::Polygon(vec![ring]))),
+                id: None,
+                properties: Some(props),
+                foreign_members: None,
+            }
+        })
+        .collect();
+
+    let collection = geojson::FeatureCollection {
+        features,
+        bbox: None,
+        foreign_members: None
+    };
+
+    std::fs::write(export_path, collection.to_string())?;
+    Ok(())
+}
+
+/// A line feature per leg with the itinerary's and the leg's index, the mode, the places and times as properties.
+/// Walks follow their street geometry, transit legs go from stop to stop and list the stops with their times.
+pub fn export_itineraries_geojson(timetable: &Timetable, itineraries: &[Itinerary], export_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut features = Vec::new();
+    for (itinerary_idx, itinerary) in itineraries.iter().enumerate() {
+        for (leg_idx, leg) in itinerary.legs.iter().enumerate() {
+            let mut props = Map::new();
+            props.insert("itinerary".to_string(), JsonValue::from(itinerary_idx));
+            props.insert("leg".to_string(), JsonValue::from(leg_idx));
+            props.insert("departure".to_string(), JsonValue::String(leg.departure().to_string()));
+            props.insert("arrival".to_string(), JsonValue::String(leg.arrival().to_string()));
+            props.insert("duration".to_string(), JsonValue::from(leg.arrival().seconds() - leg.departure().seconds()));
+
+            let mut line: Vec<Vec<f64>> = match leg {
+                ItineraryLeg::Walk { from, to, distance, geometry, .. } => {
+                    props.insert("mode".to_string(), JsonValue::from("walk"));
+                    props.insert("from".to_string(), JsonValue::String(place_name(timetable, *from)));
+                    props.insert("to".to_string(), JsonValue::String(place_name(timetable, *to)));
+                    props.insert("distance".to_string(), JsonValue::from(*distance));
+                    geometry.iter().map(|position| position.to_vec()).collect()
+                }
+                ItineraryLeg::Transit { trip, from, to, .. } => {
+                    let line = timetable.line(timetable.route(timetable.trip(*trip).route).line);
+                    let stops = ridden_stops(timetable, *trip, *from, *to);
+                    props.insert("mode".to_string(), JsonValue::from("transit"));
+                    props.insert("from".to_string(), JsonValue::String(timetable.stop(*from).name.clone()));
+                    props.insert("to".to_string(), JsonValue::String(timetable.stop(*to).name.clone()));
+                    props.insert("line".to_string(), JsonValue::String(line.short_name.clone()));
+                    props.insert("line_name".to_string(), JsonValue::String(line.long_name.clone()));
+                    props.insert("route_type".to_string(), JsonValue::from(line.route_type));
+                    props.insert("trip".to_string(), JsonValue::String(timetable.trip(*trip).gtfs_id.clone()));
+                    props.insert("stops".to_string(), JsonValue::Array(stops.iter().map(|(stop, event)| {
+                        let mut call = Map::new();
+                        call.insert("stop".to_string(), JsonValue::String(timetable.stop(*stop).gtfs_id.clone()));
+                        call.insert("name".to_string(), JsonValue::String(timetable.stop(*stop).name.clone()));
+                        call.insert("arrival".to_string(), JsonValue::String(event.arrival.to_string()));
+                        call.insert("departure".to_string(), JsonValue::String(event.departure.to_string()));
+                        JsonValue::Object(call)
+                    }).collect()));
+                    stops.iter().map(|(stop, _)| vec![timetable.stop(*stop).lon, timetable.stop(*stop).lat]).collect()
+                }
+            };
+            // a line needs two positions, walks of no length have one
+            if line.len() == 1 {
+                line.push(line[0].clone());
+            }
+
+            features.push(Feature {
+                bbox: None,
+                geometry: Some(Geometry::new(geojson::Value::LineString(line))),
+                id: None,
+                properties: Some(props),
+                foreign_members: None,
+            });
+        }
+    }
+
+    let collection = geojson::FeatureCollection {
+        features,
+        bbox: None,
+        foreign_members: None
+    };
+
+    std::fs::write(export_path, collection.to_string())?;
+    Ok(())
+}
+
+/// GPX 1.1 with a track per itinerary and a segment per leg, and a waypoint wherever a vehicle is boarded.
+/// GPX times need a date, so the times are only given in the descriptions.
+pub fn export_itineraries_gpx(timetable: &Timetable, itineraries: &[Itinerary], export_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut gpx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    gpx += "<gpx version=\"1.1\" creator=\"raptordb\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n";
+
+    // waypoints come before the tracks
+    for (idx, itinerary) in itineraries.iter().enumerate() {
+        for leg in &itinerary.legs {
+            if let ItineraryLeg::Transit { from, .. } = leg {
+                let stop = timetable.stop(*from);
+                let desc = format!("itinerary {}: {}", idx + 1, leg_summary(timetable, leg));
+                gpx += &format!("  <wpt lat=\"{}\" lon=\"{}\"><name>{}</name><desc>{}</desc></wpt>\n", stop.lat, stop.lon, escape_xml(&stop.name), escape_xml(&desc));
+            }
+        }
+    }
+
+    for (idx, itinerary) in itineraries.iter().enumerate() {
+        let name = format!("Itinerary {}, {} - {}", idx + 1, itinerary.departure(), itinerary.arrival());
+        let desc = itinerary.legs.iter().map(|leg| leg_summary(timetable, leg)).collect::<Vec<_>>().join("; ");
+        gpx += &format!("  <trk>\n    <name>{}</name>\n    <desc>{}</desc>\n", escape_xml(&name), escape_xml(&desc));
+
+        for leg in &itinerary.legs {
+            gpx += "    <trkseg>\n";
+            match leg {
+                ItineraryLeg::Walk { geometry, .. } => {
+                    for [lon, lat] in geometry {
+                        gpx += &format!("      <trkpt lat=\"{lat}\" lon=\"{lon}\"/>\n");
+                    }
+                }
+                ItineraryLeg::Transit { trip, from, to, .. } => {
+                    for (stop, _) in ridden_stops(timetable, *trip, *from, *to) {
+                        let stop = timetable.stop(stop);
+                        gpx += &format!("      <trkpt lat=\"{}\" lon=\"{}\"><name>{}</name></trkpt>\n", stop.lat, stop.lon, escape_xml(&stop.name));
+                    }
+                }
+            }
+            gpx += "    </trkseg>\n";
+        }
+        gpx += "  </trk>\n";
+    }
+    gpx += "</gpx>\n";
+
+    std::fs::write(export_path, gpx)?;
+    Ok(())
+}
+
+fn place_name(timetable: &Timetable, place: Place) -> String {
+    match place {
+        Place::Origin => "origin".to_string(),
+        Place::Destination => "destination".to_string(),
+        Place::Stop(stop) => timetable.stop(stop).name.clone(),
+    }
+}
+
+/// Stops of the trip from boarding to alighting with their times, the leg has to follow the trip's route.
+fn ridden_stops(timetable: &Timetable, trip: TripID, from: StopID, to: StopID) -> Vec<(StopID, StopEvent)> {
+    let stops = &timetable.route(timetable.trip(trip).route).stops;
+    let events = timetable.trip_events(trip);
+    let board = stops.iter().position(|stop| *stop == from).expect("trip calls at the stop the leg starts at");
+    let alight = board + 1 + stops[board + 1..].iter().position(|stop| *stop == to).expect("trip calls at the stop the leg ends at after boarding");
+    stops[board..=alight].iter().copied().zip(events[board..=alight].iter().copied()).collect()
+}
+
+fn leg_summary(timetable: &Timetable, leg: &ItineraryLeg) -> String {
+    match leg {
+        ItineraryLeg::Walk { from, to, departure, arrival, .. } => {
+            format!("walk from {} to {}, {departure} - {arrival}", place_name(timetable, *from), place_name(timetable, *to))
+        }
+        ItineraryLeg::Transit { trip, from, to, departure, arrival } => {
+            let line = timetable.line(timetable.route(timetable.trip(*trip).route).line);
+            let name = if line.short_name.is_empty() { &line.long_name } else { &line.short_name };
+            format!("{name} from {} to {}, {departure} - {arrival}", timetable.stop(*from).name, timetable.stop(*to).name)
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transit::timetable::{Line, NewTrip, Stop, Time};
+
+    use super::*;
+
+    fn itinerary() -> (Timetable, Itinerary) {
+        let mut timetable = Timetable::new();
+        let stops: Vec<_> = ["Dworzec", "Rynek", "Park & Ride"].iter().enumerate()
+            .map(|(i, name)| timetable.add_stop(Stop { gtfs_id: i.to_string(), name: name.to_string(), lat: 50.0 + i as f64 * 0.01, lon: 20.0 }))
+            .collect();
+        let line = timetable.add_line(Line { gtfs_id: "R1".into(), short_name: "1".into(), long_name: "Dworzec - Park".into(), route_type: 3 });
+        let times = [(8, 0), (8, 10), (8, 20)].map(|(hours, minutes)| Time::from_hms(hours, minutes, 0));
+        let trip = timetable.add_trip(NewTrip {
+            gtfs_id: "R1_0800".into(),
+            line,
+            service_id: "WD".into(),
+            stops: stops.clone(),
+            events: times.iter().map(|time| StopEvent { arrival: *time, departure: *time }).collect(),
+        });
+
+        let legs = vec![
+            ItineraryLeg::Walk {
+                from: Place::Origin,
+                to: Place::Stop(stops[0]),
+                departure: Time::from_hms(7, 55, 0),
+                arrival: Time::from_hms(8, 0, 0),
+                distance: 300.0,
+                geometry: vec![[19.998, 49.998], [20.0, 49.999], [20.0, 50.0]],
+            },
+            ItineraryLeg::Transit { trip, from: stops[0], to: stops[2], departure: times[0], arrival: times[2] },
+        ];
+        (timetable, Itinerary { legs })
+    }
+
+    #[test]
+    fn itineraries_as_geojson() {
+        let (timetable, itinerary) = itinerary();
+        let path = std::env::temp_dir().join(format!("raptordb-itineraries-{}.geojson", std::process::id()));
+        export_itineraries_geojson(&timetable, &[itinerary], &path).unwrap();
+        let json: JsonValue = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let features = json["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0]["properties"]["mode"], "walk");
+        assert_eq!(features[0]["properties"]["from"], "origin");
+        assert_eq!(features[0]["geometry"]["coordinates"].as_array().unwrap().len(), 3);
+
+        let transit = &features[1]["properties"];
+        assert_eq!((&transit["mode"], &transit["line"], &transit["trip"]), (&"transit".into(), &"1".into(), &"R1_0800".into()));
+        assert_eq!((&transit["departure"], &transit["arrival"], &transit["duration"]), (&"08:00:00".into(), &"08:20:00".into(), &1200.into()));
+        assert_eq!(transit["stops"][1]["name"], "Rynek");
+        assert_eq!(transit["stops"][2]["arrival"], "08:20:00");
+        assert_eq!(features[1]["geometry"]["coordinates"][2], serde_json::json!([20.0, 50.02]));
+    }
+
+    #[test]
+    fn itineraries_as_gpx() {
+        let (timetable, itinerary) = itinerary();
+        let path = std::env::temp_dir().join(format!("raptordb-itineraries-{}.gpx", std::process::id()));
+        export_itineraries_gpx(&timetable, &[itinerary], &path).unwrap();
+        let gpx = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(gpx.matches("<trk>").count(), 1);
+        assert_eq!(gpx.matches("<trkseg>").count(), 2);
+        assert_eq!(gpx.matches("<trkpt ").count(), 6);
+        assert_eq!(gpx.matches("<wpt ").count(), 1);
+        assert!(gpx.contains("<name>Itinerary 1, 07:55:00 - 08:20:00</name>"), "{gpx}");
+        assert!(gpx.contains("<name>Park &amp; Ride</name>"), "{gpx}");
+        assert!(gpx.contains("1 from Dworzec to Park &amp; Ride, 08:00:00 - 08:20:00"), "{gpx}");
+        assert!(gpx.find("<wpt ").unwrap() < gpx.find("<trk>").unwrap());
+    }
+}
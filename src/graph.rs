@@ -9,6 +9,9 @@ mod node;
 mod edge;
 mod availability_manager;
 mod store;
+mod view;
+mod read;
+mod subgraph;
 
 use log::trace;
 use log::warn;
@@ -19,6 +22,11 @@ use crate::graph::{edge::Edge, node::Node, store::Store};
 pub use crate::graph::node::NodeID;
 pub use crate::graph::edge::EdgeID;
 pub use crate::graph::edge::EdgeKind;
+pub use crate::graph::edge::EdgeLabel;
+pub use crate::graph::edge::EdgeLabelSet;
+pub use crate::graph::view::GraphView;
+pub use crate::graph::view::UnfilteredView;
+pub use crate::graph::read::GraphRead;
 
 #[derive(Debug)]
 pub struct Graph<N: Copy, E: Copy> {
@@ -50,6 +58,25 @@ impl<N, E> Graph<N, E> where N: Copy + PartialEq, E: Copy + PartialEq {
     }
 
     pub fn add_edge(&mut self, from: NodeID, to: NodeID, property: E, kind: EdgeKind,) -> EdgeID {
+        self.add_labelled_edge(from, to, property, kind, EdgeLabel::UNLABELLED)
+    }
+
+    pub fn add_labelled_edge(&mut self, from: NodeID, to: NodeID, property: E, kind: EdgeKind, label: EdgeLabel) -> EdgeID {
+        let backward_property = (kind == EdgeKind::Bidirectional).then_some(property);
+        self.add_edge_impl(Edge { from, to, kind, label, property, backward_property })
+    }
+
+    /// Adds an `EdgeKind::Bidirectional` edge, `forward` is used when going from `from` to `to` and `backward` the other way around.
+    pub fn add_bidirectional_edge(&mut self, from: NodeID, to: NodeID, forward: E, backward: E) -> EdgeID {
+        self.add_labelled_bidirectional_edge(from, to, forward, backward, EdgeLabel::UNLABELLED)
+    }
+
+    pub fn add_labelled_bidirectional_edge(&mut self, from: NodeID, to: NodeID, forward: E, backward: E, label: EdgeLabel) -> EdgeID {
+        self.add_edge_impl(Edge { from, to, kind: EdgeKind::Bidirectional, label, property: forward, backward_property: Some(backward) })
+    }
+
+    fn add_edge_impl(&mut self, edge: Edge<E>) -> EdgeID {
+        let Edge { from, to, .. } = edge;
         debug_assert!(self.node_store.exists(from), "invalid 'from' NodeID: {from:?}");
         debug_assert!(self.node_store.exists(to), "invalid 'to' NodeID: {to:?}");
         debug_assert!(from != to, "cyclical edges are not supported: from and to are the same NodeID: {from:?}");
@@ -58,7 +85,7 @@ impl<N, E> Graph<N, E> where N: Copy + PartialEq, E: Copy + PartialEq {
             warn!("parallel edge detected: there is already an edge between {from:?} and {to:?}");
         } 
 
-        let id = self.edge_store.add(Edge { from, to, kind, property });
+        let id = self.edge_store.add(edge);
 
         let to_node = self.node_store.get_mut(self.edge_store.get(id).to);
         to_node.edges.push(id);
@@ -74,11 +101,30 @@ impl<N, E> Graph<N, E> where N: Copy + PartialEq, E: Copy + PartialEq {
         &self.node_store.get(id).property
     }
 
+    /// For `EdgeKind::Bidirectional` edges this is the forward property, see `get_edge_in_direction`.
     pub fn get_edge(&self, id: EdgeID) -> &E {
         debug_assert!(self.edge_store.exists(id), "invalid EdgeID: {id:?}");
         &self.edge_store.get(id).property
     }
 
+    /// Property of the edge when it's traversed starting at `from`.
+    pub fn get_edge_in_direction(&self, id: EdgeID, from: NodeID) -> &E {
+        debug_assert!(self.edge_store.exists(id), "invalid EdgeID: {id:?}");
+        let edge = self.edge_store.get(id);
+        debug_assert!(edge.from == from || edge.to == from, "NodeID {from:?} is not an endpoint of edge {id:?}");
+        edge.property_from(from)
+    }
+
+    pub fn get_edge_kind(&self, id: EdgeID) -> EdgeKind {
+        debug_assert!(self.edge_store.exists(id), "invalid EdgeID: {id:?}");
+        self.edge_store.get(id).kind
+    }
+
+    pub fn get_edge_label(&self, id: EdgeID) -> EdgeLabel {
+        debug_assert!(self.edge_store.exists(id), "invalid EdgeID: {id:?}");
+        self.edge_store.get(id).label
+    }
+
     pub fn get_connected_nodes(&self, id: EdgeID) -> ConnectedNodes {
         let edge = self.edge_store.get(id);
         ConnectedNodes { from: edge.from, to: edge.to }
@@ -86,23 +132,31 @@ impl<N, E> Graph<N, E> where N: Copy + PartialEq, E: Copy + PartialEq {
 
     // not sure if this should count undirected edges
     pub fn get_outgoing_edges(&self, id: NodeID) -> Vec<EdgeID> {
+        self.get_outgoing_edges_labelled(id, EdgeLabelSet::ALL)
+    }
+
+    pub fn get_outgoing_edges_labelled(&self, id: NodeID, labels: EdgeLabelSet) -> Vec<EdgeID> {
         debug_assert!(self.node_store.exists(id), "invalid NodeID: {id:?}");
 
         self.node_store.get(id).edges.iter()
             .filter(|edge_id| {
                 let edge = self.edge_store.get(**edge_id);
-                edge.from == id || edge.kind == EdgeKind::Undirected
+                labels.contains(edge.label) && (edge.from == id || edge.kind.is_two_way())
             }).cloned().collect()
     }
 
     // not sure if this should count undirected edges
     pub fn get_incoming_edges(&self, id: NodeID) -> Vec<EdgeID> {
+        self.get_incoming_edges_labelled(id, EdgeLabelSet::ALL)
+    }
+
+    pub fn get_incoming_edges_labelled(&self, id: NodeID, labels: EdgeLabelSet) -> Vec<EdgeID> {
         debug_assert!(self.node_store.exists(id), "invalid NodeID: {id:?}");
 
         self.node_store.get(id).edges.iter()
             .filter(|edge_id| {
                 let edge = self.edge_store.get(**edge_id);
-                edge.to == id || edge.kind == EdgeKind::Undirected
+                labels.contains(edge.label) && (edge.to == id || edge.kind.is_two_way())
             }).copied().collect()
     }
 
@@ -121,7 +175,7 @@ impl<N, E> Graph<N, E> where N: Copy + PartialEq, E: Copy + PartialEq {
                             None
                         }
                     },
-                    EdgeKind::Undirected => {
+                    EdgeKind::Undirected | EdgeKind::Bidirectional => {
                         if (edge.to == to) || (edge.from == to) {
                             Some(*edge_id)
                         } else {
@@ -187,7 +241,7 @@ impl <N, E> PartialEq for Graph<N, E> where N: Debug + Copy + PartialEq + Hash +
                         acc
                     });
                 let edges = self.edge_store.all()
-                    .map(|n| &n.item.property)
+                    .flat_map(|n| edge_direction_properties(&n.item))
                     .fold(HashMap::new(), |mut acc, prop| {
                         *acc.entry(prop).or_insert(0) += 1;
                         acc
@@ -202,7 +256,7 @@ impl <N, E> PartialEq for Graph<N, E> where N: Debug + Copy + PartialEq + Hash +
                         acc
                     });
                 let edges = other.edge_store.all()
-                    .map(|n| &n.item.property)
+                    .flat_map(|n| edge_direction_properties(&n.item))
                     .fold(HashMap::new(), |mut acc, prop| {
                         *acc.entry(prop).or_insert(0) += 1;
                         acc
@@ -282,45 +336,61 @@ impl <N, E> Graph<N, E> where N: Debug + Copy + PartialEq + Hash + Eq + Sync + S
         self_node: NodeID,
         node_mappings: &HashMap<NodeID, NodeID>,
     ) -> bool {
-        for (self_prime_node, other_prime_node) in node_mappings {
-            let self_edges = self.get_edges_between(*self_prime_node, self_node);
-            let other_edges = other.get_edges_between(*other_prime_node, other_node);
+        node_mappings.iter().all(|(self_prime_node, other_prime_node)| {
+            self.are_edges_between_consistent(other, (*self_prime_node, self_node), (*other_prime_node, other_node))
+                && self.are_edges_between_consistent(other, (self_node, *self_prime_node), (other_node, *other_prime_node))
+        })
+    }
 
-            if self_edges.len() != other_edges.len() {
-                trace!("adjacency inconsistency detected: number of edges between {:?} and {:?} in self is {} but number of edges between {:?} and {:?} in other is {}", self_prime_node, self_node, self_edges.len(), other_prime_node, other_node, other_edges.len());
-                return false;
-            }
+    fn are_edges_between_consistent(
+        &self,
+        other: &Self,
+        (self_prime_node, self_node): (NodeID, NodeID),
+        (other_prime_node, other_node): (NodeID, NodeID),
+    ) -> bool {
+        let self_edges = self.get_edges_between(self_prime_node, self_node);
+        let other_edges = other.get_edges_between(other_prime_node, other_node);
 
-            let mut counts = HashMap::new();
+        if self_edges.len() != other_edges.len() {
+            trace!("adjacency inconsistency detected: number of edges between {:?} and {:?} in self is {} but number of edges between {:?} and {:?} in other is {}", self_prime_node, self_node, self_edges.len(), other_prime_node, other_node, other_edges.len());
+            return false;
+        }
 
-            for e in &self_edges {
-                let prop = self.get_edge(*e);
-                *counts.entry(prop).or_insert(0usize) += 1;
-            }
+        let mut counts = HashMap::new();
 
-            for e in &other_edges {
-                let prop = other.get_edge(*e);
-                match counts.get_mut(&prop) {
-                    Some(count) => {
-                        *count -= 1;
-                        if *count == 0 {
-                            counts.remove(&prop);
-                        }
+        for e in &self_edges {
+            let prop = (self.get_edge_kind(*e), self.get_edge_label(*e), self.get_edge_in_direction(*e, self_prime_node));
+            *counts.entry(prop).or_insert(0usize) += 1;
+        }
+
+        for e in &other_edges {
+            let prop = (other.get_edge_kind(*e), other.get_edge_label(*e), other.get_edge_in_direction(*e, other_prime_node));
+            match counts.get_mut(&prop) {
+                Some(count) => {
+                    *count -= 1;
+                    if *count == 0 {
+                        counts.remove(&prop);
                     }
-                    None => return false,
                 }
+                None => return false,
             }
+        }
 
-            if !counts.is_empty() {
-                trace!("adjacency inconsistency detected: edge properties between {self_prime_node:?} and {self_node:?} in self do not match edge properties between {other_prime_node:?} and {other_node:?} in other, remaining counts: {counts:?}");
-                return false;
-            }}
+        if !counts.is_empty() {
+            trace!("adjacency inconsistency detected: edge properties between {self_prime_node:?} and {self_node:?} in self do not match edge properties between {other_prime_node:?} and {other_node:?} in other, remaining counts: {counts:?}");
+            return false;
+        }
 
         true
     }
 }
 
-trait IDIntoUSize {
+// bidirectional edges contribute both of their properties, so that the result doesn't depend on which end was `from`
+fn edge_direction_properties<E>(edge: &Edge<E>) -> impl Iterator<Item = (EdgeLabel, &E)> {
+    std::iter::once((edge.label, &edge.property)).chain(edge.backward_property.as_ref().map(|backward| (edge.label, backward)))
+}
+
+pub(crate) trait IDIntoUSize {
     fn as_usize(&self) -> usize;
     fn from_usize(id: usize) -> Self;
 }
@@ -331,6 +401,20 @@ pub struct ConnectedNodes {
     pub to: NodeID,
 }
 
+impl ConnectedNodes {
+    /// The endpoint that isn't `node`.
+    pub fn opposite(&self, node: NodeID) -> NodeID {
+        debug_assert!(node == self.from || node == self.to, "NodeID {node:?} is not an endpoint: {self:?}");
+        if node == self.from { self.to } else { self.from }
+    }
+}
+
+/// Maps ids of one graph to the ids of the graph that was built or extended from it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IDMapping {
+    pub nodes: HashMap<NodeID, NodeID>,
+    pub edges: HashMap<EdgeID, EdgeID>,
+}
 
 //TODO: make this statically polymorphic
 struct StoreIterable<'a, T, I> {
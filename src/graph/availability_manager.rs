@@ -68,4 +68,9 @@ impl<T: IDIntoUSize + Copy> AvailabilityManager<T> {
     pub fn taken_count(&self) -> usize {
         self.ids.count_ones()
     }
+
+    /// All ids ever handed out are smaller than this.
+    pub fn bound(&self) -> usize {
+        self.ids.len()
+    }
 }
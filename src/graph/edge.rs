@@ -1,13 +1,72 @@
 use std::hash::Hash;
 
-use derive_more::{Display, Eq, From};
+use derive_more::{Display, Eq};
 use serde_json::Number;
 use crate::graph::{IDIntoUSize, node::NodeID};
 
 #[derive(Debug, PartialEq, Clone, Copy, Hash, Eq)]
 pub enum EdgeKind {
     Directed,
-    Undirected
+    Undirected,
+    /// Traversable both ways, with a separate property for each direction.
+    Bidirectional,
+}
+
+impl EdgeKind {
+    pub fn is_two_way(&self) -> bool {
+        matches!(self, EdgeKind::Undirected | EdgeKind::Bidirectional)
+    }
+}
+
+/// Relationship type of an edge, used to keep layers of a multimodal graph apart.
+/// Custom labels can be created with `EdgeLabel::new`, as long as they fit in an `EdgeLabelSet`.
+#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq, PartialOrd, Ord, Default)]
+pub struct EdgeLabel(u8);
+
+impl EdgeLabel {
+    pub const UNLABELLED: EdgeLabel = EdgeLabel(0);
+    pub const ROAD: EdgeLabel = EdgeLabel(1);
+    pub const FOOTPATH: EdgeLabel = EdgeLabel(2);
+    pub const TRANSFER: EdgeLabel = EdgeLabel(3);
+    pub const TRIP_SEGMENT: EdgeLabel = EdgeLabel(4);
+
+    pub const fn new(value: u8) -> Self {
+        assert!((value as u32) < u64::BITS, "edge label doesn't fit in an EdgeLabelSet");
+        EdgeLabel(value)
+    }
+}
+
+/// Bitmask of `EdgeLabel`s, so that filtering adjacency by label is a single `&`.
+#[derive(Debug, PartialEq, Clone, Copy, Hash, Eq)]
+pub struct EdgeLabelSet(u64);
+
+impl EdgeLabelSet {
+    pub const ALL: EdgeLabelSet = EdgeLabelSet(u64::MAX);
+    pub const EMPTY: EdgeLabelSet = EdgeLabelSet(0);
+
+    pub const fn of(label: EdgeLabel) -> Self {
+        EdgeLabelSet(1 << label.0)
+    }
+
+    pub const fn with(self, label: EdgeLabel) -> Self {
+        EdgeLabelSet(self.0 | (1 << label.0))
+    }
+
+    pub const fn contains(&self, label: EdgeLabel) -> bool {
+        self.0 & (1 << label.0) != 0
+    }
+}
+
+impl From<EdgeLabel> for EdgeLabelSet {
+    fn from(value: EdgeLabel) -> Self {
+        EdgeLabelSet::of(value)
+    }
+}
+
+impl FromIterator<EdgeLabel> for EdgeLabelSet {
+    fn from_iter<I: IntoIterator<Item = EdgeLabel>>(iter: I) -> Self {
+        iter.into_iter().fold(EdgeLabelSet::EMPTY, EdgeLabelSet::with)
+    }
 }
 
 #[derive(Debug, Clone, Copy, std::cmp::Eq)]
@@ -15,13 +74,25 @@ pub(in crate) struct Edge<T> {
     pub(super) from: NodeID,
     pub(super) to: NodeID,
     pub(super) kind: EdgeKind,
+    pub(super) label: EdgeLabel,
     pub(super) property: T,
+    /// Property used when traversing from `to` to `from`, only set for `EdgeKind::Bidirectional`.
+    pub(super) backward_property: Option<T>,
+}
+
+impl<T> Edge<T> {
+    pub(super) fn property_from(&self, from: NodeID) -> &T {
+        match &self.backward_property {
+            Some(backward) if from == self.to => backward,
+            _ => &self.property,
+        }
+    }
 }
 
 // TODO: move edge and node comparision into the graph itself, so that elements dependant on id can also be compared
 impl<E> PartialEq for Edge<E> where E: PartialEq {
     fn eq(&self, other: &Self) -> bool {
-        self.kind == other.kind && self.property == other.property //&& match self.kind {
+        self.kind == other.kind && self.label == other.label && self.property == other.property && self.backward_property == other.backward_property //&& match self.kind {
         //     EdgeKind::Directed => self.from == other.from && self.to == other.to,
         //     EdgeKind::Undirected => (self.from == other.from && self.to == other.to) || (self.from == other.to && self.to == other.from)
         // }
@@ -32,7 +103,9 @@ impl<E> PartialEq for Edge<E> where E: PartialEq {
 impl<E> Hash for Edge<E> where E: Hash {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.kind.hash(state);
-        self.property.hash(state) 
+        self.label.hash(state);
+        self.property.hash(state);
+        self.backward_property.hash(state)
     }
 }
 
@@ -0,0 +1,130 @@
+use crate::graph::{ConnectedNodes, EdgeID, EdgeKind, EdgeLabel, EdgeLabelSet, Graph, GraphView, NodeID, UnfilteredView, view::keep_all};
+
+/// Read-only access to a graph, so that algorithms don't have to care whether they run on a `Graph`,
+/// a `GraphView` over it, or any other representation.
+pub trait GraphRead {
+    type Node;
+    type Edge;
+
+    fn nodes(&self) -> impl Iterator<Item = NodeID>;
+    fn edges(&self) -> impl Iterator<Item = EdgeID>;
+
+    fn node_count(&self) -> usize {
+        self.nodes().count()
+    }
+
+    fn edge_count(&self) -> usize {
+        self.edges().count()
+    }
+
+    /// Every `NodeID` of this graph converted with `IDIntoUSize` is smaller than this, so algorithms can keep per node state in a `Vec`.
+    fn node_id_bound(&self) -> usize;
+    /// Same as `node_id_bound`, but for `EdgeID`s.
+    fn edge_id_bound(&self) -> usize;
+
+    fn contains_node(&self, id: NodeID) -> bool;
+    fn contains_edge(&self, id: EdgeID) -> bool;
+
+    fn get_node(&self, id: NodeID) -> &Self::Node;
+    fn get_edge(&self, id: EdgeID) -> &Self::Edge;
+    fn get_edge_in_direction(&self, id: EdgeID, from: NodeID) -> &Self::Edge;
+    fn get_edge_kind(&self, id: EdgeID) -> EdgeKind;
+    fn get_edge_label(&self, id: EdgeID) -> EdgeLabel;
+    fn get_connected_nodes(&self, id: EdgeID) -> ConnectedNodes;
+
+    fn get_outgoing_edges_labelled(&self, id: NodeID, labels: EdgeLabelSet) -> Vec<EdgeID>;
+    fn get_incoming_edges_labelled(&self, id: NodeID, labels: EdgeLabelSet) -> Vec<EdgeID>;
+    fn get_edges_between(&self, from: NodeID, to: NodeID) -> Vec<EdgeID>;
+
+    fn get_outgoing_edges(&self, id: NodeID) -> Vec<EdgeID> {
+        self.get_outgoing_edges_labelled(id, EdgeLabelSet::ALL)
+    }
+
+    fn get_incoming_edges(&self, id: NodeID) -> Vec<EdgeID> {
+        self.get_incoming_edges_labelled(id, EdgeLabelSet::ALL)
+    }
+
+    fn view<NP, EP>(&self, node_predicate: NP, edge_predicate: EP) -> GraphView<'_, Self, NP, EP> where
+        Self: Sized,
+        NP: Fn(NodeID, &Self::Node) -> bool,
+        EP: Fn(EdgeID, &Self::Edge) -> bool {
+        GraphView::new(self, node_predicate, edge_predicate)
+    }
+
+    fn reversed(&self) -> UnfilteredView<'_, Self> where Self: Sized {
+        self.view(keep_all::<NodeID, Self::Node> as _, keep_all::<EdgeID, Self::Edge> as _).reversed()
+    }
+}
+
+impl<N, E> GraphRead for Graph<N, E> where N: Copy + PartialEq, E: Copy + PartialEq {
+    type Node = N;
+    type Edge = E;
+
+    fn nodes(&self) -> impl Iterator<Item = NodeID> {
+        Graph::nodes(self)
+    }
+
+    fn edges(&self) -> impl Iterator<Item = EdgeID> {
+        Graph::edges(self)
+    }
+
+    fn node_count(&self) -> usize {
+        self.node_store.len()
+    }
+
+    fn edge_count(&self) -> usize {
+        self.edge_store.len()
+    }
+
+    fn node_id_bound(&self) -> usize {
+        self.node_store.id_bound()
+    }
+
+    fn edge_id_bound(&self) -> usize {
+        self.edge_store.id_bound()
+    }
+
+    fn contains_node(&self, id: NodeID) -> bool {
+        self.node_store.exists(id)
+    }
+
+    fn contains_edge(&self, id: EdgeID) -> bool {
+        self.edge_store.exists(id)
+    }
+
+    fn get_node(&self, id: NodeID) -> &N {
+        Graph::get_node(self, id)
+    }
+
+    fn get_edge(&self, id: EdgeID) -> &E {
+        Graph::get_edge(self, id)
+    }
+
+    fn get_edge_in_direction(&self, id: EdgeID, from: NodeID) -> &E {
+        Graph::get_edge_in_direction(self, id, from)
+    }
+
+    fn get_edge_kind(&self, id: EdgeID) -> EdgeKind {
+        Graph::get_edge_kind(self, id)
+    }
+
+    fn get_edge_label(&self, id: EdgeID) -> EdgeLabel {
+        Graph::get_edge_label(self, id)
+    }
+
+    fn get_connected_nodes(&self, id: EdgeID) -> ConnectedNodes {
+        Graph::get_connected_nodes(self, id)
+    }
+
+    fn get_outgoing_edges_labelled(&self, id: NodeID, labels: EdgeLabelSet) -> Vec<EdgeID> {
+        Graph::get_outgoing_edges_labelled(self, id, labels)
+    }
+
+    fn get_incoming_edges_labelled(&self, id: NodeID, labels: EdgeLabelSet) -> Vec<EdgeID> {
+        Graph::get_incoming_edges_labelled(self, id, labels)
+    }
+
+    fn get_edges_between(&self, from: NodeID, to: NodeID) -> Vec<EdgeID> {
+        Graph::get_edges_between(self, from, to)
+    }
+}
@@ -47,7 +47,11 @@ impl<T, I> Store<T, I> where I: IDIntoUSize + Copy + Debug {
     }
 
     pub(super) fn exists(&self, id: I) -> bool {
-        self.availability.is_taken(id)
+        id.as_usize() < self.availability.bound() && self.availability.is_taken(id)
+    }
+
+    pub(super) fn id_bound(&self) -> usize {
+        self.availability.bound()
     }
 
     pub(super) fn len(&self) -> usize {
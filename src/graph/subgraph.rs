@@ -0,0 +1,78 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use log::warn;
+
+use crate::graph::{EdgeID, Graph, IDMapping, NodeID, edge::Edge};
+
+impl<N, E> Graph<N, E> where N: Copy + PartialEq, E: Copy + PartialEq {
+    /// Builds a new graph out of `nodes` and every edge that has both of its endpoints among them.
+    /// The mapping goes from ids in `self` to ids in the new graph.
+    pub fn subgraph(&self, nodes: &HashSet<NodeID>) -> (Graph<N, E>, IDMapping) {
+        let mut subgraph = Graph::new();
+        let mut mapping = IDMapping::default();
+
+        for id in self.nodes().filter(|id| nodes.contains(id)) {
+            mapping.nodes.insert(id, subgraph.add_node(*self.get_node(id)));
+        }
+
+        for id in self.edges() {
+            let edge = self.edge_store.get(id);
+            let (Some(&from), Some(&to)) = (mapping.nodes.get(&edge.from), mapping.nodes.get(&edge.to)) else {
+                continue;
+            };
+
+            mapping.edges.insert(id, subgraph.add_edge_impl(Edge { from, to, ..*edge }));
+        }
+
+        (subgraph, mapping)
+    }
+
+    /// Adds `other` into `self`. Nodes for which `key` returns the same value are considered the same node,
+    /// and edges identical to one already present between the same nodes are not added again.
+    /// The mapping goes from ids in `other` to ids in `self`.
+    pub fn merge<K, F>(&mut self, other: &Graph<N, E>, key: F) -> IDMapping where
+        K: Hash + Eq,
+        F: Fn(&N) -> K {
+        let mut mapping = IDMapping::default();
+        let mut node_by_key = HashMap::<K, NodeID>::new();
+
+        for id in self.nodes() {
+            node_by_key.entry(key(self.get_node(id))).or_insert(id);
+        }
+
+        for id in other.nodes() {
+            let property = *other.get_node(id);
+            let merged_id = *node_by_key.entry(key(&property)).or_insert_with(|| self.add_node(property));
+            mapping.nodes.insert(id, merged_id);
+        }
+
+        for id in other.edges() {
+            let edge = other.edge_store.get(id);
+            let from = mapping.nodes[&edge.from];
+            let to = mapping.nodes[&edge.to];
+
+            if from == to {
+                warn!("edge {id:?} collapsed into a loop on {from:?} because both of its nodes have the same key, skipping");
+                continue;
+            }
+
+            let merged = Edge { from, to, ..*edge };
+            let existing = self.get_edges_between(from, to).into_iter().find(|e| self.is_same_edge(*e, &merged));
+            let merged_id = existing.unwrap_or_else(|| self.add_edge_impl(merged));
+
+            mapping.edges.insert(id, merged_id);
+        }
+
+        mapping
+    }
+
+    fn is_same_edge(&self, id: EdgeID, edge: &Edge<E>) -> bool {
+        let existing = self.edge_store.get(id);
+
+        existing.kind == edge.kind
+            && existing.label == edge.label
+            && existing.property_from(edge.from) == edge.property_from(edge.from)
+            && existing.property_from(edge.to) == edge.property_from(edge.to)
+    }
+}
@@ -2,7 +2,7 @@
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::graph::{EdgeKind, Graph};
+    use crate::graph::{EdgeKind, EdgeLabel, EdgeLabelSet, Graph, GraphRead};
 
     #[test]
     fn test_add_multiple_nodes() {
@@ -375,4 +375,301 @@ mod test {
         g2.delete_edge(e);
         assert_ne!(g1, g2);
     }
+
+    #[test]
+    fn labelled_adjacency_filters_by_label() {
+        let mut g = Graph::<i32, i32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let c = g.add_node(3);
+
+        let road = g.add_labelled_edge(a, b, 10, EdgeKind::Undirected, EdgeLabel::ROAD);
+        let footpath = g.add_labelled_edge(a, c, 20, EdgeKind::Directed, EdgeLabel::FOOTPATH);
+        let transfer = g.add_labelled_edge(c, a, 30, EdgeKind::Directed, EdgeLabel::TRANSFER);
+
+        assert_eq!(g.get_edge_label(road), EdgeLabel::ROAD);
+        assert_eq!(g.get_outgoing_edges_labelled(a, EdgeLabel::ROAD.into()), vec![road]);
+        assert_eq!(g.get_outgoing_edges_labelled(a, EdgeLabel::FOOTPATH.into()), vec![footpath]);
+        assert!(g.get_outgoing_edges_labelled(a, EdgeLabel::TRANSFER.into()).is_empty());
+        assert_eq!(g.get_incoming_edges_labelled(a, EdgeLabel::TRANSFER.into()), vec![transfer]);
+
+        let layers: EdgeLabelSet = [EdgeLabel::ROAD, EdgeLabel::FOOTPATH].into_iter().collect();
+        assert_eq!(g.get_outgoing_edges_labelled(a, layers).len(), 2);
+        assert_eq!(g.get_outgoing_edges(a).len(), 2);
+        assert!(g.get_outgoing_edges_labelled(a, EdgeLabelSet::EMPTY).is_empty());
+    }
+
+    #[test]
+    fn unlabelled_edges_get_default_label() {
+        let mut g = Graph::<i32, i32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let e = g.add_edge(a, b, 1, EdgeKind::Directed);
+
+        assert_eq!(g.get_edge_label(e), EdgeLabel::UNLABELLED);
+        assert_eq!(g.get_edge_label(e), EdgeLabel::default());
+    }
+
+    #[test]
+    fn partial_eq_considers_edge_labels() {
+        let mut g1 = Graph::<i32, i32>::new();
+        let a1 = g1.add_node(1);
+        let b1 = g1.add_node(2);
+        g1.add_labelled_edge(a1, b1, 5, EdgeKind::Undirected, EdgeLabel::ROAD);
+
+        let mut g2 = Graph::<i32, i32>::new();
+        let a2 = g2.add_node(1);
+        let b2 = g2.add_node(2);
+        g2.add_labelled_edge(a2, b2, 5, EdgeKind::Undirected, EdgeLabel::FOOTPATH);
+
+        assert_ne!(g1, g2);
+
+        let mut g3 = Graph::<i32, i32>::new();
+        let a3 = g3.add_node(1);
+        let b3 = g3.add_node(2);
+        g3.add_labelled_edge(b3, a3, 5, EdgeKind::Undirected, EdgeLabel::ROAD);
+
+        assert_eq!(g1, g3);
+    }
+
+    #[test]
+    fn bidirectional_edge_has_per_direction_properties() {
+        let mut g = Graph::<i32, i32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let e = g.add_bidirectional_edge(a, b, 50, 30);
+
+        assert_eq!(g.get_edge_kind(e), EdgeKind::Bidirectional);
+        assert_eq!(*g.get_edge(e), 50);
+        assert_eq!(*g.get_edge_in_direction(e, a), 50);
+        assert_eq!(*g.get_edge_in_direction(e, b), 30);
+
+        assert_eq!(g.get_outgoing_edges(a), vec![e]);
+        assert_eq!(g.get_outgoing_edges(b), vec![e]);
+        assert_eq!(g.get_incoming_edges(a), vec![e]);
+        assert_eq!(g.get_edges_between(a, b), vec![e]);
+        assert_eq!(g.get_edges_between(b, a), vec![e]);
+    }
+
+    #[test]
+    fn non_bidirectional_edges_have_same_property_both_ways() {
+        let mut g = Graph::<i32, i32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let e = g.add_edge(a, b, 7, EdgeKind::Undirected);
+
+        assert_eq!(*g.get_edge_in_direction(e, a), 7);
+        assert_eq!(*g.get_edge_in_direction(e, b), 7);
+    }
+
+    #[test]
+    fn bidirectional_equality_accounts_for_orientation() {
+        let mut g1 = Graph::<i32, i32>::new();
+        let a1 = g1.add_node(1);
+        let b1 = g1.add_node(2);
+        g1.add_bidirectional_edge(a1, b1, 50, 30);
+
+        let mut flipped = Graph::<i32, i32>::new();
+        let a2 = flipped.add_node(1);
+        let b2 = flipped.add_node(2);
+        flipped.add_bidirectional_edge(b2, a2, 30, 50);
+
+        assert_eq!(g1, flipped);
+
+        let mut swapped = Graph::<i32, i32>::new();
+        let a3 = swapped.add_node(1);
+        let b3 = swapped.add_node(2);
+        swapped.add_bidirectional_edge(a3, b3, 30, 50);
+
+        assert_ne!(g1, swapped);
+    }
+
+    #[test]
+    fn bidirectional_and_undirected_edges_are_not_equal() {
+        let mut g1 = Graph::<i32, i32>::new();
+        let a1 = g1.add_node(1);
+        let b1 = g1.add_node(2);
+        g1.add_bidirectional_edge(a1, b1, 5, 5);
+
+        let mut g2 = Graph::<i32, i32>::new();
+        let a2 = g2.add_node(1);
+        let b2 = g2.add_node(2);
+        g2.add_edge(a2, b2, 5, EdgeKind::Undirected);
+
+        assert_ne!(g1, g2);
+    }
+
+    #[test]
+    fn view_hides_filtered_nodes_and_edges() {
+        let mut g = Graph::<i32, i32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let c = g.add_node(3);
+        let ab = g.add_edge(a, b, 10, EdgeKind::Undirected);
+        let bc = g.add_edge(b, c, 20, EdgeKind::Directed);
+        let ac = g.add_edge(a, c, 99, EdgeKind::Directed);
+
+        let cheap = g.view(|_, _| true, |_, prop| *prop < 50);
+        assert_eq!(cheap.nodes().count(), 3);
+        assert_eq!(cheap.edges().collect::<Vec<_>>(), vec![ab, bc]);
+        assert_eq!(cheap.get_outgoing_edges(a), vec![ab]);
+        assert!(!cheap.contains_edge(ac));
+
+        let without_b = g.view(|id, _| id != b, |_, _| true);
+        assert!(!without_b.contains_node(b));
+        assert_eq!(without_b.edges().collect::<Vec<_>>(), vec![ac]);
+        assert_eq!(without_b.get_outgoing_edges(a), vec![ac]);
+        assert!(without_b.get_incoming_edges(c).iter().all(|e| *e != bc));
+
+        // the underlying graph is untouched
+        assert_eq!(g.edges().count(), 3);
+    }
+
+    #[test]
+    fn reversed_view_swaps_directed_edges() {
+        let mut g = Graph::<i32, i32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let c = g.add_node(3);
+        let ab = g.add_edge(a, b, 10, EdgeKind::Directed);
+        let bc = g.add_edge(b, c, 20, EdgeKind::Undirected);
+
+        let reversed = g.reversed();
+        assert!(reversed.get_outgoing_edges(a).is_empty());
+        assert_eq!(reversed.get_outgoing_edges(b), vec![ab, bc]);
+        assert_eq!(reversed.get_incoming_edges(a), vec![ab]);
+        assert_eq!(reversed.get_edges_between(b, a), vec![ab]);
+        assert!(reversed.get_edges_between(a, b).is_empty());
+
+        let nodes = reversed.get_connected_nodes(ab);
+        assert_eq!((nodes.from, nodes.to), (b, a));
+    }
+
+    #[test]
+    fn reversed_view_keeps_bidirectional_costs_attached_to_travel_direction() {
+        let mut g = Graph::<i32, i32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let e = g.add_bidirectional_edge(a, b, 50, 30);
+
+        let reversed = g.reversed();
+        // reaching a from b backwards means originally travelling a -> b
+        assert_eq!(*reversed.get_edge_in_direction(e, b), 50);
+        assert_eq!(*reversed.get_edge_in_direction(e, a), 30);
+        assert!(!reversed.reversed().is_reversed());
+    }
+
+    fn reachable_from<G: GraphRead>(graph: &G, start: crate::graph::NodeID) -> usize {
+        let mut seen = vec![start];
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            for edge in graph.get_outgoing_edges(node) {
+                let next = graph.get_connected_nodes(edge).opposite(node);
+                if !seen.contains(&next) {
+                    seen.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+        seen.len()
+    }
+
+    #[test]
+    fn algorithms_run_on_any_graph_read() {
+        let mut g = Graph::<i32, i32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let c = g.add_node(3);
+        let d = g.add_node(4);
+        g.add_edge(a, b, 1, EdgeKind::Directed);
+        g.add_edge(b, c, 2, EdgeKind::Directed);
+        g.add_edge(c, d, 3, EdgeKind::Undirected);
+
+        assert_eq!(reachable_from(&g, a), 4);
+        assert_eq!(reachable_from(&g.reversed(), a), 1);
+        assert_eq!(reachable_from(&g.reversed(), d), 4);
+
+        let without_c = g.view(|id, _| id != c, |_, _| true);
+        assert_eq!(reachable_from(&without_c, a), 2);
+
+        // views compose
+        let only_cheap = without_c.view(|_, _| true, |_, prop| *prop < 1);
+        assert_eq!(reachable_from(&only_cheap, a), 1);
+        assert_eq!(only_cheap.node_count(), 3);
+        assert_eq!(only_cheap.edge_count(), 0);
+    }
+
+    #[test]
+    fn subgraph_keeps_only_induced_edges() {
+        let mut g = Graph::<i32, i32>::new();
+        let a = g.add_node(1);
+        let b = g.add_node(2);
+        let c = g.add_node(3);
+        let ab = g.add_labelled_edge(a, b, 10, EdgeKind::Directed, EdgeLabel::ROAD);
+        let bc = g.add_bidirectional_edge(b, c, 20, 25);
+        g.add_edge(a, c, 30, EdgeKind::Undirected);
+
+        let (sub, mapping) = g.subgraph(&[b, c].into_iter().collect());
+
+        assert_eq!(sub.nodes().count(), 2);
+        assert_eq!(sub.edges().count(), 1);
+        assert!(!mapping.nodes.contains_key(&a));
+        assert!(!mapping.edges.contains_key(&ab));
+
+        let sub_bc = mapping.edges[&bc];
+        assert_eq!(*sub.get_node(mapping.nodes[&c]), 3);
+        assert_eq!(*sub.get_edge_in_direction(sub_bc, mapping.nodes[&c]), 25);
+        assert_eq!(sub.get_edge_kind(sub_bc), EdgeKind::Bidirectional);
+
+        let mut expected = Graph::<i32, i32>::new();
+        let x = expected.add_node(2);
+        let y = expected.add_node(3);
+        expected.add_bidirectional_edge(x, y, 20, 25);
+        assert_eq!(sub, expected);
+    }
+
+    #[test]
+    fn merge_deduplicates_nodes_by_key_and_identical_edges() {
+        let mut left = Graph::<(i32, i32), i32>::new();
+        let a = left.add_node((0, 0));
+        let b = left.add_node((1, 0));
+        let ab = left.add_edge(a, b, 10, EdgeKind::Undirected);
+
+        // neighbouring extract sharing the boundary edge a - b
+        let mut right = Graph::<(i32, i32), i32>::new();
+        let b2 = right.add_node((1, 0));
+        let a2 = right.add_node((0, 0));
+        let c2 = right.add_node((2, 0));
+        let ba2 = right.add_edge(b2, a2, 10, EdgeKind::Undirected);
+        let bc2 = right.add_edge(b2, c2, 20, EdgeKind::Directed);
+
+        let mapping = left.merge(&right, |coords| *coords);
+
+        assert_eq!(left.nodes().count(), 3);
+        assert_eq!(left.edges().count(), 2);
+        assert_eq!(mapping.nodes[&a2], a);
+        assert_eq!(mapping.nodes[&b2], b);
+        assert_eq!(mapping.edges[&ba2], ab);
+        assert_eq!(*left.get_node(mapping.nodes[&c2]), (2, 0));
+        assert_eq!(left.get_edges_between(b, mapping.nodes[&c2]), vec![mapping.edges[&bc2]]);
+    }
+
+    #[test]
+    fn merge_keeps_edges_that_differ() {
+        let mut left = Graph::<i32, i32>::new();
+        let a = left.add_node(1);
+        let b = left.add_node(2);
+        left.add_edge(a, b, 10, EdgeKind::Directed);
+
+        let mut right = Graph::<i32, i32>::new();
+        let a2 = right.add_node(1);
+        let b2 = right.add_node(2);
+        right.add_edge(b2, a2, 10, EdgeKind::Directed);
+        right.add_edge(a2, b2, 11, EdgeKind::Directed);
+
+        left.merge(&right, |n| *n);
+
+        assert_eq!(left.nodes().count(), 2);
+        assert_eq!(left.edges().count(), 3);
+    }
 }
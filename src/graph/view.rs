@@ -0,0 +1,145 @@
+use crate::graph::{ConnectedNodes, EdgeID, EdgeKind, EdgeLabel, EdgeLabelSet, GraphRead, NodeID};
+
+/// View that doesn't filter anything out, as returned by `GraphRead::reversed`.
+pub type UnfilteredView<'a, G> = GraphView<'a, G, fn(NodeID, &<G as GraphRead>::Node) -> bool, fn(EdgeID, &<G as GraphRead>::Edge) -> bool>;
+
+pub(super) fn keep_all<I, T>(_: I, _: &T) -> bool {
+    true
+}
+
+/// Read-only view over another graph that hides nodes and edges rejected by the predicates, without copying anything.
+/// Edges are hidden as well when either of their endpoints is hidden.
+pub struct GraphView<'a, G, NP, EP> {
+    graph: &'a G,
+    node_predicate: NP,
+    edge_predicate: EP,
+    reversed: bool,
+}
+
+impl<'a, G, NP, EP> GraphView<'a, G, NP, EP> where
+    G: GraphRead,
+    NP: Fn(NodeID, &G::Node) -> bool,
+    EP: Fn(EdgeID, &G::Edge) -> bool {
+    pub(super) fn new(graph: &'a G, node_predicate: NP, edge_predicate: EP) -> Self {
+        Self { graph, node_predicate, edge_predicate, reversed: false }
+    }
+
+    /// Same view, but with the direction of every edge swapped, so outgoing edges become incoming ones and vice versa.
+    /// Properties stay attached to the original direction of travel, which is what a backward search needs.
+    pub fn reversed(self) -> Self {
+        Self { reversed: !self.reversed, ..self }
+    }
+
+    pub fn is_reversed(&self) -> bool {
+        self.reversed
+    }
+}
+
+impl<G, NP, EP> GraphRead for GraphView<'_, G, NP, EP> where
+    G: GraphRead,
+    NP: Fn(NodeID, &G::Node) -> bool,
+    EP: Fn(EdgeID, &G::Edge) -> bool {
+    type Node = G::Node;
+    type Edge = G::Edge;
+
+    fn nodes(&self) -> impl Iterator<Item = NodeID> {
+        self.graph.nodes().filter(|id| (self.node_predicate)(*id, self.graph.get_node(*id)))
+    }
+
+    fn edges(&self) -> impl Iterator<Item = EdgeID> {
+        self.graph.edges().filter(|id| self.contains_edge(*id))
+    }
+
+    fn node_id_bound(&self) -> usize {
+        self.graph.node_id_bound()
+    }
+
+    fn edge_id_bound(&self) -> usize {
+        self.graph.edge_id_bound()
+    }
+
+    fn contains_node(&self, id: NodeID) -> bool {
+        self.graph.contains_node(id) && (self.node_predicate)(id, self.graph.get_node(id))
+    }
+
+    fn contains_edge(&self, id: EdgeID) -> bool {
+        if !self.graph.contains_edge(id) {
+            return false;
+        }
+
+        let nodes = self.graph.get_connected_nodes(id);
+        (self.edge_predicate)(id, self.graph.get_edge(id)) && self.contains_node(nodes.from) && self.contains_node(nodes.to)
+    }
+
+    fn get_node(&self, id: NodeID) -> &G::Node {
+        debug_assert!(self.contains_node(id), "NodeID not visible in view: {id:?}");
+        self.graph.get_node(id)
+    }
+
+    fn get_edge(&self, id: EdgeID) -> &G::Edge {
+        debug_assert!(self.contains_edge(id), "EdgeID not visible in view: {id:?}");
+        self.graph.get_edge(id)
+    }
+
+    fn get_edge_in_direction(&self, id: EdgeID, from: NodeID) -> &G::Edge {
+        debug_assert!(self.contains_edge(id), "EdgeID not visible in view: {id:?}");
+        if self.reversed {
+            // going from `from` in the reversed graph is going towards `from` in the original one
+            let original_from = self.graph.get_connected_nodes(id).opposite(from);
+            self.graph.get_edge_in_direction(id, original_from)
+        } else {
+            self.graph.get_edge_in_direction(id, from)
+        }
+    }
+
+    fn get_edge_kind(&self, id: EdgeID) -> EdgeKind {
+        self.graph.get_edge_kind(id)
+    }
+
+    fn get_edge_label(&self, id: EdgeID) -> EdgeLabel {
+        self.graph.get_edge_label(id)
+    }
+
+    fn get_connected_nodes(&self, id: EdgeID) -> ConnectedNodes {
+        let nodes = self.graph.get_connected_nodes(id);
+        if self.reversed {
+            ConnectedNodes { from: nodes.to, to: nodes.from }
+        } else {
+            nodes
+        }
+    }
+
+    fn get_outgoing_edges_labelled(&self, id: NodeID, labels: EdgeLabelSet) -> Vec<EdgeID> {
+        debug_assert!(self.contains_node(id), "NodeID not visible in view: {id:?}");
+        let mut edges = if self.reversed {
+            self.graph.get_incoming_edges_labelled(id, labels)
+        } else {
+            self.graph.get_outgoing_edges_labelled(id, labels)
+        };
+        edges.retain(|e| self.contains_edge(*e));
+        edges
+    }
+
+    fn get_incoming_edges_labelled(&self, id: NodeID, labels: EdgeLabelSet) -> Vec<EdgeID> {
+        debug_assert!(self.contains_node(id), "NodeID not visible in view: {id:?}");
+        let mut edges = if self.reversed {
+            self.graph.get_outgoing_edges_labelled(id, labels)
+        } else {
+            self.graph.get_incoming_edges_labelled(id, labels)
+        };
+        edges.retain(|e| self.contains_edge(*e));
+        edges
+    }
+
+    fn get_edges_between(&self, from: NodeID, to: NodeID) -> Vec<EdgeID> {
+        debug_assert!(self.contains_node(from), "'from' NodeID not visible in view: {from:?}");
+        debug_assert!(self.contains_node(to), "'to' NodeID not visible in view: {to:?}");
+        let mut edges = if self.reversed {
+            self.graph.get_edges_between(to, from)
+        } else {
+            self.graph.get_edges_between(from, to)
+        };
+        edges.retain(|e| self.contains_edge(*e));
+        edges
+    }
+}
@@ -7,7 +7,10 @@ use ordered_float::OrderedFloat;
 use osm_xml::OSM;
 use osmpbf::{Element, ElementReader};
 
-use crate::graph::{EdgeKind, Graph, NodeID};
+use crate::graph::{EdgeKind, EdgeLabel, Graph, NodeID};
+
+pub mod gtfs;
+pub mod gtfs_rt;
 
 #[derive(Clone, Copy, From, Debug, PartialEq, Hash, Eq)]
 pub struct Lattitude(OrderedFloat<f64>);
@@ -39,6 +42,28 @@ pub struct GraphNode {
     pub lon: Longitude,
 }
 
+impl GraphNode {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Self { lat: OrderedFloat(lat).into(), lon: OrderedFloat(lon).into() }
+    }
+}
+
+/// Anything placed on the globe, lets geographic algorithms work with node types other than `GraphNode`.
+pub trait HasCoordinates {
+    fn lat(&self) -> f64;
+    fn lon(&self) -> f64;
+}
+
+impl HasCoordinates for GraphNode {
+    fn lat(&self) -> f64 {
+        self.lat.into()
+    }
+
+    fn lon(&self) -> f64 {
+        self.lon.into()
+    }
+}
+
 #[derive(Debug, Clone)]
 struct ImportedWay {
     node_refs: Vec<i64>,
@@ -51,13 +76,25 @@ pub struct GraphWay {
     distance: OrderedFloat<f64> //TODO: newtype this probably
 }
 
-fn haversine_distance(start: &GraphNode, end: &GraphNode) -> f64 {
+impl GraphWay {
+    pub fn new(distance: f64) -> Self {
+        Self { distance: OrderedFloat(distance) }
+    }
+
+    /// Length of the way in metres.
+    pub fn distance(&self) -> f64 {
+        self.distance.into_inner()
+    }
+}
+
+/// Great-circle distance in metres.
+pub fn haversine_distance(start: &impl HasCoordinates, end: &impl HasCoordinates) -> f64 {
     const EARTH_RADIUS_M: f64 = 6371e3;
 
-    let lat1 = start.lat.0.into_inner();
-    let lon1 = start.lon.0.into_inner();
-    let lat2 = end.lat.0.into_inner();
-    let lon2 = end.lon.0.into_inner();
+    let lat1 = start.lat();
+    let lon1 = start.lon();
+    let lat2 = end.lat();
+    let lon2 = end.lon();
 
     let lat1_rad = lat1.to_radians();
     let lat2_rad = lat2.to_radians();
@@ -78,6 +115,13 @@ fn quantize_coord(v: f64) -> f64 {
     (v * COORD_QUANT).round() / COORD_QUANT
 }
 
+fn edge_label_for_highway(highway: Option<&str>) -> EdgeLabel {
+    match highway {
+        Some("footway" | "path" | "pedestrian" | "steps" | "corridor") => EdgeLabel::FOOTPATH,
+        _ => EdgeLabel::ROAD,
+    }
+}
+
 pub fn import_pbf(path: &Path) -> Result<Graph<GraphNode, GraphWay>, Box<dyn Error>> {
     let reader = ElementReader::from_path(path)?;
     let mut graph = Graph::<GraphNode, GraphWay>::new();
@@ -123,7 +167,7 @@ pub fn import_pbf(path: &Path) -> Result<Graph<GraphNode, GraphWay>, Box<dyn Err
             } else {
                 EdgeKind::Undirected
             };
-
+            let label = edge_label_for_highway(way.tags.get("highway").map(String::as_str));
 
             let Some(&start_node_graph) = graph_id_by_import_id.get(&start_node) else {
                 warn!("Encountered way with dangling node id: {way:#?}");
@@ -134,7 +178,7 @@ pub fn import_pbf(path: &Path) -> Result<Graph<GraphNode, GraphWay>, Box<dyn Err
                 return;
             };
 
-            graph.add_edge(start_node_graph, end_node_graph, GraphWay { distance: OrderedFloat(haversine_distance(graph.get_node(start_node_graph), graph.get_node(end_node_graph))) }, kind);
+            graph.add_labelled_edge(start_node_graph, end_node_graph, GraphWay { distance: OrderedFloat(haversine_distance(graph.get_node(start_node_graph), graph.get_node(end_node_graph))) }, kind, label);
         });
     }
 
@@ -184,6 +228,7 @@ pub fn import_xml(path: &Path) -> Result<Graph<GraphNode, GraphWay>, Box<dyn Err
             } else {
                 EdgeKind::Undirected
             };
+            let label = edge_label_for_highway(way.tags.iter().find(|tag| tag.key == "highway").map(|tag| tag.val.as_str()));
 
             let Some(&start_node_graph) = graph_id_by_import_id.get(&start_node) else {
                 warn!("Encountered way with dangling node id: {way:#?}");
@@ -194,7 +239,7 @@ pub fn import_xml(path: &Path) -> Result<Graph<GraphNode, GraphWay>, Box<dyn Err
                 return;
             };
 
-            graph.add_edge(start_node_graph, end_node_graph, GraphWay { distance: OrderedFloat(haversine_distance(graph.get_node(start_node_graph), graph.get_node(end_node_graph))) }, kind);
+            graph.add_labelled_edge(start_node_graph, end_node_graph, GraphWay { distance: OrderedFloat(haversine_distance(graph.get_node(start_node_graph), graph.get_node(end_node_graph))) }, kind, label);
         });  
     }
 
@@ -0,0 +1,697 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use derive_more::{Display, Error, From};
+use log::{info, warn};
+use zip::ZipArchive;
+
+use crate::transit::calendar::{Date, ServicePeriod};
+use crate::transit::timetable::{Line, LineID, NewTrip, Stop, StopEvent, StopID, Time, Timetable, Transfer};
+
+/// Everything that can go wrong reading a feed, line numbers are the ones shown by a text editor.
+#[derive(Debug, Display, Error, From)]
+pub enum GtfsError {
+    #[display("couldn't read feed: {_0}")]
+    Io(io::Error),
+    #[display("couldn't read zip archive: {_0}")]
+    Zip(zip::result::ZipError),
+    #[display("malformed csv: {_0}")]
+    Csv(csv::Error),
+    #[display("{file} is missing from the feed")]
+    #[from(ignore)]
+    MissingFile { file: &'static str },
+    #[display("{file} has no {column} column")]
+    #[from(ignore)]
+    MissingColumn { file: &'static str, column: &'static str },
+    #[display("{file}:{line}: invalid {column} {value:?}")]
+    #[from(ignore)]
+    InvalidValue { file: &'static str, line: u64, column: &'static str, value: String },
+    #[display("{file}:{line}: {column} {id:?} is defined twice")]
+    #[from(ignore)]
+    DuplicateId { file: &'static str, line: u64, column: &'static str, id: String },
+    #[display("{file}:{line}: unknown stop_id {stop_id:?}")]
+    #[from(ignore)]
+    UnknownStop { file: &'static str, line: u64, stop_id: String },
+    #[display("{file}:{line}: unknown route_id {route_id:?}")]
+    #[from(ignore)]
+    UnknownRoute { file: &'static str, line: u64, route_id: String },
+    #[display("{file}:{line}: unknown trip_id {trip_id:?}")]
+    #[from(ignore)]
+    UnknownTrip { file: &'static str, line: u64, trip_id: String },
+    #[display("trip {trip_id:?} has no time at stop_sequence {stop_sequence}, which is needed at its first and last stop")]
+    #[from(ignore)]
+    MissingTime { trip_id: String, stop_sequence: u32 },
+    #[display("trip {trip_id:?} goes back in time at stop_sequence {stop_sequence}")]
+    #[from(ignore)]
+    NonMonotonicTimes { trip_id: String, stop_sequence: u32 },
+    #[display("{file}:{line}: frequency of trip {trip_id:?} ends before it starts")]
+    #[from(ignore)]
+    EmptyFrequency { file: &'static str, line: u64, trip_id: String },
+}
+
+/// Reads stops, routes, trips, stop times, transfers and the service calendar of a GTFS feed, either a zip archive
+/// or a directory with its files. Routes are split into trip patterns, see `Timetable::add_trip`, and trips with
+/// frequencies become one trip per departure, named `<trip_id>@<departure>`.
+/// The timetable has the trips of all days, `Timetable::for_date` picks the ones of a day.
+pub fn import_gtfs(path: &Path) -> Result<Timetable, GtfsError> {
+    let mut feed = Feed::open(path)?;
+    let mut timetable = Timetable::new();
+
+    let stops = read_stops(&mut feed, &mut timetable)?;
+    let lines = read_routes(&mut feed, &mut timetable)?;
+    let trips = read_trips(&mut feed, &lines)?;
+    let stop_times = read_stop_times(&mut feed, &stops, &trips)?;
+    let frequencies = read_frequencies(&mut feed, &trips)?;
+    read_calendar(&mut feed, &mut timetable)?;
+
+    for (gtfs_id, (line, service_id)) in trips {
+        let Some(mut calls) = stop_times.get(&gtfs_id).cloned() else {
+            warn!("trip {gtfs_id:?} has no stop times, skipping it");
+            continue;
+        };
+        if calls.len() < 2 {
+            warn!("trip {gtfs_id:?} calls at less than two stops, skipping it");
+            continue;
+        }
+
+        calls.sort_by_key(|call| call.sequence);
+        let events = trip_events(&gtfs_id, &calls)?;
+        let stops: Vec<StopID> = calls.iter().map(|call| call.stop).collect();
+
+        let Some(frequencies) = frequencies.get(&gtfs_id) else {
+            timetable.add_trip(NewTrip { gtfs_id, line, service_id, stops, events });
+            continue;
+        };
+        // the stop times only give the time between stops, trips leave every headway from the start up to the end,
+        // arriving at the first stop before leaving it may shift that arrival to before midnight
+        let first = events[0].departure.seconds();
+        for frequency in frequencies {
+            for departure in (frequency.start.seconds()..frequency.end.seconds()).step_by(frequency.headway as usize) {
+                let shift = |time: Time| Time::from_seconds((time.seconds() + departure).saturating_sub(first));
+                timetable.add_trip(NewTrip {
+                    gtfs_id: format!("{gtfs_id}@{}", Time::from_seconds(departure)),
+                    line,
+                    service_id: service_id.clone(),
+                    stops: stops.clone(),
+                    events: events.iter().map(|event| StopEvent { arrival: shift(event.arrival), departure: shift(event.departure) }).collect(),
+                });
+            }
+        }
+    }
+
+    read_transfers(&mut feed, &stops, &mut timetable)?;
+
+    info!("imported {} stops, {} trips in {} routes from {}", timetable.stop_count(), timetable.trip_count(), timetable.route_count(), path.display());
+    Ok(timetable)
+}
+
+enum Feed {
+    Directory(PathBuf),
+    Zip(ZipArchive<File>),
+}
+
+impl Feed {
+    fn open(path: &Path) -> Result<Self, GtfsError> {
+        if path.is_dir() {
+            Ok(Feed::Directory(path.to_path_buf()))
+        } else {
+            Ok(Feed::Zip(ZipArchive::new(File::open(path)?)?))
+        }
+    }
+
+    /// Contents of `file`, `None` if the feed doesn't have it.
+    fn read(&mut self, file: &str) -> Result<Option<Vec<u8>>, GtfsError> {
+        let mut contents = Vec::new();
+        match self {
+            Feed::Directory(dir) => match File::open(dir.join(file)) {
+                Ok(mut opened) => opened.read_to_end(&mut contents)?,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err.into()),
+            },
+            Feed::Zip(archive) => match archive.by_name(file) {
+                Ok(mut opened) => opened.read_to_end(&mut contents)?,
+                Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            },
+        };
+        Ok(Some(contents))
+    }
+
+    fn table(&mut self, file: &'static str) -> Result<Table, GtfsError> {
+        self.optional_table(file)?.ok_or(GtfsError::MissingFile { file })
+    }
+
+    fn optional_table(&mut self, file: &'static str) -> Result<Option<Table>, GtfsError> {
+        let Some(contents) = self.read(file)? else {
+            return Ok(None);
+        };
+
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(io::Cursor::new(contents));
+        let columns = reader.headers()?
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| (name.trim_start_matches('\u{feff}').to_string(), idx))
+            .collect();
+
+        Ok(Some(Table { file, reader, columns }))
+    }
+}
+
+struct Table {
+    file: &'static str,
+    reader: csv::Reader<io::Cursor<Vec<u8>>>,
+    columns: HashMap<String, usize>,
+}
+
+#[derive(Clone, Copy)]
+struct Column {
+    name: &'static str,
+    idx: Option<usize>,
+}
+
+impl Table {
+    fn column(&self, name: &'static str) -> Result<Column, GtfsError> {
+        let column = self.optional_column(name);
+        match column.idx {
+            Some(_) => Ok(column),
+            None => Err(GtfsError::MissingColumn { file: self.file, column: name }),
+        }
+    }
+
+    fn optional_column(&self, name: &'static str) -> Column {
+        Column { name, idx: self.columns.get(name).copied() }
+    }
+
+    fn rows(&mut self) -> impl Iterator<Item = Result<Row, GtfsError>> + '_ {
+        let file = self.file;
+        self.reader.records().map(move |record| {
+            let record = record?;
+            let line = record.position().map_or(0, |position| position.line());
+            Ok(Row { file, line, record })
+        })
+    }
+}
+
+struct Row {
+    file: &'static str,
+    line: u64,
+    record: csv::StringRecord,
+}
+
+impl Row {
+    /// Empty for optional columns the file doesn't have.
+    fn get(&self, column: Column) -> &str {
+        column.idx.and_then(|idx| self.record.get(idx)).unwrap_or("")
+    }
+
+    fn parse<T: FromStr>(&self, column: Column) -> Result<T, GtfsError> {
+        let value = self.get(column);
+        value.parse().map_err(|_| self.invalid(column, value))
+    }
+
+    fn invalid(&self, column: Column, value: &str) -> GtfsError {
+        GtfsError::InvalidValue { file: self.file, line: self.line, column: column.name, value: value.to_string() }
+    }
+}
+
+fn read_stops(feed: &mut Feed, timetable: &mut Timetable) -> Result<HashMap<String, StopID>, GtfsError> {
+    let mut table = feed.table("stops.txt")?;
+    let (id, name, lat, lon) = (table.column("stop_id")?, table.column("stop_name")?, table.column("stop_lat")?, table.column("stop_lon")?);
+    let location_type = table.optional_column("location_type");
+
+    let mut stops = HashMap::new();
+    for row in table.rows() {
+        let row = row?;
+        // generic nodes and boarding areas have no position of their own
+        if matches!(row.get(location_type), "3" | "4") {
+            continue;
+        }
+
+        let stop = Stop { gtfs_id: row.get(id).to_string(), name: row.get(name).to_string(), lat: row.parse(lat)?, lon: row.parse(lon)? };
+        if stops.contains_key(&stop.gtfs_id) {
+            return Err(GtfsError::DuplicateId { file: row.file, line: row.line, column: id.name, id: stop.gtfs_id });
+        }
+        stops.insert(stop.gtfs_id.clone(), timetable.add_stop(stop));
+    }
+
+    Ok(stops)
+}
+
+fn read_routes(feed: &mut Feed, timetable: &mut Timetable) -> Result<HashMap<String, LineID>, GtfsError> {
+    let mut table = feed.table("routes.txt")?;
+    let (id, route_type) = (table.column("route_id")?, table.column("route_type")?);
+    let (short_name, long_name) = (table.optional_column("route_short_name"), table.optional_column("route_long_name"));
+
+    let mut lines = HashMap::new();
+    for row in table.rows() {
+        let row = row?;
+        let line = Line {
+            gtfs_id: row.get(id).to_string(),
+            short_name: row.get(short_name).to_string(),
+            long_name: row.get(long_name).to_string(),
+            route_type: row.parse(route_type)?,
+        };
+        if lines.contains_key(&line.gtfs_id) {
+            return Err(GtfsError::DuplicateId { file: row.file, line: row.line, column: id.name, id: line.gtfs_id });
+        }
+        lines.insert(line.gtfs_id.clone(), timetable.add_line(line));
+    }
+
+    Ok(lines)
+}
+
+/// Line and service of every trip. Ordered by trip ID, so trip IDs in the timetable don't depend on hashing.
+fn read_trips(feed: &mut Feed, lines: &HashMap<String, LineID>) -> Result<BTreeMap<String, (LineID, String)>, GtfsError> {
+    let mut table = feed.table("trips.txt")?;
+    let (route, service, id) = (table.column("route_id")?, table.column("service_id")?, table.column("trip_id")?);
+
+    let mut trips = BTreeMap::new();
+    for row in table.rows() {
+        let row = row?;
+        let Some(line) = lines.get(row.get(route)) else {
+            return Err(GtfsError::UnknownRoute { file: row.file, line: row.line, route_id: row.get(route).to_string() });
+        };
+        if trips.insert(row.get(id).to_string(), (*line, row.get(service).to_string())).is_some() {
+            return Err(GtfsError::DuplicateId { file: row.file, line: row.line, column: id.name, id: row.get(id).to_string() });
+        }
+    }
+
+    Ok(trips)
+}
+
+#[derive(Debug, Clone)]
+struct Call {
+    sequence: u32,
+    stop: StopID,
+    arrival: Option<Time>,
+    departure: Option<Time>,
+}
+
+fn read_stop_times<T>(feed: &mut Feed, stops: &HashMap<String, StopID>, trips: &BTreeMap<String, T>) -> Result<HashMap<String, Vec<Call>>, GtfsError> {
+    let mut table = feed.table("stop_times.txt")?;
+    let (trip, stop, sequence) = (table.column("trip_id")?, table.column("stop_id")?, table.column("stop_sequence")?);
+    let (arrival, departure) = (table.column("arrival_time")?, table.column("departure_time")?);
+
+    let mut calls: HashMap<String, Vec<Call>> = HashMap::new();
+    for row in table.rows() {
+        let row = row?;
+        if !trips.contains_key(row.get(trip)) {
+            return Err(GtfsError::UnknownTrip { file: row.file, line: row.line, trip_id: row.get(trip).to_string() });
+        }
+        let Some(&stop_id) = stops.get(row.get(stop)) else {
+            return Err(GtfsError::UnknownStop { file: row.file, line: row.line, stop_id: row.get(stop).to_string() });
+        };
+
+        let time = |column: Column| match row.get(column) {
+            "" => Ok(None),
+            value => Time::parse(value).map(Some).ok_or_else(|| row.invalid(column, value)),
+        };
+        let call = Call { sequence: row.parse(sequence)?, stop: stop_id, arrival: time(arrival)?, departure: time(departure)? };
+        calls.entry(row.get(trip).to_string()).or_default().push(call);
+    }
+
+    Ok(calls)
+}
+
+/// Fills in stops without times by spreading the time between the surrounding timed stops evenly,
+/// then checks that the trip never goes back in time.
+fn trip_events(trip_id: &str, calls: &[Call]) -> Result<Vec<StopEvent>, GtfsError> {
+    let timed: Vec<Option<(Time, Time)>> = calls.iter()
+        .map(|call| match (call.arrival, call.departure) {
+            (Some(arrival), Some(departure)) => Some((arrival, departure)),
+            (Some(time), None) | (None, Some(time)) => Some((time, time)),
+            (None, None) => None,
+        })
+        .collect();
+
+    let backwards = |idx: usize| GtfsError::NonMonotonicTimes { trip_id: trip_id.to_string(), stop_sequence: calls[idx].sequence };
+    let mut events = Vec::with_capacity(calls.len());
+    let mut previous: Option<(usize, Time)> = None;
+
+    for (idx, times) in timed.iter().enumerate() {
+        let Some((arrival, departure)) = *times else {
+            continue;
+        };
+
+        if let Some((previous_idx, previous_departure)) = previous {
+            if arrival < previous_departure {
+                return Err(backwards(idx));
+            }
+            let gap = idx - previous_idx;
+            let step = (arrival.seconds() - previous_departure.seconds()) as f64 / gap as f64;
+            for missing in 1..gap {
+                let time = Time::from_seconds(previous_departure.seconds() + (step * missing as f64).round() as u32);
+                events.push(StopEvent { arrival: time, departure: time });
+            }
+        } else if idx > 0 {
+            return Err(GtfsError::MissingTime { trip_id: trip_id.to_string(), stop_sequence: calls[0].sequence });
+        }
+
+        if departure < arrival {
+            return Err(backwards(idx));
+        }
+        events.push(StopEvent { arrival, departure });
+        previous = Some((idx, departure));
+    }
+
+    if events.len() != calls.len() {
+        return Err(GtfsError::MissingTime { trip_id: trip_id.to_string(), stop_sequence: calls[calls.len() - 1].sequence });
+    }
+
+    Ok(events)
+}
+
+struct Frequency {
+    start: Time,
+    end: Time,
+    headway: u32,
+}
+
+/// Departures of trips running at fixed headways, exact and estimated ones alike.
+fn read_frequencies<T>(feed: &mut Feed, trips: &BTreeMap<String, T>) -> Result<HashMap<String, Vec<Frequency>>, GtfsError> {
+    let Some(mut table) = feed.optional_table("frequencies.txt")? else {
+        return Ok(HashMap::new());
+    };
+    let (trip, start, end, headway) = (table.column("trip_id")?, table.column("start_time")?, table.column("end_time")?, table.column("headway_secs")?);
+
+    let mut frequencies: HashMap<String, Vec<Frequency>> = HashMap::new();
+    for row in table.rows() {
+        let row = row?;
+        if !trips.contains_key(row.get(trip)) {
+            return Err(GtfsError::UnknownTrip { file: row.file, line: row.line, trip_id: row.get(trip).to_string() });
+        }
+        let time = |column: Column| Time::parse(row.get(column)).ok_or_else(|| row.invalid(column, row.get(column)));
+        let frequency = Frequency { start: time(start)?, end: time(end)?, headway: row.parse(headway)? };
+        if frequency.headway == 0 {
+            return Err(row.invalid(headway, row.get(headway)));
+        }
+        if frequency.end <= frequency.start {
+            return Err(GtfsError::EmptyFrequency { file: row.file, line: row.line, trip_id: row.get(trip).to_string() });
+        }
+        frequencies.entry(row.get(trip).to_string()).or_default().push(frequency);
+    }
+
+    Ok(frequencies)
+}
+
+/// Weekly patterns from `calendar.txt` and single dates from `calendar_dates.txt`, feeds may have either or both.
+fn read_calendar(feed: &mut Feed, timetable: &mut Timetable) -> Result<(), GtfsError> {
+    let date = |row: &Row, column: Column| Date::parse(row.get(column)).ok_or_else(|| row.invalid(column, row.get(column)));
+
+    let periods = feed.optional_table("calendar.txt")?;
+    let has_periods = periods.is_some();
+    if let Some(mut table) = periods {
+        let id = table.column("service_id")?;
+        let days = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"].map(|day| table.column(day));
+        let days = days.into_iter().collect::<Result<Vec<_>, _>>()?;
+        let (start, end) = (table.column("start_date")?, table.column("end_date")?);
+
+        for row in table.rows() {
+            let row = row?;
+            let mut weekdays = [false; 7];
+            for (runs, column) in weekdays.iter_mut().zip(&days) {
+                *runs = match row.get(*column) {
+                    "0" => false,
+                    "1" => true,
+                    value => return Err(row.invalid(*column, value)),
+                };
+            }
+            let period = ServicePeriod { weekdays, start: date(&row, start)?, end: date(&row, end)? };
+            timetable.calendar_mut().add_period(row.get(id).to_string(), period);
+        }
+    }
+
+    let Some(mut table) = feed.optional_table("calendar_dates.txt")? else {
+        if !has_periods {
+            warn!("feed has neither calendar.txt nor calendar_dates.txt, no trip runs on any date");
+        }
+        return Ok(());
+    };
+    let (id, day, kind) = (table.column("service_id")?, table.column("date")?, table.column("exception_type")?);
+    for row in table.rows() {
+        let row = row?;
+        let runs = match row.get(kind) {
+            "1" => true,
+            "2" => false,
+            value => return Err(row.invalid(kind, value)),
+        };
+        timetable.calendar_mut().add_exception(row.get(id).to_string(), date(&row, day)?, runs);
+    }
+
+    Ok(())
+}
+
+fn read_transfers(feed: &mut Feed, stops: &HashMap<String, StopID>, timetable: &mut Timetable) -> Result<(), GtfsError> {
+    let Some(mut table) = feed.optional_table("transfers.txt")? else {
+        return Ok(());
+    };
+    let (from, to, kind) = (table.column("from_stop_id")?, table.column("to_stop_id")?, table.column("transfer_type")?);
+    let min_time = table.optional_column("min_transfer_time");
+
+    for row in table.rows() {
+        let row = row?;
+        let stop = |column: Column| stops.get(row.get(column)).copied()
+            .ok_or_else(|| GtfsError::UnknownStop { file: row.file, line: row.line, stop_id: row.get(column).to_string() });
+        let (from, to) = (stop(from)?, stop(to)?);
+
+        let duration = match row.get(kind) {
+            // recommended and timed transfers don't need any extra time
+            "" | "0" | "1" => 0,
+            "2" => row.parse(min_time)?,
+            "3" => continue,
+            value => return Err(row.invalid(kind, value)),
+        };
+        timetable.add_transfer(Transfer { from, to, duration });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use std::collections::BTreeSet;
+
+    use crate::graph::IDIntoUSize;
+    use crate::transit::raptor::{RaptorData, RaptorQuery};
+
+    use super::*;
+
+    fn testdata() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")
+    }
+
+    /// Copy of the test feed in a fresh directory, with `file` replaced.
+    fn feed_with(name: &str, file: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raptordb-gtfs-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for entry in std::fs::read_dir(testdata()).unwrap() {
+            let entry = entry.unwrap();
+            std::fs::copy(entry.path(), dir.join(entry.file_name())).unwrap();
+        }
+        std::fs::write(dir.join(file), contents).unwrap();
+        dir
+    }
+
+    #[test]
+    fn imports_test_feed() {
+        let timetable = import_gtfs(&testdata()).unwrap();
+
+        // the station has no trips but is still a stop
+        assert_eq!(timetable.stop_count(), 6);
+        // three of them are the Sunday trips every 20 minutes
+        assert_eq!(timetable.trip_count(), 9);
+        // line 1 has a regular pattern, the overtaking express and the way back, line 2 has one pattern
+        assert_eq!(timetable.route_count(), 4);
+
+        let b = timetable.stop_by_gtfs_id("B").unwrap();
+        assert_eq!(timetable.stop(b).name, "Rynek");
+        assert_eq!(timetable.routes_at(b).len(), 4);
+
+        let regular = timetable.trip_by_gtfs_id("R1_0800").unwrap();
+        let later = timetable.trip_by_gtfs_id("R1_0830").unwrap();
+        let route = timetable.route(timetable.trip(regular).route);
+        assert_eq!(route.trips, vec![regular, later]);
+        assert_eq!(timetable.line(route.line).short_name, "1");
+        assert_eq!(timetable.trip_events(regular)[1], StopEvent { arrival: Time::from_hms(8, 10, 0), departure: Time::from_hms(8, 11, 0) });
+
+        let express = timetable.trip_by_gtfs_id("R1_0805_EXPRESS").unwrap();
+        assert_ne!(timetable.trip(express).route, timetable.trip(regular).route);
+    }
+
+    #[test]
+    fn interpolates_missing_times_and_keeps_times_after_midnight() {
+        let timetable = import_gtfs(&testdata()).unwrap();
+
+        let back = timetable.trip_by_gtfs_id("R1_BACK_0900").unwrap();
+        assert_eq!(timetable.trip_events(back)[1].arrival, Time::from_hms(9, 10, 0));
+
+        let night = timetable.trip_by_gtfs_id("R2_NIGHT").unwrap();
+        assert_eq!(timetable.trip_events(night)[2].arrival, Time::from_hms(24, 20, 0));
+        assert_eq!(timetable.trip(night).service_id, "WD");
+    }
+
+    #[test]
+    fn imports_transfers() {
+        let timetable = import_gtfs(&testdata()).unwrap();
+        let [b, c, d] = ["B", "C", "D"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+
+        assert_eq!(timetable.transfers_from(b).collect::<Vec<_>>(), vec![&Transfer { from: b, to: b, duration: 120 }]);
+        assert_eq!(timetable.transfers_from(c).collect::<Vec<_>>(), vec![&Transfer { from: c, to: d, duration: 180 }]);
+    }
+
+    #[test]
+    fn expands_frequencies() {
+        let timetable = import_gtfs(&testdata()).unwrap();
+        assert_eq!(timetable.trip_by_gtfs_id("R1_BACK_FREQ"), None);
+
+        let departures: Vec<_> = ["10:00:00", "10:20:00", "10:40:00"].iter().map(|time| {
+            let trip = timetable.trip_by_gtfs_id(&format!("R1_BACK_FREQ@{time}")).unwrap();
+            assert_eq!(timetable.trip(trip).service_id, "SUN");
+            let events = timetable.trip_events(trip);
+            assert_eq!(events[2].arrival.seconds() - events[0].departure.seconds(), 20 * 60);
+            // the first stop is reached two minutes before leaving it
+            assert_eq!(events[0].departure.seconds() - events[0].arrival.seconds(), 2 * 60);
+            events[0].departure
+        }).collect();
+        assert_eq!(departures, vec![Time::from_hms(10, 0, 0), Time::from_hms(10, 20, 0), Time::from_hms(10, 40, 0)]);
+        assert_eq!(timetable.trip_by_gtfs_id("R1_BACK_FREQ@11:00:00"), None);
+
+        // they run the same way as the weekday trip back
+        let back = timetable.trip_by_gtfs_id("R1_BACK_0900").unwrap();
+        assert_eq!(timetable.trip(timetable.trip_by_gtfs_id("R1_BACK_FREQ@10:20:00").unwrap()).route, timetable.trip(back).route);
+    }
+
+    #[test]
+    fn imports_calendar_and_exceptions() {
+        let timetable = import_gtfs(&testdata()).unwrap();
+        let calendar = timetable.calendar();
+        let date = |text: &str| Date::parse(text).unwrap();
+
+        assert_eq!(calendar.active_services(date("20260504")), BTreeSet::from(["WD"]));
+        assert_eq!(calendar.active_services(date("20260503")), BTreeSet::from(["SUN"]));
+        // a public holiday on a Friday runs the Sunday service
+        assert_eq!(calendar.active_services(date("20260501")), BTreeSet::from(["SUN"]));
+        assert_eq!(calendar.active_services(date("20260502")), BTreeSet::new());
+        assert_eq!(calendar.active_services(date("20270104")), BTreeSet::new());
+    }
+
+    #[test]
+    fn timetable_of_a_day_includes_trips_from_the_evening_before() {
+        let timetable = import_gtfs(&testdata()).unwrap();
+        let [a, b, d, e] = ["A", "B", "D", "E"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+        let trips = |day: &Timetable| {
+            let mut trips: Vec<_> = day.trips().map(|trip| day.trip(trip).gtfs_id.clone()).collect();
+            trips.sort();
+            trips
+        };
+
+        let monday = timetable.for_date(Date::parse("20260504").unwrap());
+        assert_eq!(trips(&monday), ["R1_0800", "R1_0805_EXPRESS", "R1_0830", "R1_BACK_0900", "R2_0815", "R2_NIGHT"]);
+        // Sunday's service has no night tram
+        let night = monday.trip_by_gtfs_id("R2_NIGHT").unwrap();
+        assert_eq!(monday.trip_events(night)[0].departure, Time::from_hms(23, 50, 0));
+
+        // the night tram of Thursday is still on its way after midnight of the holiday
+        let holiday = timetable.for_date(Date::parse("20260501").unwrap());
+        assert_eq!(trips(&holiday), ["R1_BACK_FREQ@10:00:00", "R1_BACK_FREQ@10:20:00", "R1_BACK_FREQ@10:40:00", "R2_NIGHT"]);
+        let night = holiday.trip_by_gtfs_id("R2_NIGHT").unwrap();
+        assert_eq!(holiday.route(holiday.trip(night).route).stops, vec![d, e]);
+        assert_eq!(holiday.trip_events(night)[1].arrival, Time::from_hms(0, 20, 0));
+        assert_eq!(holiday.trip(night).service_date(), Date::parse("20260430"));
+
+        let data = RaptorData::new(&holiday);
+        let query = |from: StopID, departure: Time| data.query(&RaptorQuery { sources: vec![(from, 0)], departure, max_transfers: 2 });
+        assert_eq!(query(d, Time::from_hms(0, 0, 0)).earliest_arrival(e), Some(Time::from_hms(0, 20, 0)));
+        assert_eq!(query(b, Time::from_hms(8, 0, 0)).earliest_arrival(e), None);
+        assert_eq!(query(b, Time::from_hms(9, 0, 0)).earliest_arrival(a), Some(Time::from_hms(10, 20, 0)));
+
+        // nothing runs on the Saturday after, not even the night tram of the Friday
+        assert_eq!(timetable.for_date(Date::parse("20260502").unwrap()).trip_count(), 0);
+    }
+
+    #[test]
+    fn reports_bad_calendars_and_frequencies() {
+        let dir = feed_with("calendar-date", "calendar_dates.txt", "service_id,date,exception_type
+WD,2026-05-01,2
+");
+        let err = import_gtfs(&dir).unwrap_err();
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(matches!(&err, GtfsError::InvalidValue { file: "calendar_dates.txt", line: 2, .. }), "{err:?}");
+
+        let dir = feed_with("headway", "frequencies.txt", "trip_id,start_time,end_time,headway_secs
+R1_BACK_FREQ,10:00:00,11:00:00,0
+");
+        let err = import_gtfs(&dir).unwrap_err();
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(matches!(&err, GtfsError::InvalidValue { file: "frequencies.txt", line: 2, .. }), "{err:?}");
+
+        let dir = feed_with("frequency-trip", "frequencies.txt", "trip_id,start_time,end_time,headway_secs
+X,10:00:00,11:00:00,600
+");
+        let err = import_gtfs(&dir).unwrap_err();
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(matches!(&err, GtfsError::UnknownTrip { file: "frequencies.txt", line: 2, trip_id } if trip_id == "X"), "{err:?}");
+    }
+
+    #[test]
+    fn zip_and_directory_give_the_same_timetable() {
+        let path = std::env::temp_dir().join(format!("raptordb-gtfs-{}.zip", std::process::id()));
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        for entry in std::fs::read_dir(testdata()).unwrap() {
+            let entry = entry.unwrap();
+            zip.start_file(entry.file_name().to_string_lossy(), zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(&std::fs::read(entry.path()).unwrap()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let from_zip = import_gtfs(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(from_zip, import_gtfs(&testdata()).unwrap());
+    }
+
+    #[test]
+    fn reports_unknown_stops() {
+        let dir = feed_with("unknown-stop", "stop_times.txt", "trip_id,arrival_time,departure_time,stop_id,stop_sequence\nR1_0800,08:00:00,08:00:00,A,1\nR1_0800,08:10:00,08:10:00,X,2\n");
+        let err = import_gtfs(&dir).unwrap_err();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert!(matches!(&err, GtfsError::UnknownStop { file: "stop_times.txt", line: 3, stop_id } if stop_id == "X"), "{err:?}");
+        assert_eq!(err.to_string(), "stop_times.txt:3: unknown stop_id \"X\"");
+    }
+
+    #[test]
+    fn reports_times_going_backwards() {
+        let dir = feed_with("backwards", "stop_times.txt", "trip_id,arrival_time,departure_time,stop_id,stop_sequence\nR1_0800,08:00:00,08:00:00,A,1\nR1_0800,07:50:00,07:50:00,B,2\n");
+        let err = import_gtfs(&dir).unwrap_err();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert!(matches!(&err, GtfsError::NonMonotonicTimes { trip_id, stop_sequence: 2 } if trip_id == "R1_0800"), "{err:?}");
+    }
+
+    #[test]
+    fn reports_missing_files_and_columns() {
+        let dir = feed_with("missing-column", "routes.txt", "route_id,route_short_name\nR1,1\n");
+        let err = import_gtfs(&dir).unwrap_err();
+        assert!(matches!(err, GtfsError::MissingColumn { file: "routes.txt", column: "route_type" }), "{err:?}");
+
+        std::fs::remove_file(dir.join("routes.txt")).unwrap();
+        let err = import_gtfs(&dir).unwrap_err();
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(matches!(err, GtfsError::MissingFile { file: "routes.txt" }), "{err:?}");
+    }
+
+    #[test]
+    fn stop_ids_follow_the_file() {
+        let timetable = import_gtfs(&testdata()).unwrap();
+        assert_eq!(timetable.stop_by_gtfs_id("A").map(|id| id.as_usize()), Some(0));
+        assert_eq!(timetable.stop_by_gtfs_id("ST").map(|id| id.as_usize()), Some(5));
+    }
+}
@@ -0,0 +1,419 @@
+use std::io;
+use std::path::Path;
+
+use derive_more::{Display, Error, From};
+
+/// Everything that can go wrong decoding a feed, offsets count bytes from the start of the buffer.
+#[derive(Debug, Display, Error, From)]
+pub enum GtfsRtError {
+    #[display("couldn't read feed: {_0}")]
+    Io(io::Error),
+    #[display("feed ends in the middle of a field at byte {offset}")]
+    #[from(ignore)]
+    Truncated { offset: usize },
+    #[display("malformed feed at byte {offset}: {reason}")]
+    #[from(ignore)]
+    Malformed { offset: usize, reason: &'static str },
+    #[display("{message} has no {field}")]
+    #[from(ignore)]
+    MissingField { message: &'static str, field: &'static str },
+}
+
+/// GTFS-Realtime feed, only trip updates are kept, vehicle positions and alerts are skipped.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeedMessage {
+    pub header: FeedHeader,
+    pub entities: Vec<FeedEntity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeedHeader {
+    pub gtfs_realtime_version: String,
+    /// Whether the feed has every update or only the changed ones.
+    pub incremental: bool,
+    /// POSIX time the feed was created at.
+    pub timestamp: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeedEntity {
+    pub id: String,
+    /// Only used by incremental feeds, the update with this ID is gone.
+    pub is_deleted: bool,
+    pub trip_update: Option<TripUpdate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TripUpdate {
+    pub trip: TripDescriptor,
+    /// Ordered by stop sequence.
+    pub stop_time_updates: Vec<StopTimeUpdate>,
+    pub timestamp: Option<u64>,
+    /// Seconds, for the stops without a prediction of their own.
+    pub delay: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TripDescriptor {
+    pub trip_id: Option<String>,
+    pub route_id: Option<String>,
+    pub direction_id: Option<u32>,
+    /// `HH:MM:SS`, for trips running at a frequency.
+    pub start_time: Option<String>,
+    /// `YYYYMMDD`, the service day of the trip.
+    pub start_date: Option<String>,
+    pub schedule_relationship: TripRelationship,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TripRelationship {
+    #[default]
+    Scheduled,
+    Added,
+    Unscheduled,
+    Canceled,
+    Replacement,
+    Duplicated,
+    Deleted,
+    New,
+    /// A value this decoder doesn't know.
+    Other(u64),
+}
+
+impl From<u64> for TripRelationship {
+    fn from(value: u64) -> Self {
+        match value {
+            0 => Self::Scheduled,
+            1 => Self::Added,
+            2 => Self::Unscheduled,
+            3 => Self::Canceled,
+            5 => Self::Replacement,
+            6 => Self::Duplicated,
+            7 => Self::Deleted,
+            8 => Self::New,
+            other => Self::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StopTimeUpdate {
+    pub stop_sequence: Option<u32>,
+    pub stop_id: Option<String>,
+    pub arrival: Option<StopTimeEvent>,
+    pub departure: Option<StopTimeEvent>,
+    pub schedule_relationship: StopRelationship,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StopRelationship {
+    #[default]
+    Scheduled,
+    Skipped,
+    /// Nothing is known about the stop, it keeps its planned times.
+    NoData,
+    Unscheduled,
+    Other(u64),
+}
+
+impl From<u64> for StopRelationship {
+    fn from(value: u64) -> Self {
+        match value {
+            0 => Self::Scheduled,
+            1 => Self::Skipped,
+            2 => Self::NoData,
+            3 => Self::Unscheduled,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Prediction for one arrival or departure, as a delay or as a POSIX time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StopTimeEvent {
+    pub delay: Option<i32>,
+    pub time: Option<i64>,
+    pub uncertainty: Option<i32>,
+}
+
+/// Reads a feed saved to a file, e.g. a recorded response of a GTFS-Realtime endpoint.
+pub fn read_feed(path: &Path) -> Result<FeedMessage, GtfsRtError> {
+    decode_feed(&std::fs::read(path)?)
+}
+
+/// Decodes the protobuf encoding of a `FeedMessage`, unknown fields and extensions are skipped.
+pub fn decode_feed(bytes: &[u8]) -> Result<FeedMessage, GtfsRtError> {
+    let feed = FeedMessage::decode(Reader { bytes, offset: 0 })?;
+    if feed.header.gtfs_realtime_version.is_empty() {
+        return Err(GtfsRtError::MissingField { message: "FeedHeader", field: "gtfs_realtime_version" });
+    }
+    Ok(feed)
+}
+
+/// Bytes of one message, `offset` is where they start in the whole feed.
+#[derive(Clone, Copy)]
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+/// Value of a field by wire type, GTFS-Realtime has no fixed size fields and no groups.
+enum Field<'a> {
+    Varint(u64),
+    Bytes(Reader<'a>),
+    /// Skipped 32 or 64 bit value, of an extension for example.
+    Fixed,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn varint(&mut self) -> Result<u64, GtfsRtError> {
+        let mut value = 0;
+        for (i, byte) in self.bytes.iter().enumerate().take(10) {
+            value |= u64::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                self.advance(i + 1);
+                return Ok(value);
+            }
+        }
+        match self.bytes.len() {
+            ..10 => Err(GtfsRtError::Truncated { offset: self.offset + self.bytes.len() }),
+            _ => Err(GtfsRtError::Malformed { offset: self.offset, reason: "varint longer than 10 bytes" }),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<Reader<'a>, GtfsRtError> {
+        if self.bytes.len() < len {
+            return Err(GtfsRtError::Truncated { offset: self.offset + self.bytes.len() });
+        }
+        let taken = Reader { bytes: &self.bytes[..len], offset: self.offset };
+        self.advance(len);
+        Ok(taken)
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.bytes = &self.bytes[len..];
+        self.offset += len;
+    }
+
+    /// Field number and value of the next field.
+    fn field(&mut self) -> Result<(u64, Field<'a>), GtfsRtError> {
+        let offset = self.offset;
+        let key = self.varint()?;
+        let field = match key & 7 {
+            0 => Field::Varint(self.varint()?),
+            1 => self.take(8).map(|_| Field::Fixed)?,
+            2 => {
+                let len = self.varint()?;
+                Field::Bytes(self.take(usize::try_from(len).unwrap_or(usize::MAX))?)
+            }
+            5 => self.take(4).map(|_| Field::Fixed)?,
+            _ => return Err(GtfsRtError::Malformed { offset, reason: "unsupported wire type" }),
+        };
+        match key >> 3 {
+            0 => Err(GtfsRtError::Malformed { offset, reason: "field number 0" }),
+            number => Ok((number, field)),
+        }
+    }
+}
+
+impl<'a> Field<'a> {
+    fn varint(self, offset: usize) -> Result<u64, GtfsRtError> {
+        match self {
+            Field::Varint(value) => Ok(value),
+            _ => Err(GtfsRtError::Malformed { offset, reason: "expected a varint" }),
+        }
+    }
+
+    /// `int32` and `int64` are sign extended to 64 bits, negative values take 10 bytes.
+    fn int(self, offset: usize) -> Result<i64, GtfsRtError> {
+        self.varint(offset).map(|value| value as i64)
+    }
+
+    fn bytes(self, offset: usize) -> Result<Reader<'a>, GtfsRtError> {
+        match self {
+            Field::Bytes(reader) => Ok(reader),
+            _ => Err(GtfsRtError::Malformed { offset, reason: "expected a length delimited field" }),
+        }
+    }
+
+    fn string(self, offset: usize) -> Result<String, GtfsRtError> {
+        let reader = self.bytes(offset)?;
+        String::from_utf8(reader.bytes.to_vec()).map_err(|_| GtfsRtError::Malformed { offset: reader.offset, reason: "invalid UTF-8" })
+    }
+
+    fn message<M: Message>(self, offset: usize) -> Result<M, GtfsRtError> {
+        M::decode(self.bytes(offset)?)
+    }
+}
+
+/// Decoding of one protobuf message type, fields that are repeated more often than they should be overwrite each other.
+trait Message: Default {
+    /// Takes in one field, `offset` is where its key starts.
+    fn merge(&mut self, number: u64, field: Field<'_>, offset: usize) -> Result<(), GtfsRtError>;
+
+    fn decode(mut reader: Reader<'_>) -> Result<Self, GtfsRtError> {
+        let mut message = Self::default();
+        while !reader.is_empty() {
+            let offset = reader.offset;
+            let (number, field) = reader.field()?;
+            message.merge(number, field, offset)?;
+        }
+        Ok(message)
+    }
+}
+
+impl Message for FeedMessage {
+    fn merge(&mut self, number: u64, field: Field<'_>, offset: usize) -> Result<(), GtfsRtError> {
+        match number {
+            1 => self.header = field.message(offset)?,
+            2 => {
+                let entity: FeedEntity = field.message(offset)?;
+                if entity.id.is_empty() {
+                    return Err(GtfsRtError::MissingField { message: "FeedEntity", field: "id" });
+                }
+                self.entities.push(entity);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Message for FeedHeader {
+    fn merge(&mut self, number: u64, field: Field<'_>, offset: usize) -> Result<(), GtfsRtError> {
+        match number {
+            1 => self.gtfs_realtime_version = field.string(offset)?,
+            2 => self.incremental = field.varint(offset)? == 1,
+            3 => self.timestamp = Some(field.varint(offset)?),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Message for FeedEntity {
+    fn merge(&mut self, number: u64, field: Field<'_>, offset: usize) -> Result<(), GtfsRtError> {
+        match number {
+            1 => self.id = field.string(offset)?,
+            2 => self.is_deleted = field.varint(offset)? != 0,
+            3 => self.trip_update = Some(field.message(offset)?),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Message for TripUpdate {
+    fn merge(&mut self, number: u64, field: Field<'_>, offset: usize) -> Result<(), GtfsRtError> {
+        match number {
+            1 => self.trip = field.message(offset)?,
+            2 => self.stop_time_updates.push(field.message(offset)?),
+            4 => self.timestamp = Some(field.varint(offset)?),
+            5 => self.delay = Some(field.int(offset)? as i32),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Message for TripDescriptor {
+    fn merge(&mut self, number: u64, field: Field<'_>, offset: usize) -> Result<(), GtfsRtError> {
+        match number {
+            1 => self.trip_id = Some(field.string(offset)?),
+            2 => self.start_time = Some(field.string(offset)?),
+            3 => self.start_date = Some(field.string(offset)?),
+            4 => self.schedule_relationship = field.varint(offset)?.into(),
+            5 => self.route_id = Some(field.string(offset)?),
+            6 => self.direction_id = Some(field.varint(offset)? as u32),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Message for StopTimeUpdate {
+    fn merge(&mut self, number: u64, field: Field<'_>, offset: usize) -> Result<(), GtfsRtError> {
+        match number {
+            1 => self.stop_sequence = Some(field.varint(offset)? as u32),
+            2 => self.arrival = Some(field.message(offset)?),
+            3 => self.departure = Some(field.message(offset)?),
+            4 => self.stop_id = Some(field.string(offset)?),
+            5 => self.schedule_relationship = field.varint(offset)?.into(),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Message for StopTimeEvent {
+    fn merge(&mut self, number: u64, field: Field<'_>, offset: usize) -> Result<(), GtfsRtError> {
+        match number {
+            1 => self.delay = Some(field.int(offset)? as i32),
+            2 => self.time = Some(field.int(offset)?),
+            3 => self.uncertainty = Some(field.int(offset)? as i32),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-rt-testdata/trip_updates.pb")
+    }
+
+    #[test]
+    fn decodes_recorded_feed() {
+        let feed = read_feed(&fixture()).unwrap();
+        assert_eq!(feed.header, FeedHeader { gtfs_realtime_version: "2.0".into(), incremental: false, timestamp: Some(1_777_875_600) });
+        let ids: Vec<_> = feed.entities.iter().map(|entity| entity.id.as_str()).collect();
+        assert_eq!(ids, ["late-bus", "cancelled-express", "late-tram", "extra-tram", "tomorrow", "unknown", "vehicle", "sunday-frequency"]);
+        assert_eq!(feed.entities[6].trip_update, None);
+
+        let late = feed.entities[0].trip_update.as_ref().unwrap();
+        assert_eq!(late.trip.trip_id.as_deref(), Some("R1_0800"));
+        assert_eq!(late.trip.start_date.as_deref(), Some("20260504"));
+        assert_eq!(late.stop_time_updates, vec![StopTimeUpdate {
+            stop_sequence: Some(2),
+            stop_id: Some("B".into()),
+            arrival: Some(StopTimeEvent { delay: Some(35 * 60), time: None, uncertainty: None }),
+            departure: None,
+            schedule_relationship: StopRelationship::Scheduled,
+        }]);
+
+        let cancelled = feed.entities[1].trip_update.as_ref().unwrap();
+        assert_eq!(cancelled.trip.schedule_relationship, TripRelationship::Canceled);
+        // negative numbers take all ten bytes
+        assert_eq!(feed.entities[2].trip_update.as_ref().unwrap().delay, Some(-60));
+        let added = feed.entities[3].trip_update.as_ref().unwrap();
+        assert_eq!((added.trip.schedule_relationship, added.trip.route_id.as_deref()), (TripRelationship::Added, Some("R2")));
+        let frequency = feed.entities[7].trip_update.as_ref().unwrap();
+        assert_eq!((frequency.trip.trip_id.as_deref(), frequency.trip.start_time.as_deref()), (Some("R1_BACK_FREQ"), Some("10:20:00")));
+    }
+
+    #[test]
+    fn reports_broken_feeds() {
+        let bytes = std::fs::read(fixture()).unwrap();
+        let err = decode_feed(&bytes[..100]).unwrap_err();
+        assert!(matches!(err, GtfsRtError::Truncated { offset: 100 }), "{err:?}");
+
+        // an entity without a header
+        let err = decode_feed(&[0x12, 0x03, 0x0a, 0x01, b'x']).unwrap_err();
+        assert_eq!(err.to_string(), "FeedHeader has no gtfs_realtime_version");
+
+        // the version as a varint
+        let err = decode_feed(&[0x0a, 0x02, 0x08, 0x02]).unwrap_err();
+        assert!(matches!(err, GtfsRtError::Malformed { offset: 2, reason: "expected a length delimited field" }), "{err:?}");
+
+        let err = decode_feed(&[0x0b]).unwrap_err();
+        assert!(matches!(err, GtfsRtError::Malformed { offset: 0, reason: "unsupported wire type" }), "{err:?}");
+        assert!(matches!(read_feed(Path::new("/nonexistent.pb")), Err(GtfsRtError::Io(_))));
+    }
+}
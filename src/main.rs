@@ -5,6 +5,9 @@ use crate::{exporter::export_geojson, graph::NodeID, importer::{import_pbf, impo
 mod graph;
 mod importer;
 mod exporter;
+mod routing;
+mod partition;
+mod transit;
 
 fn main() {
     simple_logger::init().expect("couldnt init logger");
@@ -0,0 +1,309 @@
+use std::collections::{HashSet, VecDeque};
+use std::f64::consts::PI;
+
+use log::{debug, info};
+
+use crate::graph::{EdgeID, GraphRead, IDIntoUSize, NodeID};
+use crate::importer::HasCoordinates;
+use crate::routing::crp::{MultilevelPartition, NO_CELL};
+
+/// Cell of every node, cells are numbered in bisection order, so neighbouring IDs are usually close together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partition {
+    /// Indexed by `NodeID`, `NO_CELL` for IDs that aren't nodes.
+    cells: Vec<u32>,
+    cell_count: usize,
+}
+
+impl Partition {
+    pub fn cell(&self, node: NodeID) -> u32 {
+        self.cells[node.as_usize()]
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.cell_count
+    }
+
+    /// Nodes of every cell. Concatenated they give a node order that keeps cells together, e.g. for cache friendly storage.
+    pub fn members(&self) -> Vec<Vec<NodeID>> {
+        let mut members = vec![Vec::new(); self.cell_count];
+        for (idx, &cell) in self.cells.iter().enumerate() {
+            if cell != NO_CELL {
+                members[cell as usize].push(NodeID::from_usize(idx));
+            }
+        }
+        members
+    }
+}
+
+/// Recursive bisection, each cut being the smallest one found by max-flow between the two ends of the nodes
+/// projected on one of several lines through the map. Edge directions are ignored, every edge counts as one.
+#[derive(Debug, Clone, Copy)]
+pub struct InertialFlow {
+    /// Share of the nodes at each end of the projection used as sources and sinks, which also bounds how unbalanced a cut can get.
+    pub balance: f64,
+    /// Number of lines tried per bisection, evenly spread over half a circle.
+    pub directions: usize,
+}
+
+impl Default for InertialFlow {
+    fn default() -> Self {
+        Self { balance: 0.25, directions: 4 }
+    }
+}
+
+impl InertialFlow {
+    /// Bisects until no cell has more than `max_cell_size` nodes.
+    pub fn partition<G>(&self, graph: &G, max_cell_size: usize) -> Partition where
+        G: GraphRead + Sync,
+        G::Node: HasCoordinates {
+        let cells = self.split(graph, graph.nodes().collect(), max_cell_size);
+        info!("partitioned {} nodes into {} cells", graph.node_count(), cells.len());
+        to_partition(graph, &cells)
+    }
+
+    /// Nested partition for overlay routing, `max_cell_sizes` goes from the finest level to the coarsest.
+    /// Every level splits the cells of the one above, so cells are nested by construction.
+    pub fn multilevel<G>(&self, graph: &G, max_cell_sizes: &[usize]) -> MultilevelPartition where
+        G: GraphRead + Sync,
+        G::Node: HasCoordinates {
+        debug_assert!(max_cell_sizes.is_sorted(), "cell sizes have to grow towards the coarser levels");
+
+        let mut cells = vec![graph.nodes().collect::<Vec<_>>()];
+        let mut levels = Vec::with_capacity(max_cell_sizes.len());
+        for &max_cell_size in max_cell_sizes.iter().rev() {
+            cells = cells.into_iter().flat_map(|cell| self.split(graph, cell, max_cell_size)).collect();
+            levels.push(to_partition(graph, &cells).cells);
+        }
+
+        levels.reverse();
+        MultilevelPartition::new(levels)
+    }
+
+    fn split<G>(&self, graph: &G, nodes: Vec<NodeID>, max_cell_size: usize) -> Vec<Vec<NodeID>> where
+        G: GraphRead + Sync,
+        G::Node: HasCoordinates {
+        assert!(max_cell_size > 0, "cells must be allowed to contain nodes");
+        if nodes.len() <= max_cell_size {
+            return vec![nodes];
+        }
+
+        let (left, right) = self.bisect(graph, nodes);
+        let (mut left, right) = rayon::join(
+            || self.split(graph, left, max_cell_size),
+            || self.split(graph, right, max_cell_size),
+        );
+        left.extend(right);
+        left
+    }
+
+    /// Tries every direction and keeps the smallest cut, the more balanced one on ties.
+    fn bisect<G>(&self, graph: &G, nodes: Vec<NodeID>) -> (Vec<NodeID>, Vec<NodeID>) where
+        G: GraphRead,
+        G::Node: HasCoordinates {
+        let flow = FlowNetwork::new(graph, &nodes);
+        let terminals = ((nodes.len() as f64 * self.balance) as usize).clamp(1, nodes.len() / 2);
+        let latitude = graph.get_node(nodes[0]).lat().to_radians().cos();
+
+        let mut best: Option<(usize, usize, Vec<bool>)> = None;
+        for direction in 0..self.directions.max(1) {
+            let angle = PI * direction as f64 / self.directions.max(1) as f64;
+            let (sin, cos) = angle.sin_cos();
+            let projection = |idx: usize| {
+                let node = graph.get_node(nodes[idx]);
+                node.lon() * latitude * cos + node.lat() * sin
+            };
+
+            let mut order: Vec<usize> = (0..nodes.len()).collect();
+            order.sort_by(|a, b| projection(*a).total_cmp(&projection(*b)));
+
+            let (cut, side) = flow.min_cut(&order[..terminals], &order[order.len() - terminals..]);
+            let imbalance = side.iter().filter(|in_source| **in_source).count().abs_diff(nodes.len() / 2);
+            if best.as_ref().is_none_or(|(best_cut, best_imbalance, _)| (cut, imbalance) < (*best_cut, *best_imbalance)) {
+                best = Some((cut, imbalance, side));
+            }
+        }
+
+        let (cut, _, side) = best.expect("at least one direction is tried");
+        debug!("bisected {} nodes with a cut of {cut} edges", nodes.len());
+
+        let (left, right): (Vec<_>, Vec<_>) = nodes.into_iter().zip(side).partition(|(_, in_source)| *in_source);
+        (left.into_iter().map(|(node, _)| node).collect(), right.into_iter().map(|(node, _)| node).collect())
+    }
+}
+
+fn to_partition<G: GraphRead>(graph: &G, cells: &[Vec<NodeID>]) -> Partition {
+    let mut partition = vec![NO_CELL; graph.node_id_bound()];
+    for (cell, nodes) in cells.iter().enumerate() {
+        for node in nodes {
+            partition[node.as_usize()] = cell as u32;
+        }
+    }
+    Partition { cells: partition, cell_count: cells.len() }
+}
+
+/// Unit capacity network over the edges between a set of nodes, nodes are referred to by their index in that set.
+struct FlowNetwork {
+    /// Arcs leaving every node as `(to, arc index)`.
+    adjacency: Vec<Vec<(usize, usize)>>,
+}
+
+impl FlowNetwork {
+    fn new<G: GraphRead>(graph: &G, nodes: &[NodeID]) -> Self {
+        let mut local = vec![usize::MAX; graph.node_id_bound()];
+        for (idx, node) in nodes.iter().enumerate() {
+            local[node.as_usize()] = idx;
+        }
+
+        let mut adjacency = vec![Vec::new(); nodes.len()];
+        let mut seen: HashSet<EdgeID> = HashSet::new();
+        let mut arcs = 0;
+        for (idx, &node) in nodes.iter().enumerate() {
+            for edge in graph.get_outgoing_edges(node).into_iter().chain(graph.get_incoming_edges(node)) {
+                let other = local[graph.get_connected_nodes(edge).opposite(node).as_usize()];
+                if other == usize::MAX || other == idx || !seen.insert(edge) {
+                    continue;
+                }
+
+                // an undirected unit edge is a pair of arcs that are each other's reverse, arc `i` pairs with `i ^ 1`
+                adjacency[idx].push((other, arcs));
+                adjacency[other].push((idx, arcs + 1));
+                arcs += 2;
+            }
+        }
+
+        Self { adjacency }
+    }
+
+    /// Pushes flow from `sources` to `sinks` along shortest augmenting paths until there are none left.
+    /// Returns the cut size and which nodes are still reachable from the sources, i.e. the source side of the cut.
+    fn min_cut(&self, sources: &[usize], sinks: &[usize]) -> (usize, Vec<bool>) {
+        let arcs = self.adjacency.iter().map(Vec::len).sum::<usize>();
+        let mut flow = vec![0i8; arcs];
+        let mut is_sink = vec![false; self.adjacency.len()];
+        for &sink in sinks {
+            is_sink[sink] = true;
+        }
+
+        let mut cut = 0;
+        loop {
+            let (reached, parents) = self.residual_search(sources, &flow);
+            let Some(sink) = sinks.iter().copied().find(|sink| reached[*sink]) else {
+                return (cut, reached);
+            };
+
+            let mut current = sink;
+            while let Some((parent, arc)) = parents[current] {
+                flow[arc] += 1;
+                flow[arc ^ 1] -= 1;
+                current = parent;
+            }
+            cut += 1;
+        }
+    }
+
+    fn residual_search(&self, sources: &[usize], flow: &[i8]) -> (Vec<bool>, Vec<Option<(usize, usize)>>) {
+        let mut reached = vec![false; self.adjacency.len()];
+        let mut parents = vec![None; self.adjacency.len()];
+        let mut queue: VecDeque<usize> = sources.iter().copied().collect();
+        for &source in sources {
+            reached[source] = true;
+        }
+
+        while let Some(node) = queue.pop_front() {
+            for &(next, arc) in &self.adjacency[node] {
+                if !reached[next] && flow[arc] < 1 {
+                    reached[next] = true;
+                    parents[next] = Some((node, arc));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        (reached, parents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{EdgeKind, Graph};
+    use crate::importer::{GraphNode, GraphWay};
+    use crate::routing::{ByDistance, crp::Overlay, dijkstra::dijkstra, test_graphs::grid};
+
+    use super::*;
+
+    fn cut_edges<G: GraphRead>(graph: &G, partition: &Partition) -> usize {
+        graph.edges()
+            .filter(|edge| {
+                let nodes = graph.get_connected_nodes(*edge);
+                partition.cell(nodes.from) != partition.cell(nodes.to)
+            })
+            .count()
+    }
+
+    #[test]
+    fn cells_are_bounded_and_cover_every_node() {
+        let (graph, nodes) = grid(16, 16);
+        let partition = InertialFlow::default().partition(&graph, 40);
+
+        let members = partition.members();
+        assert_eq!(members.len(), partition.cell_count());
+        assert!(members.iter().all(|cell| !cell.is_empty() && cell.len() <= 40), "cell sizes: {:?}", members.iter().map(Vec::len).collect::<Vec<_>>());
+
+        let mut covered: Vec<NodeID> = members.into_iter().flatten().collect();
+        covered.sort();
+        assert_eq!(covered, nodes);
+    }
+
+    #[test]
+    fn cuts_a_grid_along_straight_lines() {
+        let (graph, _) = grid(16, 16);
+        let partition = InertialFlow::default().partition(&graph, 128);
+
+        // straight cuts across the grid or what's left of it never cost more than 16 edges
+        let cut = cut_edges(&graph, &partition);
+        assert!(cut <= 16 * (partition.cell_count() - 1), "{cut} edges cut for {} cells", partition.cell_count());
+    }
+
+    #[test]
+    fn separates_clusters_at_the_bridge() {
+        let mut graph = Graph::<GraphNode, GraphWay>::new();
+        let mut clusters = Vec::new();
+        for offset in [0.0, 0.1] {
+            let nodes: Vec<_> = (0..36).map(|i| graph.add_node(GraphNode::new(50.0 + (i / 6) as f64 * 0.001, 20.0 + offset + (i % 6) as f64 * 0.001))).collect();
+            for i in 0..36 {
+                if i % 6 < 5 {
+                    graph.add_edge(nodes[i], nodes[i + 1], GraphWay::new(70.0), EdgeKind::Undirected);
+                }
+                if i + 6 < 36 {
+                    graph.add_edge(nodes[i], nodes[i + 6], GraphWay::new(111.0), EdgeKind::Undirected);
+                }
+            }
+            clusters.push(nodes);
+        }
+        graph.add_edge(clusters[0][17], clusters[1][12], GraphWay::new(7000.0), EdgeKind::Directed);
+
+        let partition = InertialFlow::default().partition(&graph, 36);
+        assert_eq!(partition.cell_count(), 2);
+        assert_eq!(cut_edges(&graph, &partition), 1);
+        for cluster in &clusters {
+            assert!(cluster.iter().all(|node| partition.cell(*node) == partition.cell(cluster[0])));
+        }
+    }
+
+    #[test]
+    fn multilevel_partition_drives_the_overlay() {
+        let (graph, nodes) = grid(20, 20);
+        let partition = InertialFlow::default().multilevel(&graph, &[16, 64, 200]);
+        assert_eq!(partition.level_count(), 3);
+        assert!(partition.cell_count(0) > partition.cell_count(1) && partition.cell_count(1) > partition.cell_count(2));
+
+        let overlay = Overlay::new(&graph, partition);
+        let customization = overlay.customize(&graph, &ByDistance);
+        for (source, target) in [(0, 399), (21, 378), (399, 5), (190, 209)] {
+            let expected = dijkstra(&graph, nodes[source], nodes[target], &ByDistance).path.unwrap().cost;
+            let cost = overlay.query(&graph, &customization, &ByDistance, nodes[source], nodes[target]).path.unwrap().cost;
+            assert!((expected - cost).abs() < 1e-6, "{source} -> {target}: expected {expected}, got {cost}");
+        }
+    }
+}
@@ -0,0 +1,89 @@
+pub mod dijkstra;
+pub mod astar;
+pub mod bidirectional;
+pub mod ch;
+pub mod alt;
+pub mod crp;
+pub mod matrix;
+pub mod isochrone;
+pub mod alternatives;
+pub mod nearest;
+
+use crate::{graph::{EdgeID, GraphRead, NodeID}, importer::GraphWay};
+
+/// Turns an edge property into the cost of traversing it. Costs must not be negative,
+/// `f64::INFINITY` marks an edge as impassable.
+pub trait EdgeCost<E> {
+    fn cost(&self, edge: &E) -> f64;
+}
+
+impl<E, F> EdgeCost<E> for F where F: Fn(&E) -> f64 {
+    fn cost(&self, edge: &E) -> f64 {
+        self(edge)
+    }
+}
+
+/// Cost of a `GraphWay` is its length in metres.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByDistance;
+
+impl EdgeCost<GraphWay> for ByDistance {
+    fn cost(&self, edge: &GraphWay) -> f64 {
+        edge.distance()
+    }
+}
+
+/// Lower bound on the cost of getting from `node` to `target`, used to guide A*.
+/// It has to be admissible and consistent for the found paths to be optimal.
+pub trait Heuristic<G: GraphRead> {
+    fn estimate(&self, graph: &G, node: NodeID, target: NodeID) -> f64;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    /// Starts with the source and ends with the target.
+    pub nodes: Vec<NodeID>,
+    /// `edges[i]` connects `nodes[i]` and `nodes[i + 1]`.
+    pub edges: Vec<EdgeID>,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    /// `None` if the target can't be reached.
+    pub path: Option<Path>,
+    /// Number of nodes taken off the queue before the search stopped.
+    pub settled_nodes: usize,
+}
+
+#[cfg(test)]
+pub(crate) mod test_graphs {
+    use crate::graph::{EdgeKind, Graph, NodeID};
+    use crate::importer::{GraphNode, GraphWay, haversine_distance};
+
+    /// `width` x `height` grid of two-way roads about 111 m apart, nodes are returned in row-major order.
+    /// Some ways are made longer than the straight line between their nodes, so that shortest paths aren't trivial.
+    pub(crate) fn grid(width: usize, height: usize) -> (Graph<GraphNode, GraphWay>, Vec<NodeID>) {
+        let mut graph = Graph::new();
+        let nodes: Vec<NodeID> = (0..width * height)
+            .map(|i| graph.add_node(GraphNode::new(50.0 + (i / width) as f64 * 0.001, 20.0 + (i % width) as f64 * 0.001)))
+            .collect();
+
+        let add_way = |graph: &mut Graph<GraphNode, GraphWay>, from: usize, to: usize| {
+            let detour = 1.0 + ((from * 7919 + to * 104_729) % 5) as f64 / 4.0;
+            let distance = haversine_distance(graph.get_node(nodes[from]), graph.get_node(nodes[to])) * detour;
+            graph.add_edge(nodes[from], nodes[to], GraphWay::new(distance), EdgeKind::Undirected);
+        };
+
+        for i in 0..width * height {
+            if i % width + 1 < width {
+                add_way(&mut graph, i, i + 1);
+            }
+            if i + width < width * height {
+                add_way(&mut graph, i, i + width);
+            }
+        }
+
+        (graph, nodes)
+    }
+}
@@ -0,0 +1,250 @@
+use log::info;
+
+use crate::graph::{GraphRead, IDIntoUSize, NodeID};
+use crate::routing::{EdgeCost, Heuristic, dijkstra::DijkstraSearch};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LandmarkStrategy {
+    /// Each landmark is the node farthest from the ones picked so far.
+    Farthest,
+    /// Picks landmarks in the parts of a shortest path tree where the current ones give the weakest bounds.
+    Avoid,
+}
+
+/// Distances to and from a handful of landmarks, giving A* lower bounds through the triangle inequality.
+/// The bounds stay valid when edges only get more expensive after preprocessing, e.g. because of traffic,
+/// cheaper edges require a `recompute`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Landmarks {
+    landmarks: Vec<NodeID>,
+    /// `from_landmark[i][v]` is the cost of going from landmark `i` to `v`.
+    from_landmark: Vec<Vec<f64>>,
+    /// `to_landmark[i][v]` is the cost of going from `v` to landmark `i`.
+    to_landmark: Vec<Vec<f64>>,
+}
+
+impl Landmarks {
+    pub fn select<G, C>(graph: &G, cost: &C, count: usize, strategy: LandmarkStrategy) -> Self where
+        G: GraphRead,
+        C: EdgeCost<G::Edge> {
+        let mut landmarks = Self { landmarks: Vec::new(), from_landmark: Vec::new(), to_landmark: Vec::new() };
+        let Some(first) = graph.nodes().next() else {
+            return landmarks;
+        };
+
+        while landmarks.landmarks.len() < count.min(graph.node_count()) {
+            let next = match strategy {
+                LandmarkStrategy::Farthest => landmarks.farthest(graph, cost, first),
+                LandmarkStrategy::Avoid => landmarks.avoid(graph, cost, first),
+            };
+            let Some(next) = next else {
+                break;
+            };
+
+            landmarks.add(graph, cost, next);
+        }
+
+        info!("selected {} landmarks: {:?}", landmarks.landmarks.len(), landmarks.landmarks);
+        landmarks
+    }
+
+    pub fn landmarks(&self) -> &[NodeID] {
+        &self.landmarks
+    }
+
+    /// Recomputes the distance tables for the same landmarks, needed when edges got cheaper.
+    pub fn recompute<G, C>(&mut self, graph: &G, cost: &C) where G: GraphRead, C: EdgeCost<G::Edge> {
+        let landmarks = std::mem::take(&mut self.landmarks);
+        self.from_landmark.clear();
+        self.to_landmark.clear();
+        for landmark in landmarks {
+            self.add(graph, cost, landmark);
+        }
+    }
+
+    fn add<G, C>(&mut self, graph: &G, cost: &C, landmark: NodeID) where G: GraphRead, C: EdgeCost<G::Edge> {
+        self.from_landmark.push(all_distances(graph, cost, landmark));
+        self.to_landmark.push(all_distances(&graph.reversed(), cost, landmark));
+        self.landmarks.push(landmark);
+    }
+
+    fn farthest<G, C>(&self, graph: &G, cost: &C, first: NodeID) -> Option<NodeID> where G: GraphRead, C: EdgeCost<G::Edge> {
+        // without landmarks yet, go as far as possible from an arbitrary node
+        let from_start;
+        let tables: Vec<&Vec<f64>> = if self.landmarks.is_empty() {
+            from_start = all_distances(graph, cost, first);
+            vec![&from_start]
+        } else {
+            self.from_landmark.iter().collect()
+        };
+
+        graph.nodes()
+            .filter(|node| !self.landmarks.contains(node))
+            .map(|node| {
+                let closest = tables.iter().map(|table| table[node.as_usize()]).filter(|d| d.is_finite()).reduce(f64::min);
+                (node, closest.unwrap_or(0.0))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(node, _)| node)
+    }
+
+    fn avoid<G, C>(&self, graph: &G, cost: &C, first: NodeID) -> Option<NodeID> where G: GraphRead, C: EdgeCost<G::Edge> {
+        let root = if self.landmarks.is_empty() { first } else { self.farthest(graph, cost, first)? };
+
+        let mut tree = DijkstraSearch::new(graph, cost);
+        tree.add_source(root, 0.0, 0.0);
+        let mut order = Vec::new();
+        while let Some(node) = tree.settle_next(|_| 0.0) {
+            order.push(node);
+        }
+
+        // weight is how much the current landmarks underestimate the distance from the root,
+        // size sums it over a subtree, unless the subtree already contains a landmark
+        let bound = graph.node_id_bound();
+        let mut size = vec![0.0; bound];
+        let mut has_landmark = vec![false; bound];
+        let mut best_child: Vec<Option<NodeID>> = vec![None; bound];
+
+        for &node in order.iter().rev() {
+            let idx = node.as_usize();
+            has_landmark[idx] |= self.landmarks.contains(&node);
+            if has_landmark[idx] {
+                size[idx] = 0.0;
+            } else {
+                size[idx] += tree.distance(node) - self.estimate(graph, root, node);
+            }
+
+            if let Some(edge) = tree.parent_edge(node) {
+                let parent = graph.get_connected_nodes(edge).opposite(node);
+                let parent_idx = parent.as_usize();
+                has_landmark[parent_idx] |= has_landmark[idx];
+                size[parent_idx] += size[idx];
+                if best_child[parent_idx].is_none_or(|child| size[child.as_usize()] < size[idx]) {
+                    best_child[parent_idx] = Some(node);
+                }
+            }
+        }
+
+        let mut current = order.iter()
+            .filter(|node| size[node.as_usize()] > 0.0)
+            .max_by(|a, b| size[a.as_usize()].total_cmp(&size[b.as_usize()]))
+            .copied()
+            .or_else(|| self.farthest(graph, cost, first))?;
+
+        // walk down the heaviest branch to a leaf
+        while let Some(child) = best_child[current.as_usize()].filter(|child| size[child.as_usize()] > 0.0) {
+            current = child;
+        }
+
+        Some(current).filter(|node| !self.landmarks.contains(node))
+    }
+}
+
+/// Cost from `source` to every node, indexed by `NodeID`, `f64::INFINITY` where it can't be reached.
+fn all_distances<G, C>(graph: &G, cost: &C, source: NodeID) -> Vec<f64> where G: GraphRead, C: EdgeCost<G::Edge> {
+    let mut search = DijkstraSearch::new(graph, cost);
+    search.add_source(source, 0.0, 0.0);
+    while search.settle_next(|_| 0.0).is_some() {}
+
+    (0..graph.node_id_bound()).map(|idx| search.distance(NodeID::from_usize(idx))).collect()
+}
+
+impl<G: GraphRead> Heuristic<G> for Landmarks {
+    fn estimate(&self, _graph: &G, node: NodeID, target: NodeID) -> f64 {
+        let (node, target) = (node.as_usize(), target.as_usize());
+        let mut best: f64 = 0.0;
+
+        for (from, to) in self.from_landmark.iter().zip(&self.to_landmark) {
+            // d(L, t) - d(L, v) <= d(v, t) and d(v, L) - d(t, L) <= d(v, t)
+            for bound in [from[target] - from[node], to[node] - to[target]] {
+                if bound.is_finite() {
+                    best = best.max(bound);
+                }
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{EdgeKind, Graph};
+    use crate::importer::GraphWay;
+    use crate::routing::{ByDistance, astar::astar, dijkstra::dijkstra, test_graphs::grid};
+
+    use super::*;
+
+    #[test]
+    fn bounds_are_admissible() {
+        let (graph, nodes) = grid(10, 10);
+        for strategy in [LandmarkStrategy::Farthest, LandmarkStrategy::Avoid] {
+            let landmarks = Landmarks::select(&graph, &ByDistance, 4, strategy);
+            assert_eq!(landmarks.landmarks().len(), 4);
+
+            for &target in nodes.iter().step_by(9) {
+                for &node in &nodes {
+                    let exact = dijkstra(&graph, node, target, &ByDistance).path.unwrap().cost;
+                    let estimate = landmarks.estimate(&graph, node, target);
+                    assert!(estimate <= exact + 1e-6, "{strategy:?}: estimate {estimate} for {node:?} -> {target:?} exceeds {exact}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn farthest_picks_corners_of_a_grid() {
+        let (graph, nodes) = grid(10, 10);
+        let landmarks = Landmarks::select(&graph, &ByDistance, 2, LandmarkStrategy::Farthest);
+        let corners = [nodes[0], nodes[9], nodes[90], nodes[99]];
+        assert!(landmarks.landmarks().iter().all(|l| corners.contains(l)), "landmarks {:?} aren't corners", landmarks.landmarks());
+    }
+
+    #[test]
+    fn astar_with_landmarks_matches_dijkstra_and_settles_less() {
+        let (graph, nodes) = grid(20, 20);
+        let landmarks = Landmarks::select(&graph, &ByDistance, 6, LandmarkStrategy::Avoid);
+
+        for (source, target) in [(0, 399), (21, 378), (399, 5), (190, 209)] {
+            let expected = dijkstra(&graph, nodes[source], nodes[target], &ByDistance);
+            let result = astar(&graph, nodes[source], nodes[target], &ByDistance, &landmarks);
+
+            let (expected_cost, cost) = (expected.path.unwrap().cost, result.path.unwrap().cost);
+            assert!((expected_cost - cost).abs() < 1e-6, "{source} -> {target}: expected {expected_cost}, got {cost}");
+            assert!(result.settled_nodes < expected.settled_nodes, "{source} -> {target}: ALT settled {}, dijkstra {}", result.settled_nodes, expected.settled_nodes);
+        }
+    }
+
+    #[test]
+    fn stays_correct_after_weights_increase() {
+        let (graph, nodes) = grid(12, 12);
+        let landmarks = Landmarks::select(&graph, &ByDistance, 4, LandmarkStrategy::Farthest);
+
+        // congestion on the middle rows of the grid
+        let congested = |way: &GraphWay| if way.distance() > 150.0 { way.distance() * 5.0 } else { way.distance() };
+        for (source, target) in [(0, 143), (5, 138), (60, 71)] {
+            let expected = dijkstra(&graph, nodes[source], nodes[target], &congested).path.unwrap().cost;
+            let cost = astar(&graph, nodes[source], nodes[target], &congested, &landmarks).path.unwrap().cost;
+            assert!((expected - cost).abs() < 1e-6, "{source} -> {target}: expected {expected}, got {cost}");
+        }
+    }
+
+    #[test]
+    fn recompute_after_weights_decrease() {
+        let mut graph = Graph::<usize, f64>::new();
+        let nodes: Vec<_> = (0..5).map(|i| graph.add_node(i)).collect();
+        for pair in nodes.windows(2) {
+            graph.add_edge(pair[0], pair[1], 10.0, EdgeKind::Undirected);
+        }
+        graph.add_edge(nodes[0], nodes[4], 100.0, EdgeKind::Directed);
+
+        let mut landmarks = Landmarks::select(&graph, &|w: &f64| *w, 2, LandmarkStrategy::Farthest);
+        let shortcut = |w: &f64| if *w == 100.0 { 1.0 } else { *w };
+        landmarks.recompute(&graph, &shortcut);
+
+        let exact = dijkstra(&graph, nodes[0], nodes[4], &shortcut).path.unwrap().cost;
+        assert_eq!(exact, 1.0);
+        assert!(landmarks.estimate(&graph, nodes[0], nodes[4]) <= exact);
+        assert_eq!(astar(&graph, nodes[0], nodes[4], &shortcut, &landmarks).path.unwrap().cost, exact);
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::graph::{EdgeID, GraphRead, NodeID};
+use crate::routing::{EdgeCost, Path, dijkstra::{DijkstraSearch, dijkstra}};
+
+// the local optimality test accepts near ties, e.g. staircases through a street grid that only differ by rounding
+const LOCAL_OPTIMALITY_SLACK: f64 = 1e-3;
+
+/// Up to `k` loopless paths from `source` to `target` in order of their cost (Yen's algorithm).
+/// Each spur search runs on a view of the graph without the root path and the edges that earlier paths took from the spur node.
+pub fn k_shortest_paths<G, C>(graph: &G, source: NodeID, target: NodeID, cost: &C, k: usize) -> Vec<Path> where
+    G: GraphRead,
+    C: EdgeCost<G::Edge> {
+    let Some(shortest) = dijkstra(graph, source, target, cost).path else {
+        return Vec::new();
+    };
+
+    let mut paths = vec![shortest];
+    let mut candidates: Vec<Path> = Vec::new();
+    let mut seen: HashSet<Vec<EdgeID>> = HashSet::from([paths[0].edges.clone()]);
+
+    while paths.len() < k {
+        let previous = paths.last().expect("there's at least the shortest path");
+
+        for spur_idx in 0..previous.edges.len() {
+            let spur_node = previous.nodes[spur_idx];
+            let root_nodes = &previous.nodes[..=spur_idx];
+            let root_edges = &previous.edges[..spur_idx];
+
+            let banned_edges: HashSet<EdgeID> = paths.iter()
+                .filter(|path| path.edges.len() > spur_idx && path.edges[..spur_idx] == *root_edges && path.nodes[..=spur_idx] == *root_nodes)
+                .map(|path| path.edges[spur_idx])
+                .collect();
+            let banned_nodes: HashSet<NodeID> = root_nodes[..spur_idx].iter().copied().collect();
+
+            let view = graph.view(|id, _| !banned_nodes.contains(&id), |id, _| !banned_edges.contains(&id));
+            let Some(spur) = dijkstra(&view, spur_node, target, cost).path else {
+                continue;
+            };
+
+            let mut edges = root_edges.to_vec();
+            edges.extend(&spur.edges);
+            if !seen.insert(edges.clone()) {
+                continue;
+            }
+
+            let mut nodes = root_nodes.to_vec();
+            nodes.extend(&spur.nodes[1..]);
+            let root_cost = path_cost(graph, cost, &nodes[..=spur_idx], root_edges);
+            candidates.push(Path { nodes, edges, cost: root_cost + spur.cost });
+        }
+
+        let Some(best) = candidates.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.cost.total_cmp(&b.cost).then(a.edges.len().cmp(&b.edges.len())))
+            .map(|(idx, _)| idx) else {
+            break;
+        };
+        paths.push(candidates.swap_remove(best));
+    }
+
+    paths
+}
+
+/// Limits for `AlternativeRoutes::find`, all relative to the cost of the optimal path.
+#[derive(Debug, Clone, Copy)]
+pub struct AlternativeRoutes {
+    /// Most paths returned, including the optimal one.
+    pub max_count: usize,
+    /// How much more expensive than the optimal path an alternative may be, 0.25 allows 25% more.
+    pub max_stretch: f64,
+    /// Largest share of any already chosen path's cost that an alternative may run along.
+    pub max_sharing: f64,
+    /// Parts of an alternative this long around its via node have to be shortest paths themselves,
+    /// which rules out silly detours off the optimal path.
+    pub local_optimality: f64,
+}
+
+impl Default for AlternativeRoutes {
+    fn default() -> Self {
+        Self { max_count: 3, max_stretch: 0.25, max_sharing: 0.8, local_optimality: 0.25 }
+    }
+}
+
+impl AlternativeRoutes {
+    /// Via-node alternatives: every node settled by both a forward search from `source` and a backward search from `target`
+    /// gives a candidate through it. The cheapest candidates passing the limits are returned after the optimal path.
+    pub fn find<G, C>(&self, graph: &G, source: NodeID, target: NodeID, cost: &C) -> Vec<Path> where
+        G: GraphRead,
+        C: EdgeCost<G::Edge> {
+        debug_assert!(graph.contains_node(target), "invalid target NodeID: {target:?}");
+
+        let mut forward = DijkstraSearch::new(graph, cost);
+        forward.add_source(source, 0.0, 0.0);
+        let mut settled_forward = Vec::new();
+        while !forward.is_settled(target) {
+            let Some(node) = forward.settle_next(|_| 0.0) else {
+                return Vec::new();
+            };
+            settled_forward.push(node);
+        }
+
+        let optimal = forward.distance(target);
+        let limit = optimal * (1.0 + self.max_stretch);
+        while forward.peek_key().is_some_and(|key| key <= limit) {
+            settled_forward.extend(forward.settle_next(|_| 0.0));
+        }
+
+        let reversed = graph.reversed();
+        let mut backward = DijkstraSearch::new(&reversed, cost);
+        backward.add_source(target, 0.0, 0.0);
+        while backward.peek_key().is_some_and(|key| key <= limit) {
+            backward.settle_next(|_| 0.0);
+        }
+
+        let mut vias: Vec<(f64, NodeID)> = settled_forward.into_iter()
+            .filter(|node| backward.is_settled(*node))
+            .map(|node| (forward.distance(node) + backward.distance(node), node))
+            .filter(|(total, _)| *total <= limit)
+            .collect();
+        vias.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        let mut chosen = vec![forward.path_to(target).expect("target was settled")];
+        let mut seen: HashSet<Vec<EdgeID>> = HashSet::from([chosen[0].edges.clone()]);
+
+        for (_, via) in vias {
+            if chosen.len() >= self.max_count {
+                break;
+            }
+
+            let to_via = forward.path_to(via).expect("via nodes are settled in both directions");
+            let from_via = backward.path_to(via).expect("via nodes are settled in both directions");
+            let via_idx = to_via.nodes.len() - 1;
+            let mut nodes = to_via.nodes;
+            nodes.extend(from_via.nodes.iter().rev().skip(1));
+            let mut edges = to_via.edges;
+            edges.extend(from_via.edges.iter().rev());
+
+            if !seen.insert(edges.clone()) {
+                continue;
+            }
+            let path = Path { nodes, edges, cost: to_via.cost + from_via.cost };
+
+            let unique: HashSet<NodeID> = path.nodes.iter().copied().collect();
+            if unique.len() != path.nodes.len() {
+                continue;
+            }
+            if chosen.iter().any(|other| shared_cost(graph, cost, &path, other) > self.max_sharing * other.cost) {
+                continue;
+            }
+            if !self.is_locally_optimal(graph, cost, &path, via_idx, optimal) {
+                continue;
+            }
+
+            chosen.push(path);
+        }
+
+        chosen
+    }
+
+    /// T-test: the part of the path within `local_optimality` of the via node, in both directions, must be a shortest path.
+    fn is_locally_optimal<G, C>(&self, graph: &G, cost: &C, path: &Path, via_idx: usize, optimal: f64) -> bool where
+        G: GraphRead,
+        C: EdgeCost<G::Edge> {
+        let prefix = prefix_costs(graph, cost, path);
+        let window = self.local_optimality * optimal;
+        let via_cost = prefix[via_idx];
+
+        let from = (0..=via_idx).rev().find(|idx| via_cost - prefix[*idx] >= window).unwrap_or(0);
+        let to = (via_idx..path.nodes.len()).find(|idx| prefix[*idx] - via_cost >= window).unwrap_or(path.nodes.len() - 1);
+
+        let detour = prefix[to] - prefix[from];
+        dijkstra(graph, path.nodes[from], path.nodes[to], cost).path.is_some_and(|shortest| shortest.cost * (1.0 + LOCAL_OPTIMALITY_SLACK) >= detour)
+    }
+}
+
+fn path_cost<G, C>(graph: &G, cost: &C, nodes: &[NodeID], edges: &[EdgeID]) -> f64 where G: GraphRead, C: EdgeCost<G::Edge> {
+    edges.iter().zip(nodes).map(|(edge, from)| cost.cost(graph.get_edge_in_direction(*edge, *from))).sum()
+}
+
+/// `prefix[i]` is the cost of the path up to `nodes[i]`.
+fn prefix_costs<G, C>(graph: &G, cost: &C, path: &Path) -> Vec<f64> where G: GraphRead, C: EdgeCost<G::Edge> {
+    let mut prefix = Vec::with_capacity(path.nodes.len());
+    prefix.push(0.0);
+    for (edge, from) in path.edges.iter().zip(&path.nodes) {
+        prefix.push(prefix.last().expect("starts with the source") + cost.cost(graph.get_edge_in_direction(*edge, *from)));
+    }
+    prefix
+}
+
+/// Cost of the edges `path` has in common with `other`, taken in the same direction.
+fn shared_cost<G, C>(graph: &G, cost: &C, path: &Path, other: &Path) -> f64 where G: GraphRead, C: EdgeCost<G::Edge> {
+    let other_steps: HashMap<EdgeID, NodeID> = other.edges.iter().copied().zip(other.nodes.iter().copied()).collect();
+    path.edges.iter().zip(&path.nodes)
+        .filter(|(edge, from)| other_steps.get(*edge) == Some(*from))
+        .map(|(edge, from)| cost.cost(graph.get_edge_in_direction(*edge, *from)))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{EdgeKind, Graph};
+    use crate::routing::{ByDistance, test_graphs::grid};
+
+    use super::*;
+
+    fn weight(edge: &u32) -> f64 {
+        *edge as f64
+    }
+
+    fn all_simple_path_costs(graph: &Graph<usize, u32>, node: NodeID, target: NodeID, visited: &mut Vec<NodeID>, cost: f64, costs: &mut Vec<f64>) {
+        if node == target {
+            costs.push(cost);
+            return;
+        }
+        for edge in graph.get_outgoing_edges(node) {
+            let next = graph.get_connected_nodes(edge).opposite(node);
+            if !visited.contains(&next) {
+                visited.push(next);
+                all_simple_path_costs(graph, next, target, visited, cost + weight(graph.get_edge_in_direction(edge, node)), costs);
+                visited.pop();
+            }
+        }
+    }
+
+    #[test]
+    fn yen_on_textbook_graph() {
+        let mut graph = Graph::<char, u32>::new();
+        let [c, d, e, f, g, h] = ['c', 'd', 'e', 'f', 'g', 'h'].map(|name| graph.add_node(name));
+        for (from, to, w) in [(c, d, 3), (c, e, 2), (d, f, 4), (e, d, 1), (e, f, 2), (e, g, 3), (f, g, 2), (f, h, 1), (g, h, 2)] {
+            graph.add_edge(from, to, w, EdgeKind::Directed);
+        }
+
+        let paths = k_shortest_paths(&graph, c, h, &weight, 3);
+        assert_eq!(paths.iter().map(|p| p.cost).collect::<Vec<_>>(), vec![5.0, 7.0, 8.0]);
+        assert_eq!(paths[0].nodes, vec![c, e, f, h]);
+        assert_eq!(paths[1].nodes, vec![c, e, g, h]);
+    }
+
+    #[test]
+    fn yen_matches_brute_force() {
+        let mut graph = Graph::<usize, u32>::new();
+        let nodes: Vec<_> = (0..9).map(|i| graph.add_node(i)).collect();
+        let mut seed = 777u64;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        for _ in 0..22 {
+            let (from, to) = (next() % nodes.len(), next() % nodes.len());
+            if from != to {
+                let kind = if next() % 2 == 0 { EdgeKind::Directed } else { EdgeKind::Undirected };
+                graph.add_edge(nodes[from], nodes[to], (next() % 9 + 1) as u32, kind);
+            }
+        }
+
+        for &target in &nodes[1..] {
+            let mut expected = Vec::new();
+            all_simple_path_costs(&graph, nodes[0], target, &mut vec![nodes[0]], 0.0, &mut expected);
+            expected.sort_by(f64::total_cmp);
+            expected.truncate(6);
+
+            let paths = k_shortest_paths(&graph, nodes[0], target, &weight, 6);
+            assert_eq!(paths.iter().map(|p| p.cost).collect::<Vec<_>>(), expected, "paths to {target:?}");
+            for path in &paths {
+                assert_eq!(path_cost(&graph, &weight, &path.nodes, &path.edges), path.cost);
+                assert_eq!(path.nodes.iter().collect::<HashSet<_>>().len(), path.nodes.len(), "{path:?} has a loop");
+            }
+        }
+    }
+
+    #[test]
+    fn alternatives_respect_limits() {
+        let (graph, nodes) = grid(15, 15);
+        let limits = AlternativeRoutes::default();
+        let paths = limits.find(&graph, nodes[0], nodes[224], &ByDistance);
+
+        assert!(paths.len() > 1, "no alternatives found");
+        assert!(paths.len() <= limits.max_count);
+        let optimal = dijkstra(&graph, nodes[0], nodes[224], &ByDistance).path.unwrap();
+        assert!((paths[0].cost - optimal.cost).abs() < 1e-6);
+
+        for (idx, path) in paths.iter().enumerate() {
+            assert_eq!(path.nodes.first(), Some(&nodes[0]));
+            assert_eq!(path.nodes.last(), Some(&nodes[224]));
+            assert!(path.cost <= optimal.cost * (1.0 + limits.max_stretch) + 1e-6);
+            assert!((path_cost(&graph, &ByDistance, &path.nodes, &path.edges) - path.cost).abs() < 1e-6);
+            for other in &paths[..idx] {
+                assert!(shared_cost(&graph, &ByDistance, path, other) <= limits.max_sharing * other.cost + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn alternative_takes_the_other_corridor() {
+        // two disjoint corridors between s and t, the northern one slightly longer
+        let mut graph = Graph::<usize, u32>::new();
+        let s = graph.add_node(0);
+        let t = graph.add_node(1);
+        let mut corridor = |length: u32| {
+            let mut previous = s;
+            let mut edges = Vec::new();
+            for i in 0..4 {
+                let next = graph.add_node(10 + i);
+                edges.push(graph.add_edge(previous, next, length, EdgeKind::Undirected));
+                previous = next;
+            }
+            edges.push(graph.add_edge(previous, t, length, EdgeKind::Undirected));
+            edges
+        };
+        let south = corridor(10);
+        let north = corridor(11);
+
+        let paths = AlternativeRoutes::default().find(&graph, s, t, &weight);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].edges, south);
+        assert_eq!(paths[1].edges, north);
+        assert_eq!(paths[1].cost, 55.0);
+
+        let strict = AlternativeRoutes { max_stretch: 0.05, ..AlternativeRoutes::default() };
+        assert_eq!(strict.find(&graph, s, t, &weight).len(), 1);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::graph::{GraphRead, NodeID};
+use crate::importer::{HasCoordinates, haversine_distance};
+use crate::routing::{EdgeCost, Heuristic, SearchResult, dijkstra::DijkstraSearch};
+
+/// Great-circle distance to the target, scaled by the smallest possible cost of travelling one metre.
+/// With `ByDistance` costs the scale is 1, for travel times it's the inverse of the highest speed.
+#[derive(Debug, Clone, Copy)]
+pub struct GreatCircle {
+    pub cost_per_metre: f64,
+}
+
+impl Default for GreatCircle {
+    fn default() -> Self {
+        Self { cost_per_metre: 1.0 }
+    }
+}
+
+impl<G> Heuristic<G> for GreatCircle where G: GraphRead, G::Node: HasCoordinates {
+    fn estimate(&self, graph: &G, node: NodeID, target: NodeID) -> f64 {
+        haversine_distance(graph.get_node(node), graph.get_node(target)) * self.cost_per_metre
+    }
+}
+
+/// Same results as `dijkstra`, but settles nodes in order of their distance plus the heuristic's estimate,
+/// which keeps the search headed towards the target.
+pub fn astar<G, C, H>(graph: &G, source: NodeID, target: NodeID, cost: &C, heuristic: &H) -> SearchResult where
+    G: GraphRead,
+    C: EdgeCost<G::Edge>,
+    H: Heuristic<G> {
+    debug_assert!(graph.contains_node(target), "invalid target NodeID: {target:?}");
+
+    let potential = |node: NodeID| heuristic.estimate(graph, node, target);
+    let mut search = DijkstraSearch::new(graph, cost);
+    search.add_source(source, 0.0, potential(source));
+
+    while let Some(node) = search.settle_next(potential) {
+        if node == target {
+            break;
+        }
+    }
+
+    SearchResult {
+        path: search.is_settled(target).then(|| search.path_to(target)).flatten(),
+        settled_nodes: search.settled_count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{EdgeKind, Graph};
+    use crate::importer::{GraphNode, GraphWay};
+    use crate::routing::{ByDistance, dijkstra::dijkstra, test_graphs::grid};
+
+    use super::*;
+
+    #[test]
+    fn matches_dijkstra_while_settling_fewer_nodes() {
+        let (graph, nodes) = grid(20, 20);
+        let queries = [(0, 399), (21, 378), (399, 5), (190, 209), (45, 312)];
+
+        for (source, target) in queries {
+            let expected = dijkstra(&graph, nodes[source], nodes[target], &ByDistance);
+            let result = astar(&graph, nodes[source], nodes[target], &ByDistance, &GreatCircle::default());
+
+            let expected_cost = expected.path.unwrap().cost;
+            let cost = result.path.unwrap().cost;
+            assert!((expected_cost - cost).abs() < 1e-6, "{source} -> {target}: dijkstra found {expected_cost}, A* found {cost}");
+            assert!(result.settled_nodes < expected.settled_nodes, "{source} -> {target}: A* settled {} nodes, dijkstra {}", result.settled_nodes, expected.settled_nodes);
+        }
+    }
+
+    #[test]
+    fn unreachable_target_has_no_path() {
+        let mut graph = Graph::<GraphNode, GraphWay>::new();
+        let a = graph.add_node(GraphNode::new(50.0, 20.0));
+        let b = graph.add_node(GraphNode::new(50.001, 20.0));
+        graph.add_edge(b, a, GraphWay::new(111.0), EdgeKind::Directed);
+
+        let result = astar(&graph, a, b, &ByDistance, &GreatCircle::default());
+        assert!(result.path.is_none());
+        assert_eq!(result.settled_nodes, 1);
+    }
+
+    #[test]
+    fn custom_node_types_can_opt_in() {
+        #[derive(Clone, Copy, PartialEq)]
+        struct Stop {
+            lat: f64,
+            lon: f64,
+        }
+
+        impl HasCoordinates for Stop {
+            fn lat(&self) -> f64 { self.lat }
+            fn lon(&self) -> f64 { self.lon }
+        }
+
+        let mut graph = Graph::<Stop, f64>::new();
+        let a = graph.add_node(Stop { lat: 0.0, lon: 0.0 });
+        let b = graph.add_node(Stop { lat: 0.0, lon: 0.01 });
+        graph.add_edge(a, b, 2000.0, EdgeKind::Undirected);
+
+        let estimate = GreatCircle::default().estimate(&graph, a, b);
+        assert!((estimate - 1111.95).abs() < 0.1, "unexpected estimate {estimate}");
+        assert_eq!(astar(&graph, a, b, &|w: &f64| *w, &GreatCircle::default()).path.unwrap().cost, 2000.0);
+    }
+}
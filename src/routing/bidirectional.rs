@@ -0,0 +1,136 @@
+use crate::graph::{GraphRead, NodeID};
+use crate::routing::{EdgeCost, Path, SearchResult, dijkstra::DijkstraSearch};
+
+/// Runs Dijkstra forward from `source` and backward from `target` over a reversed view of the graph,
+/// until the two searches can't improve the best path found where they meet.
+pub fn bidirectional_dijkstra<G, C>(graph: &G, source: NodeID, target: NodeID, cost: &C) -> SearchResult where
+    G: GraphRead,
+    C: EdgeCost<G::Edge> {
+    debug_assert!(graph.contains_node(target), "invalid target NodeID: {target:?}");
+
+    let reversed = graph.reversed();
+    let mut forward = DijkstraSearch::new(graph, cost);
+    let mut backward = DijkstraSearch::new(&reversed, cost);
+    forward.add_source(source, 0.0, 0.0);
+    backward.add_source(target, 0.0, 0.0);
+
+    let mut best = f64::INFINITY;
+    let mut meeting = None;
+
+    // both tentative distances are checked whenever a node gets settled, which is enough to see the best meeting node
+    // before the queues stop overlapping
+    while let (Some(forward_key), Some(backward_key)) = (forward.peek_key(), backward.peek_key()) {
+        if forward_key + backward_key >= best {
+            break;
+        }
+
+        let settled = if forward_key <= backward_key {
+            forward.settle_next(|_| 0.0)
+        } else {
+            backward.settle_next(|_| 0.0)
+        };
+
+        if let Some(node) = settled {
+            let through = forward.distance(node) + backward.distance(node);
+            if through < best {
+                best = through;
+                meeting = Some(node);
+            }
+        }
+    }
+
+    let path = meeting.map(|node| {
+        let to_meeting = forward.path_to(node).expect("meeting node was reached by the forward search");
+        let from_meeting = backward.path_to(node).expect("meeting node was reached by the backward search");
+
+        let mut nodes = to_meeting.nodes;
+        nodes.extend(from_meeting.nodes.iter().rev().skip(1));
+        let mut edges = to_meeting.edges;
+        edges.extend(from_meeting.edges.iter().rev());
+
+        Path { nodes, edges, cost: best }
+    });
+
+    SearchResult { path, settled_nodes: forward.settled_count() + backward.settled_count() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{EdgeKind, Graph};
+    use crate::routing::{ByDistance, dijkstra::dijkstra, test_graphs::grid};
+
+    use super::*;
+
+    fn weight(edge: &u32) -> f64 {
+        *edge as f64
+    }
+
+    #[test]
+    fn matches_dijkstra_on_grid() {
+        let (graph, nodes) = grid(15, 15);
+
+        for (source, target) in [(0, 224), (14, 210), (100, 101), (37, 190)] {
+            let expected = dijkstra(&graph, nodes[source], nodes[target], &ByDistance).path.unwrap();
+            let path = bidirectional_dijkstra(&graph, nodes[source], nodes[target], &ByDistance).path.unwrap();
+
+            assert!((expected.cost - path.cost).abs() < 1e-6, "{source} -> {target}: expected {}, got {}", expected.cost, path.cost);
+            assert_eq!(path.nodes.first(), Some(&nodes[source]));
+            assert_eq!(path.nodes.last(), Some(&nodes[target]));
+            assert_eq!(path.nodes.len(), path.edges.len() + 1);
+        }
+    }
+
+    #[test]
+    fn handles_mixed_edge_kinds() {
+        // pseudo random graph mixing all edge kinds, compared against plain dijkstra for every pair
+        let mut graph = Graph::<usize, u32>::new();
+        let nodes: Vec<_> = (0..12).map(|i| graph.add_node(i)).collect();
+        let mut seed = 12345u64;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+
+        for _ in 0..30 {
+            let from = next() % nodes.len();
+            let to = next() % nodes.len();
+            if from == to {
+                continue;
+            }
+            let weight = (next() % 20 + 1) as u32;
+            match next() % 3 {
+                0 => graph.add_edge(nodes[from], nodes[to], weight, EdgeKind::Directed),
+                1 => graph.add_edge(nodes[from], nodes[to], weight, EdgeKind::Undirected),
+                _ => graph.add_bidirectional_edge(nodes[from], nodes[to], weight, (next() % 20 + 1) as u32),
+            };
+        }
+
+        for &source in &nodes {
+            for &target in &nodes {
+                let expected = dijkstra(&graph, source, target, &weight).path.map(|p| p.cost);
+                let path = bidirectional_dijkstra(&graph, source, target, &weight).path;
+
+                assert_eq!(path.as_ref().map(|p| p.cost), expected, "{source:?} -> {target:?}");
+                if let Some(path) = path {
+                    let recomputed: f64 = path.edges.iter().zip(&path.nodes)
+                        .map(|(edge, from)| weight(graph.get_edge_in_direction(*edge, *from)))
+                        .sum();
+                    assert_eq!(recomputed, path.cost, "{source:?} -> {target:?}: edges don't add up to the cost");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn source_is_target() {
+        let mut graph = Graph::<char, u32>::new();
+        let a = graph.add_node('a');
+        let b = graph.add_node('b');
+        graph.add_edge(a, b, 3, EdgeKind::Directed);
+
+        let path = bidirectional_dijkstra(&graph, a, a, &weight).path.unwrap();
+        assert_eq!(path.nodes, vec![a]);
+        assert_eq!(path.cost, 0.0);
+        assert!(bidirectional_dijkstra(&graph, b, a, &weight).path.is_none());
+    }
+}
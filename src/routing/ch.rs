@@ -0,0 +1,634 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use log::info;
+use ordered_float::OrderedFloat;
+
+use crate::graph::{EdgeID, GraphRead, IDIntoUSize, NodeID};
+use crate::routing::{EdgeCost, Path, SearchResult};
+
+const NOT_IN_HIERARCHY: u32 = u32::MAX;
+// witness searches only have to be good enough, giving up early just adds a few unnecessary shortcuts
+const WITNESS_SETTLE_LIMIT: usize = 64;
+const FILE_MAGIC: &[u8; 4] = b"RCH1";
+// from, to, cost, origin tag and the two origin fields of a saved arc
+const ARC_BYTES: u64 = 8 + 8 + 8 + 1 + 8 + 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArcOrigin {
+    Edge(EdgeID),
+    /// Replaces the arcs `first` and `second`, which went through the contracted node.
+    Shortcut { first: usize, second: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Arc {
+    from: usize,
+    to: usize,
+    cost: f64,
+    origin: ArcOrigin,
+}
+
+/// Contraction Hierarchy built for one cost function, answering queries with a bidirectional search that only goes up the hierarchy.
+/// Paths are unpacked back into `EdgeID`s of the graph the hierarchy was built from, so it has to be rebuilt whenever that graph changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractionHierarchy {
+    ranks: Vec<u32>,
+    arcs: Vec<Arc>,
+    /// Arcs leaving a node towards a higher ranked one, used by the forward search.
+    upward: Vec<Vec<usize>>,
+    /// Arcs entering a node from a higher ranked one, used by the backward search.
+    downward: Vec<Vec<usize>>,
+}
+
+impl ContractionHierarchy {
+    /// Orders the nodes by edge difference and contracts them one by one, adding shortcuts wherever a witness search
+    /// doesn't find a path at least as cheap around the contracted node.
+    pub fn build<G, C>(graph: &G, cost: &C) -> Self where G: GraphRead, C: EdgeCost<G::Edge> {
+        let mut arcs = Vec::new();
+        for edge in graph.edges() {
+            let nodes = graph.get_connected_nodes(edge);
+            let mut push_arc = |from: NodeID, to: NodeID| {
+                let cost = cost.cost(graph.get_edge_in_direction(edge, from));
+                if cost.is_finite() {
+                    arcs.push(Arc { from: from.as_usize(), to: to.as_usize(), cost, origin: ArcOrigin::Edge(edge) });
+                }
+            };
+
+            push_arc(nodes.from, nodes.to);
+            if graph.get_edge_kind(edge).is_two_way() {
+                push_arc(nodes.to, nodes.from);
+            }
+        }
+
+        let original_arcs = arcs.len();
+        let mut contractor = Contractor::new(graph.node_id_bound(), arcs);
+        let ranks = contractor.contract(graph.nodes().map(|n| n.as_usize()));
+        info!("contraction hierarchy built with {} shortcuts on top of {original_arcs} arcs", contractor.arcs.len() - original_arcs);
+
+        Self::from_parts(ranks, contractor.arcs)
+    }
+
+    fn from_parts(ranks: Vec<u32>, arcs: Vec<Arc>) -> Self {
+        let mut upward = vec![Vec::new(); ranks.len()];
+        let mut downward = vec![Vec::new(); ranks.len()];
+
+        for (idx, arc) in arcs.iter().enumerate() {
+            if ranks[arc.from] < ranks[arc.to] {
+                upward[arc.from].push(idx);
+            } else {
+                downward[arc.to].push(idx);
+            }
+        }
+
+        Self { ranks, arcs, upward, downward }
+    }
+
+    pub fn shortcut_count(&self) -> usize {
+        self.arcs.iter().filter(|arc| matches!(arc.origin, ArcOrigin::Shortcut { .. })).count()
+    }
+
+    pub fn query(&self, source: NodeID, target: NodeID) -> SearchResult {
+        debug_assert!(self.contains(source), "NodeID not in the hierarchy: {source:?}");
+        debug_assert!(self.contains(target), "NodeID not in the hierarchy: {target:?}");
+
+        let mut forward = UpwardSearch::new(source.as_usize(), true);
+        let mut backward = UpwardSearch::new(target.as_usize(), false);
+        let mut best = f64::INFINITY;
+        let mut meeting = None;
+
+        // a direction is done once its queue can't beat the best path anymore, the other one may still find a better meeting node
+        loop {
+            let forward_key = forward.peek_key().filter(|key| *key < best);
+            let backward_key = backward.peek_key().filter(|key| *key < best);
+
+            let (search, other) = match (forward_key, backward_key) {
+                (None, None) => break,
+                (Some(f), Some(b)) if f <= b => (&mut forward, &backward),
+                (Some(_), None) => (&mut forward, &backward),
+                _ => (&mut backward, &forward),
+            };
+
+            let Some(node) = search.settle_next(self) else {
+                continue;
+            };
+
+            if let (Some(distance), Some(other_distance)) = (search.distance(node), other.distance(node))
+                && distance + other_distance < best {
+                best = distance + other_distance;
+                meeting = Some(node);
+            }
+        }
+
+        let path = meeting.map(|node| {
+            let mut arcs = Vec::new();
+            for arc in forward.arcs_to(node, &self.arcs).into_iter().rev().chain(backward.arcs_to(node, &self.arcs)) {
+                self.unpack(arc, &mut arcs);
+            }
+
+            let mut nodes = vec![source];
+            nodes.extend(arcs.iter().map(|arc| NodeID::from_usize(self.arcs[*arc].to)));
+            let edges = arcs.iter().map(|arc| match self.arcs[*arc].origin {
+                ArcOrigin::Edge(edge) => edge,
+                ArcOrigin::Shortcut { .. } => unreachable!("shortcuts are fully unpacked"),
+            }).collect();
+
+            Path { nodes, edges, cost: best }
+        });
+
+        SearchResult { path, settled_nodes: forward.settled_count + backward.settled_count }
+    }
+
+    fn contains(&self, node: NodeID) -> bool {
+        self.ranks.get(node.as_usize()).is_some_and(|rank| *rank != NOT_IN_HIERARCHY)
+    }
+
+    fn unpack(&self, arc: usize, out: &mut Vec<usize>) {
+        let mut stack = vec![arc];
+        while let Some(arc) = stack.pop() {
+            match self.arcs[arc].origin {
+                ArcOrigin::Edge(_) => out.push(arc),
+                ArcOrigin::Shortcut { first, second } => {
+                    stack.push(second);
+                    stack.push(first);
+                },
+            }
+        }
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&(self.ranks.len() as u64).to_le_bytes())?;
+        for rank in &self.ranks {
+            writer.write_all(&rank.to_le_bytes())?;
+        }
+
+        writer.write_all(&(self.arcs.len() as u64).to_le_bytes())?;
+        for arc in &self.arcs {
+            let (tag, first, second) = match arc.origin {
+                ArcOrigin::Edge(edge) => (0u8, edge.as_usize(), 0),
+                ArcOrigin::Shortcut { first, second } => (1u8, first, second),
+            };
+            writer.write_all(&(arc.from as u64).to_le_bytes())?;
+            writer.write_all(&(arc.to as u64).to_le_bytes())?;
+            writer.write_all(&arc.cost.to_le_bytes())?;
+            writer.write_all(&[tag])?;
+            writer.write_all(&(first as u64).to_le_bytes())?;
+            writer.write_all(&(second as u64).to_le_bytes())?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let truncated = || format!("{} is truncated", path.display());
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            return Err(format!("{} is not a contraction hierarchy file", path.display()).into());
+        }
+
+        // counts are checked against the file length before allocating for them
+        let node_count = read_u64(&mut reader)?;
+        if node_count > file_len.saturating_sub(12) / 4 {
+            return Err(truncated().into());
+        }
+        let node_count = node_count as usize;
+        let ranks = (0..node_count).map(|_| read_u32(&mut reader)).collect::<Result<Vec<_>, _>>()?;
+
+        let arc_count = read_u64(&mut reader)?;
+        if arc_count > file_len.saturating_sub(20 + 4 * node_count as u64) / ARC_BYTES {
+            return Err(truncated().into());
+        }
+        let mut arcs = Vec::with_capacity(arc_count as usize);
+        for _ in 0..arc_count {
+            let from = read_u64(&mut reader)? as usize;
+            let to = read_u64(&mut reader)? as usize;
+            let cost = f64::from_bits(read_u64(&mut reader)?);
+            let mut tag = [0u8; 1];
+            reader.read_exact(&mut tag)?;
+            let first = read_u64(&mut reader)? as usize;
+            let second = read_u64(&mut reader)? as usize;
+
+            let origin = match tag[0] {
+                0 => ArcOrigin::Edge(EdgeID::from_usize(first)),
+                1 if first < arcs.len() && second < arcs.len() => ArcOrigin::Shortcut { first, second },
+                _ => return Err(format!("corrupted arc {} in {}", arcs.len(), path.display()).into()),
+            };
+            if from >= node_count || to >= node_count {
+                return Err(format!("arc {} in {} points outside of the hierarchy", arcs.len(), path.display()).into());
+            }
+
+            arcs.push(Arc { from, to, cost, origin });
+        }
+
+        Ok(Self::from_parts(ranks, arcs))
+    }
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Dijkstra over the arcs of the hierarchy, keeping its state in maps since CH search spaces are tiny.
+struct UpwardSearch {
+    is_forward: bool,
+    labels: HashMap<usize, (f64, Option<usize>)>,
+    settled: HashSet<usize>,
+    queue: BinaryHeap<Reverse<(OrderedFloat<f64>, usize)>>,
+    settled_count: usize,
+}
+
+impl UpwardSearch {
+    fn new(source: usize, is_forward: bool) -> Self {
+        Self {
+            is_forward,
+            labels: HashMap::from([(source, (0.0, None))]),
+            settled: HashSet::new(),
+            queue: BinaryHeap::from([Reverse((OrderedFloat(0.0), source))]),
+            settled_count: 0,
+        }
+    }
+
+    fn peek_key(&mut self) -> Option<f64> {
+        while let Some(Reverse((key, node))) = self.queue.peek() {
+            if !self.settled.contains(node) {
+                return Some(key.into_inner());
+            }
+            self.queue.pop();
+        }
+        None
+    }
+
+    fn settle_next(&mut self, hierarchy: &ContractionHierarchy) -> Option<usize> {
+        let Reverse((_, node)) = self.queue.pop()?;
+        if !self.settled.insert(node) {
+            return None;
+        }
+        self.settled_count += 1;
+
+        let distance = self.labels[&node].0;
+        let arcs_of = if self.is_forward { &hierarchy.upward } else { &hierarchy.downward };
+        for &idx in &arcs_of[node] {
+            let arc = &hierarchy.arcs[idx];
+            let next = if self.is_forward { arc.to } else { arc.from };
+            let candidate = distance + arc.cost;
+
+            if self.labels.get(&next).is_none_or(|(current, _)| candidate < *current) {
+                self.labels.insert(next, (candidate, Some(idx)));
+                self.queue.push(Reverse((OrderedFloat(candidate), next)));
+            }
+        }
+
+        Some(node)
+    }
+
+    fn distance(&self, node: usize) -> Option<f64> {
+        self.labels.get(&node).map(|(distance, _)| *distance)
+    }
+
+    /// Arcs on the way from `node` back to where the search started, nearest to `node` first.
+    fn arcs_to(&self, node: usize, arcs: &[Arc]) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut current = node;
+        while let Some((_, Some(idx))) = self.labels.get(&current) {
+            path.push(*idx);
+            current = if self.is_forward { arcs[*idx].from } else { arcs[*idx].to };
+        }
+        path
+    }
+}
+
+/// Keeps the remaining graph while nodes are being contracted.
+struct Contractor {
+    arcs: Vec<Arc>,
+    outgoing: Vec<Vec<usize>>,
+    incoming: Vec<Vec<usize>>,
+    contracted: Vec<bool>,
+    contracted_neighbours: Vec<i64>,
+    witness: WitnessSearch,
+}
+
+impl Contractor {
+    fn new(node_bound: usize, arcs: Vec<Arc>) -> Self {
+        let mut outgoing = vec![Vec::new(); node_bound];
+        let mut incoming = vec![Vec::new(); node_bound];
+        for (idx, arc) in arcs.iter().enumerate() {
+            outgoing[arc.from].push(idx);
+            incoming[arc.to].push(idx);
+        }
+
+        Self {
+            arcs,
+            outgoing,
+            incoming,
+            contracted: vec![false; node_bound],
+            contracted_neighbours: vec![0; node_bound],
+            witness: WitnessSearch::new(node_bound),
+        }
+    }
+
+    /// Contracts all of `nodes`, returning the rank of every node, `NOT_IN_HIERARCHY` for ids that weren't given.
+    fn contract(&mut self, nodes: impl Iterator<Item = usize>) -> Vec<u32> {
+        let mut ranks = vec![NOT_IN_HIERARCHY; self.contracted.len()];
+        let mut queue: BinaryHeap<Reverse<(i64, usize)>> = nodes
+            .map(|node| Reverse((self.priority(node), node)))
+            .collect();
+
+        let mut next_rank = 0;
+        while let Some(Reverse((_, node))) = queue.pop() {
+            if self.contracted[node] {
+                continue;
+            }
+
+            // lazy updates: priorities of neighbours of contracted nodes go stale, so check before committing
+            let priority = self.priority(node);
+            if queue.peek().is_some_and(|Reverse((next, _))| priority > *next) {
+                queue.push(Reverse((priority, node)));
+                continue;
+            }
+
+            self.contract_node(node);
+            ranks[node] = next_rank;
+            next_rank += 1;
+        }
+
+        ranks
+    }
+
+    fn priority(&mut self, node: usize) -> i64 {
+        let shortcuts = self.shortcuts_for(node).len() as i64;
+        let removed = (self.live_arcs(&self.incoming[node], true).count() + self.live_arcs(&self.outgoing[node], false).count()) as i64;
+        shortcuts - removed + self.contracted_neighbours[node]
+    }
+
+    fn contract_node(&mut self, node: usize) {
+        for (first, second, cost) in self.shortcuts_for(node) {
+            let from = self.arcs[first].from;
+            let to = self.arcs[second].to;
+            let idx = self.arcs.len();
+            self.arcs.push(Arc { from, to, cost, origin: ArcOrigin::Shortcut { first, second } });
+            self.outgoing[from].push(idx);
+            self.incoming[to].push(idx);
+        }
+
+        self.contracted[node] = true;
+
+        let mut neighbours: Vec<usize> = self.incoming[node].iter().map(|idx| self.arcs[*idx].from)
+            .chain(self.outgoing[node].iter().map(|idx| self.arcs[*idx].to))
+            .filter(|n| !self.contracted[*n])
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+
+        for neighbour in neighbours {
+            self.contracted_neighbours[neighbour] += 1;
+            let arcs = &self.arcs;
+            self.outgoing[neighbour].retain(|idx| arcs[*idx].to != node);
+            self.incoming[neighbour].retain(|idx| arcs[*idx].from != node);
+        }
+    }
+
+    /// Arcs in `list` whose other end is still in the graph.
+    fn live_arcs<'a>(&'a self, list: &'a [usize], incoming: bool) -> impl Iterator<Item = usize> + 'a {
+        list.iter().copied().filter(move |idx| {
+            let arc = &self.arcs[*idx];
+            !self.contracted[if incoming { arc.from } else { arc.to }]
+        })
+    }
+
+    /// Shortcuts needed to contract `node`, as the pair of arcs they replace and their cost.
+    fn shortcuts_for(&mut self, node: usize) -> Vec<(usize, usize, f64)> {
+        let incoming = cheapest_per_node(self.live_arcs(&self.incoming[node], true).map(|idx| (self.arcs[idx].from, idx)), &self.arcs);
+        let outgoing = cheapest_per_node(self.live_arcs(&self.outgoing[node], false).map(|idx| (self.arcs[idx].to, idx)), &self.arcs);
+        let mut shortcuts = Vec::new();
+
+        for &(from, in_arc) in &incoming {
+            let targets: Vec<_> = outgoing.iter().filter(|(to, _)| *to != from).collect();
+            let Some(limit) = targets.iter().map(|(_, out_arc)| self.arcs[in_arc].cost + self.arcs[*out_arc].cost).reduce(f64::max) else {
+                continue;
+            };
+
+            self.witness.run(from, node, limit, &self.arcs, &self.outgoing, &self.contracted);
+
+            for &&(to, out_arc) in &targets {
+                let cost = self.arcs[in_arc].cost + self.arcs[out_arc].cost;
+                if self.witness.distance(to) > cost {
+                    shortcuts.push((in_arc, out_arc, cost));
+                }
+            }
+        }
+
+        shortcuts
+    }
+}
+
+fn cheapest_per_node(arcs: impl Iterator<Item = (usize, usize)>, all: &[Arc]) -> Vec<(usize, usize)> {
+    let mut cheapest = HashMap::<usize, usize>::new();
+    for (node, idx) in arcs {
+        let entry = cheapest.entry(node).or_insert(idx);
+        if all[idx].cost < all[*entry].cost {
+            *entry = idx;
+        }
+    }
+    let mut cheapest: Vec<_> = cheapest.into_iter().collect();
+    cheapest.sort_unstable();
+    cheapest
+}
+
+/// Local Dijkstra looking for paths that avoid the node being contracted.
+struct WitnessSearch {
+    distances: Vec<f64>,
+    touched: Vec<usize>,
+}
+
+impl WitnessSearch {
+    fn new(node_bound: usize) -> Self {
+        Self { distances: vec![f64::INFINITY; node_bound], touched: Vec::new() }
+    }
+
+    fn run(&mut self, source: usize, avoid: usize, limit: f64, arcs: &[Arc], outgoing: &[Vec<usize>], contracted: &[bool]) {
+        for node in self.touched.drain(..) {
+            self.distances[node] = f64::INFINITY;
+        }
+
+        let mut queue = BinaryHeap::from([Reverse((OrderedFloat(0.0), source))]);
+        self.distances[source] = 0.0;
+        self.touched.push(source);
+        let mut settled = 0;
+
+        while let Some(Reverse((key, node))) = queue.pop() {
+            let distance = key.into_inner();
+            if distance > self.distances[node] {
+                continue;
+            }
+            if distance > limit || settled >= WITNESS_SETTLE_LIMIT {
+                break;
+            }
+            settled += 1;
+
+            for &idx in &outgoing[node] {
+                let arc = &arcs[idx];
+                if arc.to == avoid || contracted[arc.to] {
+                    continue;
+                }
+
+                let candidate = distance + arc.cost;
+                if candidate < self.distances[arc.to] {
+                    if self.distances[arc.to].is_infinite() {
+                        self.touched.push(arc.to);
+                    }
+                    self.distances[arc.to] = candidate;
+                    queue.push(Reverse((OrderedFloat(candidate), arc.to)));
+                }
+            }
+        }
+    }
+
+    fn distance(&self, node: usize) -> f64 {
+        self.distances[node]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{EdgeKind, Graph};
+    use crate::importer::{GraphNode, GraphWay};
+    use crate::routing::{ByDistance, dijkstra::dijkstra, test_graphs::grid};
+
+    use super::*;
+
+    fn assert_valid_path<G: GraphRead<Edge = GraphWay>>(graph: &G, path: &Path, source: NodeID, target: NodeID) {
+        assert_eq!(path.nodes.first(), Some(&source));
+        assert_eq!(path.nodes.last(), Some(&target));
+        assert_eq!(path.nodes.len(), path.edges.len() + 1);
+
+        let mut total = 0.0;
+        for (edge, pair) in path.edges.iter().zip(path.nodes.windows(2)) {
+            assert!(graph.get_edges_between(pair[0], pair[1]).contains(edge), "{edge:?} doesn't lead from {:?} to {:?}", pair[0], pair[1]);
+            total += graph.get_edge_in_direction(*edge, pair[0]).distance();
+        }
+        assert!((total - path.cost).abs() < 1e-6, "edges add up to {total}, path claims {}", path.cost);
+    }
+
+    #[test]
+    fn queries_match_dijkstra() {
+        let (graph, nodes) = grid(12, 12);
+        let hierarchy = ContractionHierarchy::build(&graph, &ByDistance);
+
+        for source in (0..nodes.len()).step_by(7) {
+            for target in (0..nodes.len()).step_by(11) {
+                let expected = dijkstra(&graph, nodes[source], nodes[target], &ByDistance).path.unwrap();
+                let path = hierarchy.query(nodes[source], nodes[target]).path.unwrap();
+
+                assert!((expected.cost - path.cost).abs() < 1e-6, "{source} -> {target}: expected {}, got {}", expected.cost, path.cost);
+                assert_valid_path(&graph, &path, nodes[source], nodes[target]);
+            }
+        }
+    }
+
+    #[test]
+    fn settles_fewer_nodes_than_dijkstra() {
+        let (graph, nodes) = grid(20, 20);
+        let hierarchy = ContractionHierarchy::build(&graph, &ByDistance);
+
+        let expected = dijkstra(&graph, nodes[0], nodes[399], &ByDistance);
+        let result = hierarchy.query(nodes[0], nodes[399]);
+        assert!(result.settled_nodes < expected.settled_nodes / 2, "CH settled {}, dijkstra {}", result.settled_nodes, expected.settled_nodes);
+    }
+
+    #[test]
+    fn respects_one_way_streets() {
+        let mut graph = Graph::<GraphNode, GraphWay>::new();
+        let nodes: Vec<_> = (0..4).map(|i| graph.add_node(GraphNode::new(50.0, 20.0 + i as f64 * 0.001))).collect();
+        // a ring where the short way round is one way
+        graph.add_edge(nodes[0], nodes[1], GraphWay::new(10.0), EdgeKind::Directed);
+        graph.add_edge(nodes[1], nodes[2], GraphWay::new(10.0), EdgeKind::Directed);
+        graph.add_edge(nodes[2], nodes[3], GraphWay::new(100.0), EdgeKind::Undirected);
+        graph.add_edge(nodes[3], nodes[0], GraphWay::new(100.0), EdgeKind::Undirected);
+        graph.add_bidirectional_edge(nodes[1], nodes[3], GraphWay::new(5.0), GraphWay::new(500.0));
+
+        let hierarchy = ContractionHierarchy::build(&graph, &ByDistance);
+        for &source in &nodes {
+            for &target in &nodes {
+                let expected = dijkstra(&graph, source, target, &ByDistance).path.map(|p| p.cost);
+                let path = hierarchy.query(source, target).path;
+                assert_eq!(path.as_ref().map(|p| p.cost), expected, "{source:?} -> {target:?}");
+                if let Some(path) = path {
+                    assert_valid_path(&graph, &path, source, target);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable_target_has_no_path() {
+        let mut graph = Graph::<GraphNode, GraphWay>::new();
+        let a = graph.add_node(GraphNode::new(50.0, 20.0));
+        let b = graph.add_node(GraphNode::new(50.0, 20.001));
+        graph.add_node(GraphNode::new(50.0, 20.002));
+        graph.add_edge(a, b, GraphWay::new(1.0), EdgeKind::Directed);
+
+        let hierarchy = ContractionHierarchy::build(&graph, &ByDistance);
+        assert!(hierarchy.query(b, a).path.is_none());
+        assert_eq!(hierarchy.query(a, a).path.unwrap().cost, 0.0);
+    }
+
+    #[test]
+    fn survives_save_and_load() {
+        let (graph, nodes) = grid(8, 8);
+        let hierarchy = ContractionHierarchy::build(&graph, &ByDistance);
+
+        let file = std::env::temp_dir().join(format!("raptordb-ch-{}.bin", std::process::id()));
+        hierarchy.save(&file).expect("failed to save hierarchy");
+        let loaded = ContractionHierarchy::load(&file).expect("failed to load hierarchy");
+        std::fs::remove_file(&file).ok();
+
+        assert_eq!(loaded, hierarchy);
+        assert_eq!(loaded.query(nodes[3], nodes[60]), hierarchy.query(nodes[3], nodes[60]));
+    }
+
+    #[test]
+    fn load_rejects_other_files() {
+        let file = std::env::temp_dir().join(format!("raptordb-not-ch-{}.bin", std::process::id()));
+        std::fs::write(&file, b"definitely not a hierarchy").unwrap();
+        assert!(ContractionHierarchy::load(&file).is_err());
+        std::fs::remove_file(&file).ok();
+    }
+
+    #[test]
+    fn load_rejects_truncated_files() {
+        let (graph, _) = grid(4, 4);
+        let file = std::env::temp_dir().join(format!("raptordb-ch-truncated-{}.bin", std::process::id()));
+        ContractionHierarchy::build(&graph, &ByDistance).save(&file).unwrap();
+        let bytes = std::fs::read(&file).unwrap();
+
+        std::fs::write(&file, &bytes[..bytes.len() - 5]).unwrap();
+        assert!(ContractionHierarchy::load(&file).is_err());
+        // a huge arc count right after the ranks
+        let arcs_at = 12 + 4 * 16;
+        let mut crafted = bytes[..arcs_at].to_vec();
+        crafted.extend_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&file, &crafted).unwrap();
+        assert!(ContractionHierarchy::load(&file).is_err());
+        std::fs::remove_file(&file).ok();
+    }
+}
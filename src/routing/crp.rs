@@ -0,0 +1,495 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use log::info;
+use ordered_float::OrderedFloat;
+use rayon::prelude::*;
+
+use crate::graph::{EdgeID, GraphRead, IDIntoUSize, NodeID};
+use crate::importer::HasCoordinates;
+use crate::routing::{EdgeCost, Path, SearchResult};
+
+/// Cell of IDs that don't belong to a node.
+pub const NO_CELL: u32 = u32::MAX;
+// each level of `by_coordinates` merges two rounds of bisection, i.e. four cells of the level below
+const BISECTIONS_PER_LEVEL: usize = 2;
+
+/// Nested cells on every level, level 0 being the finest. It only depends on the graph's topology, never on costs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultilevelPartition {
+    /// `cells[level][node]` is the cell of `node` on `level`.
+    cells: Vec<Vec<u32>>,
+    cell_counts: Vec<usize>,
+}
+
+impl MultilevelPartition {
+    /// Takes a cell for every `NodeID` on every level, finest first, and renumbers them densely.
+    /// Panics if a cell is split between several cells of the next level.
+    pub fn new(levels: Vec<Vec<u32>>) -> Self {
+        let mut cells: Vec<Vec<u32>> = Vec::with_capacity(levels.len());
+        let mut cell_counts = Vec::with_capacity(levels.len());
+
+        for level in levels {
+            let mut renumbered = HashMap::new();
+            let level: Vec<u32> = level.into_iter()
+                .map(|cell| {
+                    if cell == NO_CELL {
+                        return NO_CELL;
+                    }
+                    let next = renumbered.len() as u32;
+                    *renumbered.entry(cell).or_insert(next)
+                })
+                .collect();
+
+            if let Some(finer) = cells.last() {
+                assert_eq!(finer.len(), level.len(), "level {} has cells for a different number of nodes", cells.len());
+                let mut parents = HashMap::new();
+                for (&fine, &coarse) in finer.iter().zip(&level) {
+                    assert_eq!(fine == NO_CELL, coarse == NO_CELL, "level {} doesn't cover the same nodes as the one below", cells.len());
+                    let parent = *parents.entry(fine).or_insert(coarse);
+                    assert_eq!(parent, coarse, "cell {fine} on level {} is split between cells {parent} and {coarse} of the next level", cells.len() - 1);
+                }
+            }
+
+            cell_counts.push(renumbered.len());
+            cells.push(level);
+        }
+
+        Self { cells, cell_counts }
+    }
+
+    /// Recursively bisects the nodes at the median of their longer coordinate extent until no cell has more than
+    /// `max_cell_size` nodes. Every further level merges four neighbouring cells.
+    pub fn by_coordinates<G>(graph: &G, max_cell_size: usize, levels: usize) -> Self where G: GraphRead, G::Node: HasCoordinates {
+        assert!(max_cell_size > 0, "cells must be allowed to contain nodes");
+
+        let mut nodes: Vec<NodeID> = graph.nodes().collect();
+        let mut depth = 0;
+        while nodes.len().div_ceil(1 << depth) > max_cell_size {
+            depth += 1;
+        }
+
+        let mut finest = vec![NO_CELL; graph.node_id_bound()];
+        bisect(graph, &mut nodes, depth, 0, &mut finest);
+
+        let levels = (0..levels)
+            .map(|level| {
+                let shift = (level * BISECTIONS_PER_LEVEL) as u32;
+                finest.iter()
+                    .map(|&cell| if cell == NO_CELL { NO_CELL } else { cell.checked_shr(shift).unwrap_or(0) })
+                    .collect()
+            })
+            .collect();
+
+        Self::new(levels)
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn cell_count(&self, level: usize) -> usize {
+        self.cell_counts[level]
+    }
+
+    pub fn cell(&self, level: usize, node: NodeID) -> u32 {
+        self.cells[level][node.as_usize()]
+    }
+}
+
+fn bisect<G>(graph: &G, nodes: &mut [NodeID], depth: usize, prefix: u32, cells: &mut [u32]) where G: GraphRead, G::Node: HasCoordinates {
+    if depth == 0 || nodes.len() <= 1 {
+        for node in nodes.iter() {
+            cells[node.as_usize()] = prefix << depth;
+        }
+        return;
+    }
+
+    let extent = |coordinate: fn(&G::Node) -> f64| {
+        let values = nodes.iter().map(|node| coordinate(graph.get_node(*node)));
+        values.clone().fold(f64::NEG_INFINITY, f64::max) - values.fold(f64::INFINITY, f64::min)
+    };
+    // longitude degrees get shorter towards the poles
+    let latitude = graph.get_node(nodes[0]).lat();
+    let by_lat = extent(|node| node.lat()) >= extent(|node| node.lon()) * latitude.to_radians().cos();
+    let key = |node: &NodeID| {
+        let node = graph.get_node(*node);
+        if by_lat { node.lat() } else { node.lon() }
+    };
+
+    let middle = nodes.len() / 2;
+    nodes.select_nth_unstable_by(middle, |a, b| key(a).total_cmp(&key(b)));
+    let (left, right) = nodes.split_at_mut(middle);
+    bisect(graph, left, depth - 1, prefix * 2, cells);
+    bisect(graph, right, depth - 1, prefix * 2 + 1, cells);
+}
+
+/// Boundary nodes of every cell on every level, i.e. the nodes with an edge leaving the cell.
+/// Like the partition it doesn't depend on costs, those only come in with `customize`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlay {
+    partition: MultilevelPartition,
+    /// `boundaries[level][cell]` lists the boundary nodes of `cell`.
+    boundaries: Vec<Vec<Vec<NodeID>>>,
+    /// `positions[level]` maps a boundary node to its index in the list of its cell.
+    positions: Vec<HashMap<NodeID, usize>>,
+}
+
+/// Costs between the boundary nodes of every cell for one cost function.
+/// Recomputing it is all it takes to react to changed weights or closed roads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Customization {
+    /// `cliques[level][cell]` is a row-major matrix of the costs between the cell's boundary nodes.
+    cliques: Vec<Vec<Vec<f64>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Edge(EdgeID),
+    /// Clique arc of a cell on `level`, unpacked by searching the cell again.
+    Overlay { level: usize },
+}
+
+impl Overlay {
+    pub fn new<G: GraphRead>(graph: &G, partition: MultilevelPartition) -> Self {
+        let levels = partition.level_count();
+        let mut boundaries: Vec<Vec<Vec<NodeID>>> = (0..levels).map(|level| vec![Vec::new(); partition.cell_count(level)]).collect();
+        let mut positions = vec![HashMap::new(); levels];
+
+        for edge in graph.edges() {
+            let nodes = graph.get_connected_nodes(edge);
+            debug_assert!(partition.cell(0, nodes.from) != NO_CELL && partition.cell(0, nodes.to) != NO_CELL, "partition doesn't cover edge {edge:?}");
+
+            // cells are nested, so once both ends share a cell they do on all coarser levels as well
+            for level in (0..levels).take_while(|&level| partition.cell(level, nodes.from) != partition.cell(level, nodes.to)) {
+                for node in [nodes.from, nodes.to] {
+                    let cell = &mut boundaries[level][partition.cell(level, node) as usize];
+                    positions[level].entry(node).or_insert_with(|| {
+                        cell.push(node);
+                        cell.len() - 1
+                    });
+                }
+            }
+        }
+
+        for (level, positions) in positions.iter().enumerate() {
+            info!("overlay level {level}: {} cells with {} boundary nodes", partition.cell_count(level), positions.len());
+        }
+
+        Self { partition, boundaries, positions }
+    }
+
+    pub fn partition(&self) -> &MultilevelPartition {
+        &self.partition
+    }
+
+    /// Computes the cliques bottom up, cells of a level are independent of each other and run in parallel.
+    /// Searches on level 0 use the graph's edges, higher levels combine the cliques of the level below.
+    pub fn customize<G, C>(&self, graph: &G, cost: &C) -> Customization where
+        G: GraphRead + Sync,
+        C: EdgeCost<G::Edge> + Sync {
+        let mut customization = Customization { cliques: Vec::with_capacity(self.partition.level_count()) };
+
+        for level in 0..self.partition.level_count() {
+            let cliques = (0..self.partition.cell_count(level))
+                .into_par_iter()
+                .map(|cell| self.clique(graph, cost, &customization, level, cell as u32))
+                .collect();
+            customization.cliques.push(cliques);
+        }
+
+        customization
+    }
+
+    fn clique<G, C>(&self, graph: &G, cost: &C, customization: &Customization, level: usize, cell: u32) -> Vec<f64> where
+        G: GraphRead,
+        C: EdgeCost<G::Edge> {
+        let boundary = &self.boundaries[level][cell as usize];
+        let mut costs = Vec::with_capacity(boundary.len() * boundary.len());
+
+        for &source in boundary {
+            let search = LocalSearch::run(source, None, |node, arcs| {
+                if level == 0 {
+                    self.edges_from(graph, cost, node, |next| self.partition.cell(0, next) == cell, arcs);
+                } else {
+                    let subcell = self.partition.cell(level - 1, node);
+                    self.clique_arcs(customization, level - 1, node, arcs);
+                    self.edges_from(graph, cost, node, |next| {
+                        self.partition.cell(level - 1, next) != subcell && self.partition.cell(level, next) == cell
+                    }, arcs);
+                }
+            });
+            costs.extend(boundary.iter().map(|target| search.distance(*target)));
+        }
+
+        costs
+    }
+
+    /// Shortest path that only uses edges inside the finest cells of `source` and `target`, everywhere else it goes over
+    /// the cliques of the coarsest cell that contains neither of them. `cost` has to be the one `customization` was made for.
+    pub fn query<G, C>(&self, graph: &G, customization: &Customization, cost: &C, source: NodeID, target: NodeID) -> SearchResult where
+        G: GraphRead,
+        C: EdgeCost<G::Edge> {
+        debug_assert!(graph.contains_node(source), "invalid source NodeID: {source:?}");
+        debug_assert!(graph.contains_node(target), "invalid target NodeID: {target:?}");
+
+        let partition = &self.partition;
+        let query_level = |node: NodeID| (0..partition.level_count()).rev().find(|&level| {
+            let cell = partition.cell(level, node);
+            cell != partition.cell(level, source) && cell != partition.cell(level, target)
+        });
+
+        let search = LocalSearch::run(source, Some(target), |node, arcs| match query_level(node) {
+            None => self.edges_from(graph, cost, node, |_| true, arcs),
+            Some(level) => {
+                let cell = partition.cell(level, node);
+                self.clique_arcs(customization, level, node, arcs);
+                self.edges_from(graph, cost, node, |next| partition.cell(level, next) != cell, arcs);
+            }
+        });
+
+        let path = search.steps_to(target).map(|steps| {
+            let mut nodes = vec![source];
+            let mut edges = Vec::new();
+            for (from, to, step) in steps {
+                match step {
+                    Step::Edge(edge) => {
+                        edges.push(edge);
+                        nodes.push(to);
+                    }
+                    Step::Overlay { level } => {
+                        let cell = partition.cell(level, from);
+                        let inner = LocalSearch::run(from, Some(to), |node, arcs| {
+                            self.edges_from(graph, cost, node, |next| partition.cell(level, next) == cell, arcs);
+                        });
+                        let inner_steps = inner.steps_to(to).expect("clique arcs have a path inside their cell");
+                        for (_, inner_to, inner_step) in inner_steps {
+                            let Step::Edge(edge) = inner_step else {
+                                unreachable!("level 0 searches only use edges");
+                            };
+                            edges.push(edge);
+                            nodes.push(inner_to);
+                        }
+                    }
+                }
+            }
+
+            Path { nodes, edges, cost: search.distance(target) }
+        });
+
+        SearchResult { path, settled_nodes: search.settled_count }
+    }
+
+    fn edges_from<G, C>(&self, graph: &G, cost: &C, node: NodeID, keep: impl Fn(NodeID) -> bool, arcs: &mut Vec<(NodeID, f64, Step)>) where
+        G: GraphRead,
+        C: EdgeCost<G::Edge> {
+        for edge in graph.get_outgoing_edges(node) {
+            let next = graph.get_connected_nodes(edge).opposite(node);
+            if !keep(next) {
+                continue;
+            }
+
+            let edge_cost = cost.cost(graph.get_edge_in_direction(edge, node));
+            if edge_cost.is_finite() {
+                arcs.push((next, edge_cost, Step::Edge(edge)));
+            }
+        }
+    }
+
+    fn clique_arcs(&self, customization: &Customization, level: usize, node: NodeID, arcs: &mut Vec<(NodeID, f64, Step)>) {
+        let cell = self.partition.cell(level, node) as usize;
+        let boundary = &self.boundaries[level][cell];
+        let row = *self.positions[level].get(&node).expect("overlay searches only reach boundary nodes");
+        let costs = &customization.cliques[level][cell][row * boundary.len()..(row + 1) * boundary.len()];
+
+        for (&next, &cost) in boundary.iter().zip(costs) {
+            if next != node && cost.is_finite() {
+                arcs.push((next, cost, Step::Overlay { level }));
+            }
+        }
+    }
+}
+
+/// Dijkstra keeping its labels in a HashMap, as searches inside a cell only touch a small part of the graph.
+struct LocalSearch {
+    labels: HashMap<NodeID, (f64, Option<(NodeID, Step)>)>,
+    settled_count: usize,
+}
+
+impl LocalSearch {
+    /// Searches until `target` is settled, or everything reachable if there's none.
+    fn run(source: NodeID, target: Option<NodeID>, neighbours: impl Fn(NodeID, &mut Vec<(NodeID, f64, Step)>)) -> Self {
+        let mut labels = HashMap::from([(source, (0.0, None))]);
+        let mut settled = HashSet::new();
+        let mut queue = BinaryHeap::from([Reverse((OrderedFloat(0.0), source))]);
+        let mut arcs = Vec::new();
+
+        while let Some(Reverse((_, node))) = queue.pop() {
+            if !settled.insert(node) {
+                continue;
+            }
+            if Some(node) == target {
+                break;
+            }
+
+            let distance = labels[&node].0;
+            arcs.clear();
+            neighbours(node, &mut arcs);
+
+            for &(next, cost, step) in &arcs {
+                let candidate = distance + cost;
+                if !settled.contains(&next) && labels.get(&next).is_none_or(|(current, _)| candidate < *current) {
+                    labels.insert(next, (candidate, Some((node, step))));
+                    queue.push(Reverse((OrderedFloat(candidate), next)));
+                }
+            }
+        }
+
+        Self { labels, settled_count: settled.len() }
+    }
+
+    fn distance(&self, node: NodeID) -> f64 {
+        self.labels.get(&node).map_or(f64::INFINITY, |(distance, _)| *distance)
+    }
+
+    /// Steps from the source to `node` as `(from, to, step)`.
+    fn steps_to(&self, node: NodeID) -> Option<Vec<(NodeID, NodeID, Step)>> {
+        self.labels.get(&node)?;
+
+        let mut steps = Vec::new();
+        let mut current = node;
+        while let Some((_, Some((parent, step)))) = self.labels.get(&current) {
+            steps.push((*parent, current, *step));
+            current = *parent;
+        }
+
+        steps.reverse();
+        Some(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{EdgeKind, Graph};
+    use crate::importer::GraphWay;
+    use crate::routing::{ByDistance, dijkstra::dijkstra, test_graphs::grid};
+
+    use super::*;
+
+    fn weight(edge: &u32) -> f64 {
+        *edge as f64
+    }
+
+    #[test]
+    fn coordinate_partition_is_nested_and_bounded() {
+        let (graph, nodes) = grid(16, 16);
+        let partition = MultilevelPartition::by_coordinates(&graph, 16, 3);
+
+        assert_eq!(partition.level_count(), 3);
+        assert_eq!(partition.cell_count(0), 16);
+        assert_eq!(partition.cell_count(1), 4);
+        assert_eq!(partition.cell_count(2), 1);
+
+        let mut sizes = vec![0; partition.cell_count(0)];
+        for &node in &nodes {
+            sizes[partition.cell(0, node) as usize] += 1;
+        }
+        assert!(sizes.iter().all(|&size| size == 16), "unbalanced cells: {sizes:?}");
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_partitions_that_are_not_nested() {
+        MultilevelPartition::new(vec![vec![0, 0, 1, 1], vec![0, 1, 1, 1]]);
+    }
+
+    #[test]
+    fn matches_dijkstra_on_grid() {
+        let (graph, nodes) = grid(20, 20);
+        let overlay = Overlay::new(&graph, MultilevelPartition::by_coordinates(&graph, 10, 3));
+        let customization = overlay.customize(&graph, &ByDistance);
+
+        for (source, target) in [(0, 399), (21, 378), (399, 5), (190, 209), (45, 312), (7, 7)] {
+            let expected = dijkstra(&graph, nodes[source], nodes[target], &ByDistance).path.unwrap();
+            let path = overlay.query(&graph, &customization, &ByDistance, nodes[source], nodes[target]).path.unwrap();
+
+            assert!((expected.cost - path.cost).abs() < 1e-6, "{source} -> {target}: expected {}, got {}", expected.cost, path.cost);
+            assert_eq!(path.nodes.first(), Some(&nodes[source]));
+            assert_eq!(path.nodes.last(), Some(&nodes[target]));
+            assert_eq!(path.nodes.len(), path.edges.len() + 1);
+
+            let recomputed: f64 = path.edges.iter().zip(&path.nodes)
+                .map(|(edge, from)| graph.get_edge_in_direction(*edge, *from).distance())
+                .sum();
+            assert!((recomputed - path.cost).abs() < 1e-6, "{source} -> {target}: unpacked edges add up to {recomputed}");
+        }
+    }
+
+    #[test]
+    fn handles_mixed_edge_kinds() {
+        let mut graph = Graph::<usize, u32>::new();
+        let nodes: Vec<_> = (0..16).map(|i| graph.add_node(i)).collect();
+        let mut seed = 4242u64;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+
+        for _ in 0..40 {
+            let from = next() % nodes.len();
+            let to = next() % nodes.len();
+            if from == to {
+                continue;
+            }
+            let weight = (next() % 20 + 1) as u32;
+            match next() % 3 {
+                0 => graph.add_edge(nodes[from], nodes[to], weight, EdgeKind::Directed),
+                1 => graph.add_edge(nodes[from], nodes[to], weight, EdgeKind::Undirected),
+                _ => graph.add_bidirectional_edge(nodes[from], nodes[to], weight, (next() % 20 + 1) as u32),
+            };
+        }
+
+        let partition = MultilevelPartition::new(vec![
+            (0..16).map(|i| i / 4).collect(),
+            (0..16).map(|i| i / 8).collect(),
+        ]);
+        let overlay = Overlay::new(&graph, partition);
+        let customization = overlay.customize(&graph, &weight);
+
+        for &source in &nodes {
+            for &target in &nodes {
+                let expected = dijkstra(&graph, source, target, &weight).path.map(|p| p.cost);
+                let path = overlay.query(&graph, &customization, &weight, source, target).path;
+
+                assert_eq!(path.as_ref().map(|p| p.cost), expected, "{source:?} -> {target:?}");
+                if let Some(path) = path {
+                    let recomputed: f64 = path.edges.iter().zip(&path.nodes)
+                        .map(|(edge, from)| weight(graph.get_edge_in_direction(*edge, *from)))
+                        .sum();
+                    assert_eq!(recomputed, path.cost, "{source:?} -> {target:?}: edges don't add up to the cost");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn customization_reacts_to_closures() {
+        let (graph, nodes) = grid(12, 12);
+        let overlay = Overlay::new(&graph, MultilevelPartition::by_coordinates(&graph, 9, 2));
+        let open = overlay.customize(&graph, &ByDistance);
+
+        // the longest detours are closed, the overlay itself stays the same
+        let closed = |way: &GraphWay| if way.distance() > 200.0 { f64::INFINITY } else { way.distance() };
+        let with_closures = overlay.customize(&graph, &closed);
+        assert_ne!(open, with_closures);
+
+        for (source, target) in [(0, 143), (5, 138), (60, 71), (11, 132)] {
+            let expected = dijkstra(&graph, nodes[source], nodes[target], &closed).path.map(|p| p.cost);
+            let cost = overlay.query(&graph, &with_closures, &closed, nodes[source], nodes[target]).path.map(|p| p.cost);
+            match (expected, cost) {
+                (Some(expected), Some(cost)) => assert!((expected - cost).abs() < 1e-6, "{source} -> {target}: expected {expected}, got {cost}"),
+                _ => assert_eq!(expected, cost, "{source} -> {target}"),
+            }
+        }
+    }
+}
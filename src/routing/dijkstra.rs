@@ -0,0 +1,250 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use ordered_float::OrderedFloat;
+
+use crate::graph::{EdgeID, GraphRead, IDIntoUSize, NodeID};
+use crate::routing::{EdgeCost, Path, SearchResult};
+
+/// Label setting search over outgoing edges, shared by the searches in this module.
+/// Queue keys are tentative distances plus a potential, a zero potential gives plain Dijkstra.
+pub(crate) struct DijkstraSearch<'a, G, C> {
+    graph: &'a G,
+    cost: &'a C,
+    distances: Vec<f64>,
+    parents: Vec<Option<EdgeID>>,
+    settled: Vec<bool>,
+    queue: BinaryHeap<Reverse<(OrderedFloat<f64>, NodeID)>>,
+    settled_count: usize,
+}
+
+impl<'a, G, C> DijkstraSearch<'a, G, C> where G: GraphRead, C: EdgeCost<G::Edge> {
+    pub(crate) fn new(graph: &'a G, cost: &'a C) -> Self {
+        let bound = graph.node_id_bound();
+        Self {
+            graph,
+            cost,
+            distances: vec![f64::INFINITY; bound],
+            parents: vec![None; bound],
+            settled: vec![false; bound],
+            queue: BinaryHeap::new(),
+            settled_count: 0,
+        }
+    }
+
+    pub(crate) fn add_source(&mut self, node: NodeID, initial_cost: f64, potential: f64) {
+        debug_assert!(self.graph.contains_node(node), "invalid source NodeID: {node:?}");
+        if initial_cost < self.distances[node.as_usize()] {
+            self.distances[node.as_usize()] = initial_cost;
+            self.parents[node.as_usize()] = None;
+            self.queue.push(Reverse((OrderedFloat(initial_cost + potential), node)));
+        }
+    }
+
+    /// Smallest key left in the queue.
+    pub(crate) fn peek_key(&mut self) -> Option<f64> {
+        while let Some(Reverse((key, node))) = self.queue.peek() {
+            if !self.settled[node.as_usize()] {
+                return Some(key.into_inner());
+            }
+            self.queue.pop();
+        }
+        None
+    }
+
+    /// Settles the next node and relaxes its outgoing edges.
+    pub(crate) fn settle_next(&mut self, potential: impl Fn(NodeID) -> f64) -> Option<NodeID> {
+        let node = loop {
+            let Reverse((_, node)) = self.queue.pop()?;
+            if !self.settled[node.as_usize()] {
+                break node;
+            }
+        };
+
+        self.settled[node.as_usize()] = true;
+        self.settled_count += 1;
+        let distance = self.distances[node.as_usize()];
+
+        for edge in self.graph.get_outgoing_edges(node) {
+            let next = self.graph.get_connected_nodes(edge).opposite(node);
+            if self.settled[next.as_usize()] {
+                continue;
+            }
+
+            let edge_cost = self.cost.cost(self.graph.get_edge_in_direction(edge, node));
+            debug_assert!(edge_cost >= 0.0, "negative edge cost {edge_cost} on edge {edge:?}");
+            let candidate = distance + edge_cost;
+
+            if candidate < self.distances[next.as_usize()] {
+                self.distances[next.as_usize()] = candidate;
+                self.parents[next.as_usize()] = Some(edge);
+                self.queue.push(Reverse((OrderedFloat(candidate + potential(next)), next)));
+            }
+        }
+
+        Some(node)
+    }
+
+    pub(crate) fn is_settled(&self, node: NodeID) -> bool {
+        self.settled[node.as_usize()]
+    }
+
+    /// Tentative distance, final once the node is settled.
+    pub(crate) fn distance(&self, node: NodeID) -> f64 {
+        self.distances[node.as_usize()]
+    }
+
+    pub(crate) fn parent_edge(&self, node: NodeID) -> Option<EdgeID> {
+        self.parents[node.as_usize()]
+    }
+
+    pub(crate) fn settled_count(&self) -> usize {
+        self.settled_count
+    }
+
+    /// Follows parent edges from `node` back to the source that reached it.
+    pub(crate) fn path_to(&self, node: NodeID) -> Option<Path> {
+        let cost = self.distance(node);
+        if cost.is_infinite() {
+            return None;
+        }
+
+        let mut nodes = vec![node];
+        let mut edges = Vec::new();
+        let mut current = node;
+        while let Some(edge) = self.parent_edge(current) {
+            current = self.graph.get_connected_nodes(edge).opposite(current);
+            edges.push(edge);
+            nodes.push(current);
+        }
+
+        nodes.reverse();
+        edges.reverse();
+        Some(Path { nodes, edges, cost })
+    }
+}
+
+/// Shortest path from `source` to `target`, respecting edge directions. Stops as soon as `target` is settled.
+pub fn dijkstra<G, C>(graph: &G, source: NodeID, target: NodeID, cost: &C) -> SearchResult where
+    G: GraphRead,
+    C: EdgeCost<G::Edge> {
+    debug_assert!(graph.contains_node(target), "invalid target NodeID: {target:?}");
+
+    let mut search = DijkstraSearch::new(graph, cost);
+    search.add_source(source, 0.0, 0.0);
+
+    while let Some(node) = search.settle_next(|_| 0.0) {
+        if node == target {
+            break;
+        }
+    }
+
+    SearchResult {
+        path: search.is_settled(target).then(|| search.path_to(target)).flatten(),
+        settled_nodes: search.settled_count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{EdgeKind, Graph};
+    use crate::importer::GraphWay;
+    use crate::routing::ByDistance;
+
+    use super::*;
+
+    fn weight(edge: &u32) -> f64 {
+        *edge as f64
+    }
+
+    #[test]
+    fn finds_cheapest_path() {
+        let mut g = Graph::<char, u32>::new();
+        let a = g.add_node('a');
+        let b = g.add_node('b');
+        let c = g.add_node('c');
+        let d = g.add_node('d');
+        let ab = g.add_edge(a, b, 1, EdgeKind::Undirected);
+        g.add_edge(a, c, 4, EdgeKind::Undirected);
+        let bc = g.add_edge(b, c, 1, EdgeKind::Undirected);
+        g.add_edge(b, d, 7, EdgeKind::Undirected);
+        let cd = g.add_edge(c, d, 2, EdgeKind::Undirected);
+
+        let path = dijkstra(&g, a, d, &weight).path.expect("d is reachable");
+
+        assert_eq!(path.nodes, vec![a, b, c, d]);
+        assert_eq!(path.edges, vec![ab, bc, cd]);
+        assert_eq!(path.cost, 4.0);
+    }
+
+    #[test]
+    fn honours_directed_edges() {
+        let mut g = Graph::<char, u32>::new();
+        let a = g.add_node('a');
+        let b = g.add_node('b');
+        let c = g.add_node('c');
+        g.add_edge(b, a, 1, EdgeKind::Directed);
+        g.add_edge(a, c, 5, EdgeKind::Directed);
+        g.add_edge(c, b, 5, EdgeKind::Directed);
+
+        assert_eq!(dijkstra(&g, a, b, &weight).path.unwrap().cost, 10.0);
+        assert_eq!(dijkstra(&g, b, a, &weight).path.unwrap().cost, 1.0);
+    }
+
+    #[test]
+    fn unreachable_target_has_no_path() {
+        let mut g = Graph::<char, u32>::new();
+        let a = g.add_node('a');
+        let b = g.add_node('b');
+        g.add_edge(b, a, 1, EdgeKind::Directed);
+
+        let result = dijkstra(&g, a, b, &weight);
+        assert!(result.path.is_none());
+        assert_eq!(result.settled_nodes, 1);
+    }
+
+    #[test]
+    fn source_is_target() {
+        let mut g = Graph::<char, u32>::new();
+        let a = g.add_node('a');
+
+        let path = dijkstra(&g, a, a, &weight).path.unwrap();
+        assert_eq!(path.nodes, vec![a]);
+        assert!(path.edges.is_empty());
+        assert_eq!(path.cost, 0.0);
+    }
+
+    #[test]
+    fn stops_once_target_is_settled() {
+        let mut g = Graph::<char, u32>::new();
+        let a = g.add_node('a');
+        let b = g.add_node('b');
+        let c = g.add_node('c');
+        let far = g.add_node('z');
+        g.add_edge(a, b, 1, EdgeKind::Undirected);
+        g.add_edge(a, c, 2, EdgeKind::Undirected);
+        g.add_edge(c, far, 100, EdgeKind::Undirected);
+
+        let result = dijkstra(&g, a, b, &weight);
+        assert_eq!(result.settled_nodes, 2);
+    }
+
+    #[test]
+    fn uses_direction_dependent_costs_and_skips_impassable_edges() {
+        let mut g = Graph::<char, GraphWay>::new();
+        let a = g.add_node('a');
+        let b = g.add_node('b');
+        let c = g.add_node('c');
+        // uphill from a to b is expensive
+        g.add_bidirectional_edge(a, b, GraphWay::new(500.0), GraphWay::new(100.0));
+        g.add_edge(a, c, GraphWay::new(150.0), EdgeKind::Undirected);
+        g.add_edge(c, b, GraphWay::new(150.0), EdgeKind::Undirected);
+
+        assert_eq!(dijkstra(&g, a, b, &ByDistance).path.unwrap().cost, 300.0);
+        assert_eq!(dijkstra(&g, b, a, &ByDistance).path.unwrap().cost, 100.0);
+
+        let closed = |way: &GraphWay| if way.distance() == 150.0 { f64::INFINITY } else { way.distance() };
+        assert_eq!(dijkstra(&g, a, b, &closed).path.unwrap().cost, 500.0);
+        assert!(dijkstra(&g, a, c, &closed).path.is_none());
+    }
+}
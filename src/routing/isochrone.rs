@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+
+use crate::graph::{GraphRead, NodeID};
+use crate::importer::HasCoordinates;
+use crate::routing::{EdgeCost, dijkstra::DijkstraSearch};
+
+// metres per degree, close enough for the local projection used to build hulls
+const METRES_PER_DEGREE_LAT: f64 = 110_540.0;
+const METRES_PER_DEGREE_LON: f64 = 111_320.0;
+
+/// Area reachable within `limit`, in whatever unit the cost function uses, e.g. metres for `ByDistance`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Isochrone {
+    pub limit: f64,
+    /// Closed counter-clockwise ring of `[lon, lat]` positions, empty if less than three distinct points were reachable.
+    pub polygon: Vec<[f64; 2]>,
+}
+
+/// Concave hull by digging into the edges of the convex hull (Park & Oh), an edge is replaced by two going through
+/// the nearest point inside whenever the edge is more than `concavity` times longer than the way to that point.
+#[derive(Debug, Clone, Copy)]
+pub struct ConcaveHull {
+    /// Lower values follow the points more closely, values around 1 give very ragged polygons.
+    pub concavity: f64,
+    /// Edges shorter than this many metres are kept as they are.
+    pub min_edge_length: f64,
+}
+
+impl Default for ConcaveHull {
+    fn default() -> Self {
+        Self { concavity: 2.0, min_edge_length: 0.0 }
+    }
+}
+
+/// Every node reachable from `source` within `limit`, with its cost.
+pub fn reachable<G, C>(graph: &G, source: NodeID, cost: &C, limit: f64) -> HashMap<NodeID, f64> where
+    G: GraphRead,
+    C: EdgeCost<G::Edge> {
+    let (search, settled) = bounded_search(graph, source, cost, limit);
+    settled.into_iter().map(|node| (node, search.distance(node))).collect()
+}
+
+/// One search up to the largest limit, then a hull for every limit around the reachable nodes and the points
+/// where the limit runs out along edges leaving them.
+pub fn isochrones<G, C>(graph: &G, source: NodeID, cost: &C, limits: &[f64], hull: &ConcaveHull) -> Vec<Isochrone> where
+    G: GraphRead,
+    G::Node: HasCoordinates,
+    C: EdgeCost<G::Edge> {
+    let max_limit = limits.iter().copied().fold(0.0, f64::max);
+    let (search, settled) = bounded_search(graph, source, cost, max_limit);
+
+    limits.iter()
+        .map(|&limit| {
+            let mut points = Vec::new();
+            for &node in settled.iter().filter(|node| search.distance(**node) <= limit) {
+                let distance = search.distance(node);
+                let from = graph.get_node(node);
+                points.push([from.lon(), from.lat()]);
+
+                for edge in graph.get_outgoing_edges(node) {
+                    let edge_cost = cost.cost(graph.get_edge_in_direction(edge, node));
+                    if !edge_cost.is_finite() || distance + edge_cost <= limit {
+                        continue;
+                    }
+
+                    let to = graph.get_node(graph.get_connected_nodes(edge).opposite(node));
+                    let fraction = (limit - distance) / edge_cost;
+                    points.push([from.lon() + (to.lon() - from.lon()) * fraction, from.lat() + (to.lat() - from.lat()) * fraction]);
+                }
+            }
+
+            Isochrone { limit, polygon: hull.polygon(&points) }
+        })
+        .collect()
+}
+
+fn bounded_search<'a, G, C>(graph: &'a G, source: NodeID, cost: &'a C, limit: f64) -> (DijkstraSearch<'a, G, C>, Vec<NodeID>) where
+    G: GraphRead,
+    C: EdgeCost<G::Edge> {
+    let mut search = DijkstraSearch::new(graph, cost);
+    search.add_source(source, 0.0, 0.0);
+
+    let mut settled = Vec::new();
+    while search.peek_key().is_some_and(|key| key <= limit) {
+        settled.extend(search.settle_next(|_| 0.0));
+    }
+
+    (search, settled)
+}
+
+impl ConcaveHull {
+    /// Hull of `[lon, lat]` positions, as a closed counter-clockwise ring.
+    pub fn polygon(&self, positions: &[[f64; 2]]) -> Vec<[f64; 2]> {
+        let Some(origin) = positions.first() else {
+            return Vec::new();
+        };
+
+        // digging compares lengths, so it runs on a local projection in metres
+        let scale = [METRES_PER_DEGREE_LON * origin[1].to_radians().cos(), METRES_PER_DEGREE_LAT];
+        let points: Vec<[f64; 2]> = positions.iter()
+            .map(|position| [(position[0] - origin[0]) * scale[0], (position[1] - origin[1]) * scale[1]])
+            .collect();
+
+        let mut ring = convex_hull(&points);
+        if ring.len() < 3 {
+            return Vec::new();
+        }
+
+        let grid = PointGrid::new(&points);
+        let mut on_hull = vec![false; points.len()];
+        for &idx in &ring {
+            on_hull[idx] = true;
+        }
+
+        let mut idx = 0;
+        while idx < ring.len() {
+            let (a, b) = (ring[idx], ring[(idx + 1) % ring.len()]);
+            match self.dig(&points, &grid, &ring, &on_hull, a, b) {
+                Some(inner) => {
+                    on_hull[inner] = true;
+                    ring.insert(idx + 1, inner);
+                }
+                None => idx += 1,
+            }
+        }
+
+        let mut polygon: Vec<[f64; 2]> = ring.iter().map(|idx| positions[*idx]).collect();
+        polygon.push(polygon[0]);
+        polygon
+    }
+
+    /// Point to replace the edge from `a` to `b` with, if there's one worth digging to.
+    fn dig(&self, points: &[[f64; 2]], grid: &PointGrid, ring: &[usize], on_hull: &[bool], a: usize, b: usize) -> Option<usize> {
+        let length = distance(points[a], points[b]);
+        if length <= self.min_edge_length {
+            return None;
+        }
+
+        // the point nearest to the edge leaves no other one outside of the new edges, points further away from it than
+        // the edge length over the concavity are too far away to dig to anyway
+        let reach = length / self.concavity;
+        let low = [points[a][0].min(points[b][0]) - reach, points[a][1].min(points[b][1]) - reach];
+        let high = [points[a][0].max(points[b][0]) + reach, points[a][1].max(points[b][1]) + reach];
+        let (inner, _) = grid.points_within(low, high)
+            .filter(|idx| !on_hull[*idx])
+            .filter_map(|idx| segment_distance(points[idx], points[a], points[b]).map(|d| (idx, d)))
+            .min_by(|(i, x), (j, y)| x.total_cmp(y).then(i.cmp(j)))?;
+
+        let decision = distance(points[inner], points[a]).min(distance(points[inner], points[b]));
+        if decision <= 0.0 || length / decision <= self.concavity {
+            return None;
+        }
+
+        let crosses = |from: usize, to: usize| (0..ring.len()).any(|idx| {
+            let (c, d) = (ring[idx], ring[(idx + 1) % ring.len()]);
+            ![c, d].contains(&from) && ![c, d].contains(&to) && segments_intersect(points[from], points[to], points[c], points[d])
+        });
+        (!crosses(a, inner) && !crosses(inner, b)).then_some(inner)
+    }
+}
+
+/// Uniform grid over the projected points, sized for about one point per cell.
+struct PointGrid {
+    origin: [f64; 2],
+    cell_size: f64,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>,
+}
+
+impl PointGrid {
+    fn new(points: &[[f64; 2]]) -> Self {
+        let low = points.iter().fold([f64::INFINITY; 2], |low, p| [low[0].min(p[0]), low[1].min(p[1])]);
+        let high = points.iter().fold([f64::NEG_INFINITY; 2], |high, p| [high[0].max(p[0]), high[1].max(p[1])]);
+        let (width, height) = (high[0] - low[0], high[1] - low[1]);
+        // thin clouds get cells along their long side instead of an unbounded number of tiny ones
+        let count = points.len() as f64;
+        let cell_size = (width * height / count).sqrt().max(width.max(height) / count).max(f64::MIN_POSITIVE);
+        let columns = (width / cell_size) as usize + 1;
+        let rows = (height / cell_size) as usize + 1;
+
+        let mut grid = Self { origin: low, cell_size, columns, rows, cells: vec![Vec::new(); columns * rows] };
+        for (idx, point) in points.iter().enumerate() {
+            let (column, row) = grid.cell_of(*point);
+            grid.cells[row * columns + column].push(idx);
+        }
+        grid
+    }
+
+    fn cell_of(&self, point: [f64; 2]) -> (usize, usize) {
+        let column = ((point[0] - self.origin[0]) / self.cell_size).max(0.0) as usize;
+        let row = ((point[1] - self.origin[1]) / self.cell_size).max(0.0) as usize;
+        (column.min(self.columns - 1), row.min(self.rows - 1))
+    }
+
+    /// Points in the cells overlapping the box, which may include some just outside of it.
+    fn points_within(&self, low: [f64; 2], high: [f64; 2]) -> impl Iterator<Item = usize> + '_ {
+        let (first_column, first_row) = self.cell_of(low);
+        let (last_column, last_row) = self.cell_of(high);
+        (first_row..=last_row)
+            .flat_map(move |row| (first_column..=last_column).map(move |column| row * self.columns + column))
+            .flat_map(|cell| self.cells[cell].iter().copied())
+    }
+}
+
+/// Andrew's monotone chain, counter-clockwise without repeating the first point.
+fn convex_hull(points: &[[f64; 2]]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by(|a, b| points[*a][0].total_cmp(&points[*b][0]).then(points[*a][1].total_cmp(&points[*b][1])));
+    order.dedup_by(|a, b| points[*a] == points[*b]);
+    if order.len() < 3 {
+        return order;
+    }
+
+    let mut hull: Vec<usize> = Vec::with_capacity(order.len() * 2);
+    for pass in [order.clone(), order.iter().rev().copied().collect()] {
+        let start = hull.len();
+        for idx in pass {
+            while hull.len() >= start + 2 && cross(points[hull[hull.len() - 2]], points[hull[hull.len() - 1]], points[idx]) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(idx);
+        }
+        hull.pop();
+    }
+
+    hull
+}
+
+fn cross(o: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+/// Distance from `p` to the segment, `None` if `p` doesn't project onto it.
+fn segment_distance(p: [f64; 2], a: [f64; 2], b: [f64; 2]) -> Option<f64> {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let t = ((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / (dx * dx + dy * dy);
+    (0.0..=1.0).contains(&t).then(|| distance(p, [a[0] + t * dx, a[1] + t * dy]))
+}
+
+fn segments_intersect(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2]) -> bool {
+    let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+    let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exporter::export_isochrones_geojson;
+    use crate::routing::{ByDistance, dijkstra::dijkstra, test_graphs::grid};
+
+    use super::*;
+
+    fn area(polygon: &[[f64; 2]]) -> f64 {
+        polygon.windows(2).map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1]).sum::<f64>() / 2.0
+    }
+
+    /// Ray casting, positions on the boundary count as inside.
+    fn contains(polygon: &[[f64; 2]], p: [f64; 2]) -> bool {
+        let mut inside = false;
+        for w in polygon.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            if cross(a, b, p).abs() < 1e-12 && segment_distance(p, a, b).is_some() {
+                return true;
+            }
+            if (a[1] > p[1]) != (b[1] > p[1]) && p[0] < a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    #[test]
+    fn reachable_matches_dijkstra() {
+        let (graph, nodes) = grid(10, 10);
+        let reached = reachable(&graph, nodes[44], &ByDistance, 400.0);
+
+        for &node in &nodes {
+            let cost = dijkstra(&graph, nodes[44], node, &ByDistance).path.unwrap().cost;
+            assert_eq!(reached.contains_key(&node), cost <= 400.0, "{node:?} at {cost}");
+            if let Some(reached_cost) = reached.get(&node) {
+                assert!((reached_cost - cost).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn polygons_enclose_reachable_nodes_and_grow_with_the_limit() {
+        let (graph, nodes) = grid(20, 20);
+        let limits = [300.0, 600.0, 900.0];
+        let isochrones = isochrones(&graph, nodes[210], &ByDistance, &limits, &ConcaveHull::default());
+        assert_eq!(isochrones.len(), 3);
+
+        for isochrone in &isochrones {
+            assert!(isochrone.polygon.len() >= 4);
+            assert_eq!(isochrone.polygon.first(), isochrone.polygon.last());
+            assert!(area(&isochrone.polygon) > 0.0, "ring isn't counter-clockwise");
+
+            for (node, _) in reachable(&graph, nodes[210], &ByDistance, isochrone.limit) {
+                let node = graph.get_node(node);
+                assert!(contains(&isochrone.polygon, [node.lon(), node.lat()]), "{node:?} outside of the {} isochrone", isochrone.limit);
+            }
+        }
+
+        assert!(area(&isochrones[0].polygon) < area(&isochrones[1].polygon));
+        assert!(area(&isochrones[1].polygon) < area(&isochrones[2].polygon));
+    }
+
+    #[test]
+    fn hull_follows_concave_shapes() {
+        // L shaped cloud of points
+        let mut points = Vec::new();
+        for x in 0..10 {
+            for y in 0..10 {
+                if x < 3 || y < 3 {
+                    points.push([20.0 + x as f64 * 0.001, 50.0 + y as f64 * 0.001]);
+                }
+            }
+        }
+
+        let concave = ConcaveHull::default().polygon(&points);
+        let convex = ConcaveHull { concavity: f64::INFINITY, min_edge_length: 0.0 }.polygon(&points);
+        assert!(area(&concave) < area(&convex) * 0.75, "concave area {} vs convex {}", area(&concave), area(&convex));
+        assert!(points.iter().all(|p| contains(&concave, *p)));
+        assert!(!contains(&concave, [20.008, 50.008]));
+    }
+
+    #[test]
+    fn hull_of_a_large_cloud_encloses_it() {
+        // sunflower pattern filling a ring with 20000 points, the grid only looks at the points near every edge
+        let golden_angle = std::f64::consts::PI * (3.0 - 5f64.sqrt());
+        let points: Vec<[f64; 2]> = (0..20_000)
+            .map(|i| {
+                let (angle, radius) = (i as f64 * golden_angle, 0.01 + 0.01 * (i as f64 / 20_000.0).sqrt());
+                [20.0 + radius * angle.cos(), 50.0 + radius * angle.sin()]
+            })
+            .collect();
+
+        let polygon = ConcaveHull::default().polygon(&points);
+        assert!(area(&polygon) > 0.0);
+        assert!(points.iter().step_by(97).all(|p| contains(&polygon, *p)));
+    }
+
+    #[test]
+    fn too_few_points_have_no_polygon() {
+        assert!(ConcaveHull::default().polygon(&[]).is_empty());
+        assert!(ConcaveHull::default().polygon(&[[20.0, 50.0], [20.1, 50.0], [20.0, 50.0]]).is_empty());
+    }
+
+    #[test]
+    fn exports_geojson() {
+        let (graph, nodes) = grid(8, 8);
+        let isochrones = isochrones(&graph, nodes[27], &ByDistance, &[200.0, 500.0], &ConcaveHull::default());
+
+        let path = std::env::temp_dir().join(format!("raptordb-isochrones-{}.geojson", std::process::id()));
+        export_isochrones_geojson(&isochrones, &path).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let features = json["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[0]["geometry"]["type"], "Polygon");
+        assert_eq!(features[1]["properties"]["limit"], 500.0);
+    }
+}
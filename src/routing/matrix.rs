@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use log::info;
+use rayon::prelude::*;
+
+use crate::graph::{GraphRead, NodeID};
+use crate::routing::{EdgeCost, dijkstra::DijkstraSearch};
+
+/// Costs from every source to every target, `f64::INFINITY` where a target can't be reached.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceMatrix {
+    sources: Vec<NodeID>,
+    targets: Vec<NodeID>,
+    /// Row-major, one row per source.
+    costs: Vec<f64>,
+}
+
+impl DistanceMatrix {
+    pub fn sources(&self) -> &[NodeID] {
+        &self.sources
+    }
+
+    pub fn targets(&self) -> &[NodeID] {
+        &self.targets
+    }
+
+    /// Cost from `sources()[source]` to `targets()[target]`.
+    pub fn get(&self, source: usize, target: usize) -> f64 {
+        self.costs[source * self.targets.len() + target]
+    }
+
+    pub fn row(&self, source: usize) -> &[f64] {
+        &self.costs[source * self.targets.len()..(source + 1) * self.targets.len()]
+    }
+}
+
+/// One-to-many Dijkstra from every source, run in parallel. A search covers all targets at once and stops
+/// as soon as the last of them is settled, so nothing beyond the farthest target gets explored.
+pub fn distance_matrix<G, C>(graph: &G, sources: &[NodeID], targets: &[NodeID], cost: &C) -> DistanceMatrix where
+    G: GraphRead + Sync,
+    C: EdgeCost<G::Edge> + Sync {
+    debug_assert!(targets.iter().all(|target| graph.contains_node(*target)), "invalid target NodeID");
+
+    // duplicate targets only have to be waited for once
+    let distinct: HashSet<NodeID> = targets.iter().copied().collect();
+
+    let rows: Vec<Vec<f64>> = sources.par_iter()
+        .map(|&source| {
+            let mut search = DijkstraSearch::new(graph, cost);
+            search.add_source(source, 0.0, 0.0);
+
+            let mut remaining = distinct.len();
+            while remaining > 0 {
+                let Some(node) = search.settle_next(|_| 0.0) else {
+                    break;
+                };
+                if distinct.contains(&node) {
+                    remaining -= 1;
+                }
+            }
+
+            targets.iter().map(|target| if search.is_settled(*target) { search.distance(*target) } else { f64::INFINITY }).collect()
+        })
+        .collect();
+
+    info!("computed a {}x{} distance matrix", sources.len(), targets.len());
+    DistanceMatrix { sources: sources.to_vec(), targets: targets.to_vec(), costs: rows.concat() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exporter::{export_matrix_csv, export_matrix_json};
+    use crate::graph::{EdgeKind, Graph};
+    use crate::routing::{ByDistance, dijkstra::dijkstra, test_graphs::grid};
+
+    use super::*;
+
+    #[test]
+    fn matches_dijkstra_for_every_pair() {
+        let (graph, nodes) = grid(12, 12);
+        let sources: Vec<_> = [0, 17, 77, 143].iter().map(|i| nodes[*i]).collect();
+        let targets: Vec<_> = [5, 60, 71, 130, 143, 60].iter().map(|i| nodes[*i]).collect();
+
+        let matrix = distance_matrix(&graph, &sources, &targets, &ByDistance);
+        assert_eq!(matrix.sources(), sources.as_slice());
+        assert_eq!(matrix.targets(), targets.as_slice());
+
+        for (i, &source) in sources.iter().enumerate() {
+            assert_eq!(matrix.row(i).len(), targets.len());
+            for (j, &target) in targets.iter().enumerate() {
+                let expected = dijkstra(&graph, source, target, &ByDistance).path.unwrap().cost;
+                assert!((matrix.get(i, j) - expected).abs() < 1e-6, "{source:?} -> {target:?}: expected {expected}, got {}", matrix.get(i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn unreachable_targets_are_infinite() {
+        let mut graph = Graph::<char, f64>::new();
+        let a = graph.add_node('a');
+        let b = graph.add_node('b');
+        let c = graph.add_node('c');
+        graph.add_edge(a, b, 2.0, EdgeKind::Directed);
+
+        let matrix = distance_matrix(&graph, &[a, b], &[a, b, c], &|w: &f64| *w);
+        assert_eq!(matrix.row(0), &[0.0, 2.0, f64::INFINITY]);
+        assert_eq!(matrix.row(1), &[f64::INFINITY, 0.0, f64::INFINITY]);
+    }
+
+    #[test]
+    fn exports_csv_and_json() {
+        let mut graph = Graph::<char, f64>::new();
+        let a = graph.add_node('a');
+        let b = graph.add_node('b');
+        graph.add_edge(a, b, 2.5, EdgeKind::Directed);
+        let matrix = distance_matrix(&graph, &[a, b], &[b], &|w: &f64| *w);
+
+        let dir = std::env::temp_dir();
+        let csv_path = dir.join(format!("raptordb-matrix-{}.csv", std::process::id()));
+        let json_path = dir.join(format!("raptordb-matrix-{}.json", std::process::id()));
+        export_matrix_csv(&matrix, &csv_path).unwrap();
+        export_matrix_json(&matrix, &json_path).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&csv_path).unwrap(), "source,1\n0,2.5\n1,0\n");
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&json_path).unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!({ "sources": [0, 1], "targets": [1], "costs": [[2.5], [0.0]] }));
+
+        std::fs::remove_file(csv_path).unwrap();
+        std::fs::remove_file(json_path).unwrap();
+    }
+}
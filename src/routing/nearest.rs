@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use crate::graph::{GraphRead, NodeID};
+use crate::importer::{HasCoordinates, haversine_distance};
+
+// metres per degree, only used to size grid cells
+const METRES_PER_DEGREE_LAT: f64 = 110_540.0;
+const METRES_PER_DEGREE_LON: f64 = 111_320.0;
+
+/// Buckets the nodes of a graph into a grid of roughly square cells to find the ones close to a point.
+/// It's a snapshot, nodes added to the graph later aren't found.
+#[derive(Debug, Clone)]
+pub struct NearestNodes<N> {
+    cells: HashMap<(i64, i64), Vec<(NodeID, N)>>,
+    lat_step: f64,
+    lon_step: f64,
+}
+
+impl<N: HasCoordinates + Copy> NearestNodes<N> {
+    /// Cells are `cell_size` metres wide, queries for radii around that size are the fastest.
+    pub fn new<G: GraphRead<Node = N>>(graph: &G, cell_size: f64) -> Self {
+        // cells get narrower in degrees of longitude away from the equator, the first node is as good a reference as any
+        let reference_lat = graph.nodes().next().map_or(0.0, |node| graph.get_node(node).lat());
+        let mut index = Self {
+            cells: HashMap::new(),
+            lat_step: cell_size / METRES_PER_DEGREE_LAT,
+            lon_step: cell_size / (METRES_PER_DEGREE_LON * reference_lat.to_radians().cos().max(0.01)),
+        };
+
+        for node in graph.nodes() {
+            let property = *graph.get_node(node);
+            index.cells.entry(index.cell(&property)).or_default().push((node, property));
+        }
+        index
+    }
+
+    fn cell(&self, point: &impl HasCoordinates) -> (i64, i64) {
+        ((point.lat() / self.lat_step).floor() as i64, (point.lon() / self.lon_step).floor() as i64)
+    }
+
+    /// Nodes at most `radius` metres from `point` with their distance, closest first.
+    pub fn within(&self, point: &impl HasCoordinates, radius: f64) -> Vec<(NodeID, f64)> {
+        let (lat_cell, lon_cell) = self.cell(point);
+        let lat_reach = (radius / METRES_PER_DEGREE_LAT / self.lat_step).ceil() as i64;
+        // longitude degrees shrink towards the poles, the reference latitude may be closer to the equator than the point
+        let lon_reach = (radius / (METRES_PER_DEGREE_LON * point.lat().to_radians().cos().max(0.01)) / self.lon_step).ceil() as i64;
+
+        let mut found: Vec<(NodeID, f64)> = (lat_cell - lat_reach..=lat_cell + lat_reach)
+            .flat_map(|lat| (lon_cell - lon_reach..=lon_cell + lon_reach).map(move |lon| (lat, lon)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .map(|(node, property)| (*node, haversine_distance(point, property)))
+            .filter(|(_, distance)| *distance <= radius)
+            .collect();
+        found.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        found
+    }
+
+    /// Closest node at most `max_distance` metres from `point`.
+    pub fn nearest(&self, point: &impl HasCoordinates, max_distance: f64) -> Option<(NodeID, f64)> {
+        self.within(point, max_distance).into_iter().next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::importer::GraphNode;
+    use crate::routing::test_graphs::grid;
+
+    use super::*;
+
+    #[test]
+    fn matches_brute_force() {
+        let (graph, nodes) = grid(10, 10);
+        for cell_size in [50.0, 150.0, 1000.0] {
+            let index = NearestNodes::new(&graph, cell_size);
+            for (i, radius) in [(0, 120.0), (37, 300.0), (99, 10.0), (55, 0.0)] {
+                let point = GraphNode::new(50.0 + 0.0004 + (i / 10) as f64 * 0.001, 20.0 + 0.0003 + (i % 10) as f64 * 0.001);
+                let mut expected: Vec<NodeID> = nodes.iter().copied().filter(|node| haversine_distance(&point, graph.get_node(*node)) <= radius).collect();
+                expected.sort_by(|a, b| haversine_distance(&point, graph.get_node(*a)).total_cmp(&haversine_distance(&point, graph.get_node(*b))));
+
+                let found: Vec<NodeID> = index.within(&point, radius).into_iter().map(|(node, _)| node).collect();
+                assert_eq!(found, expected, "cell size {cell_size}, radius {radius}");
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_node() {
+        let (graph, nodes) = grid(5, 5);
+        let index = NearestNodes::new(&graph, 100.0);
+
+        let (node, distance) = index.nearest(&GraphNode::new(50.0021, 20.0029), 100.0).unwrap();
+        assert_eq!(node, nodes[2 * 5 + 3]);
+        assert!(distance < 20.0);
+        assert_eq!(index.nearest(&GraphNode::new(51.0, 21.0), 1000.0), None);
+    }
+}
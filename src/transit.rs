@@ -0,0 +1,166 @@
+pub mod timetable;
+pub mod calendar;
+pub mod raptor;
+pub mod csa;
+pub mod mcraptor;
+pub mod footpaths;
+pub mod multimodal;
+pub mod realtime;
+
+use crate::transit::timetable::{StopID, Time, TripID};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Leg {
+    Transit { trip: TripID, from: StopID, to: StopID, departure: Time, arrival: Time },
+    /// Walking between stops.
+    Transfer { from: StopID, to: StopID, departure: Time, arrival: Time },
+}
+
+impl Leg {
+    pub fn from(&self) -> StopID {
+        match self {
+            Leg::Transit { from, .. } | Leg::Transfer { from, .. } => *from,
+        }
+    }
+
+    pub fn to(&self) -> StopID {
+        match self {
+            Leg::Transit { to, .. } | Leg::Transfer { to, .. } => *to,
+        }
+    }
+
+    pub fn departure(&self) -> Time {
+        match self {
+            Leg::Transit { departure, .. } | Leg::Transfer { departure, .. } => *departure,
+        }
+    }
+
+    pub fn arrival(&self) -> Time {
+        match self {
+            Leg::Transit { arrival, .. } | Leg::Transfer { arrival, .. } => *arrival,
+        }
+    }
+}
+
+/// Way from a source stop to a target stop, consecutive legs meet at the same stop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Journey {
+    pub source: StopID,
+    /// Empty if the source is the target.
+    pub legs: Vec<Leg>,
+    /// When the source stop was reached, the first leg may leave later.
+    pub start: Time,
+}
+
+impl Journey {
+    pub fn target(&self) -> StopID {
+        self.legs.last().map_or(self.source, Leg::to)
+    }
+
+    pub fn arrival(&self) -> Time {
+        self.legs.last().map_or(self.start, Leg::arrival)
+    }
+
+    /// Vehicles changed along the way, one less than the transit legs.
+    pub fn transfers(&self) -> usize {
+        self.legs.iter().filter(|leg| matches!(leg, Leg::Transit { .. })).count().saturating_sub(1)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_timetables {
+    use crate::transit::timetable::{Line, NewTrip, Stop, StopEvent, StopID, Time, Timetable, Transfer};
+
+    /// Linear congruential generator, random tests see the same numbers on every run.
+    pub(crate) struct Lcg(u64);
+
+    impl Lcg {
+        pub(crate) fn new(seed: u64) -> Self {
+            Self(seed)
+        }
+
+        /// Number in `0..bound`.
+        pub(crate) fn below(&mut self, bound: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 33) as usize % bound
+        }
+    }
+
+    /// Transfers of a random timetable. RAPTOR's labels only know the arrival time and walks aren't chained, so it
+    /// could miss journeys where both change times and footpaths matter, or where footpaths aren't transitively closed.
+    pub(crate) enum RandomTransfers {
+        None,
+        /// Up to three minutes to change vehicles at every stop, no walking between stops.
+        ChangeTimes,
+        /// Footpaths between all stops, placed on a line and walking a metre per second, vehicles are changed at once.
+        Footpaths,
+    }
+
+    /// Shape of a random timetable, all of its trips belong to one line.
+    pub(crate) struct RandomTimetable {
+        pub(crate) stops: usize,
+        pub(crate) patterns: usize,
+        pub(crate) trips_per_pattern: usize,
+        /// Patterns call at two up to this many different stops.
+        pub(crate) max_calls: usize,
+        /// Trips start between this and `departure_window` seconds later.
+        pub(crate) first_departure: Time,
+        pub(crate) departure_window: u32,
+        /// Going to the next stop takes a minute plus up to this many seconds.
+        pub(crate) hop_spread: u32,
+        /// Vehicles wait up to this many seconds at every stop.
+        pub(crate) max_dwell: u32,
+        pub(crate) transfers: RandomTransfers,
+    }
+
+    impl RandomTimetable {
+        pub(crate) fn build(&self, random: &mut Lcg) -> (Timetable, Vec<StopID>) {
+            let mut timetable = Timetable::new();
+            let stops: Vec<_> = (0..self.stops)
+                .map(|i| timetable.add_stop(Stop { gtfs_id: i.to_string(), name: String::new(), lat: 0.0, lon: 0.0 }))
+                .collect();
+            let line = timetable.add_line(Line { gtfs_id: "L".into(), short_name: String::new(), long_name: String::new(), route_type: 3 });
+
+            for pattern_idx in 0..self.patterns {
+                let calls = 2 + random.below(self.max_calls - 1);
+                let mut pattern: Vec<StopID> = Vec::new();
+                while pattern.len() < calls {
+                    let stop = stops[random.below(stops.len())];
+                    if !pattern.contains(&stop) {
+                        pattern.push(stop);
+                    }
+                }
+
+                for trip in 0..self.trips_per_pattern {
+                    let mut time = self.first_departure + random.below(self.departure_window as usize) as u32;
+                    let events = pattern.iter().map(|_| {
+                        let arrival = time;
+                        let departure = arrival + random.below(self.max_dwell as usize + 1) as u32;
+                        time = departure + 60 + random.below(self.hop_spread as usize + 1) as u32;
+                        StopEvent { arrival, departure }
+                    }).collect();
+                    timetable.add_trip(NewTrip { gtfs_id: format!("{pattern_idx}-{trip}"), line, service_id: "S".into(), stops: pattern.clone(), events });
+                }
+            }
+
+            match self.transfers {
+                RandomTransfers::None => {}
+                RandomTransfers::ChangeTimes => {
+                    for &stop in &stops {
+                        timetable.add_transfer(Transfer { from: stop, to: stop, duration: random.below(4) as u32 * 60 });
+                    }
+                }
+                RandomTransfers::Footpaths => {
+                    let positions: Vec<u32> = stops.iter().map(|_| random.below(2000) as u32).collect();
+                    for (i, &from) in stops.iter().enumerate() {
+                        for (j, &to) in stops.iter().enumerate().filter(|(j, _)| *j != i) {
+                            timetable.add_transfer(Transfer { from, to, duration: positions[i].abs_diff(positions[j]) + 1 });
+                        }
+                    }
+                }
+            }
+
+            (timetable, stops)
+        }
+    }
+}
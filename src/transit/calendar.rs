@@ -0,0 +1,164 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// Calendar day, counted in days since 1970-01-01.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Date(i32);
+
+impl Date {
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        // days from civil, see http://howardhinnant.github.io/date_algorithms.html
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month as i32 + 9) % 12) + 2) / 5 + day as i32 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Some(Self(era * 146_097 + day_of_era - 719_468))
+    }
+
+    /// Parses GTFS `YYYYMMDD`.
+    pub fn parse(text: &str) -> Option<Self> {
+        if text.len() != 8 || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        Self::from_ymd(text[..4].parse().ok()?, text[4..6].parse().ok()?, text[6..].parse().ok()?)
+    }
+
+    /// Year, month and day.
+    pub fn ymd(self) -> (i32, u32, u32) {
+        let days = self.0 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+        let year = year_of_era + era * 400 + i32::from(month <= 2);
+        (year, month, day)
+    }
+
+    /// 0 for Monday up to 6 for Sunday, the order of the weekday columns in `calendar.txt`.
+    pub fn weekday(self) -> usize {
+        // 1970-01-01 was a Thursday
+        (self.0 + 3).rem_euclid(7) as usize
+    }
+
+    pub fn add_days(self, days: i32) -> Self {
+        Self(self.0 + days)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Weekly pattern of a service from `calendar.txt`, valid between two dates including both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServicePeriod {
+    /// Monday first.
+    pub weekdays: [bool; 7],
+    pub start: Date,
+    pub end: Date,
+}
+
+/// Days every service runs on, weekly patterns with single dates added or removed by `calendar_dates.txt`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Calendar {
+    periods: HashMap<String, ServicePeriod>,
+    /// `true` where the service runs on a date it otherwise wouldn't, `false` where it doesn't run.
+    exceptions: HashMap<String, BTreeMap<Date, bool>>,
+}
+
+impl Calendar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the service's period if it already had one.
+    pub fn add_period(&mut self, service_id: String, period: ServicePeriod) {
+        self.periods.insert(service_id, period);
+    }
+
+    pub fn add_exception(&mut self, service_id: String, date: Date, runs: bool) {
+        self.exceptions.entry(service_id).or_default().insert(date, runs);
+    }
+
+    /// Unknown services never run.
+    pub fn is_active(&self, service_id: &str, date: Date) -> bool {
+        if let Some(runs) = self.exceptions.get(service_id).and_then(|exceptions| exceptions.get(&date)) {
+            return *runs;
+        }
+        self.periods.get(service_id)
+            .is_some_and(|period| period.start <= date && date <= period.end && period.weekdays[date.weekday()])
+    }
+
+    pub fn active_services(&self, date: Date) -> BTreeSet<&str> {
+        self.periods.keys()
+            .chain(self.exceptions.keys())
+            .map(String::as_str)
+            .filter(|service_id| self.is_active(service_id, date))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_round_trip() {
+        assert_eq!(Date::from_ymd(1970, 1, 1), Some(Date(0)));
+        assert_eq!(Date::parse("20260501").unwrap().to_string(), "2026-05-01");
+        assert_eq!(Date::parse("20240229").unwrap().add_days(1).to_string(), "2024-03-01");
+        assert_eq!(Date::parse("20231231").map(|date| date.add_days(1)), Date::from_ymd(2024, 1, 1));
+        assert_eq!(Date::parse("20230229"), None);
+        assert_eq!(Date::parse("2026-5-1"), None);
+
+        let start = Date::from_ymd(1899, 12, 25).unwrap();
+        for days in 0..100_000 {
+            let date = start.add_days(days);
+            let (year, month, day) = date.ymd();
+            assert_eq!(Date::from_ymd(year, month, day), Some(date));
+        }
+    }
+
+    #[test]
+    fn weekdays() {
+        // a Friday and a Sunday
+        assert_eq!(Date::from_ymd(2026, 5, 1).unwrap().weekday(), 4);
+        assert_eq!(Date::from_ymd(1969, 12, 28).unwrap().weekday(), 6);
+    }
+
+    #[test]
+    fn exceptions_override_periods() {
+        let mut calendar = Calendar::new();
+        let weekdays = [true, true, true, true, true, false, false];
+        calendar.add_period("WD".into(), ServicePeriod { weekdays, start: Date::parse("20260101").unwrap(), end: Date::parse("20261231").unwrap() });
+        calendar.add_exception("WD".into(), Date::parse("20260501").unwrap(), false);
+        calendar.add_exception("HOLIDAY".into(), Date::parse("20260501").unwrap(), true);
+
+        assert!(calendar.is_active("WD", Date::parse("20260430").unwrap()));
+        assert!(!calendar.is_active("WD", Date::parse("20260501").unwrap()));
+        assert!(!calendar.is_active("WD", Date::parse("20260502").unwrap()));
+        assert!(!calendar.is_active("WD", Date::parse("20270104").unwrap()));
+        assert!(!calendar.is_active("unknown", Date::parse("20260430").unwrap()));
+        assert_eq!(calendar.active_services(Date::parse("20260501").unwrap()), BTreeSet::from(["HOLIDAY"]));
+    }
+}
@@ -0,0 +1,423 @@
+use log::debug;
+
+use crate::graph::IDIntoUSize;
+use crate::transit::raptor::RaptorData;
+use crate::transit::timetable::{RouteID, StopID, Time, TripID};
+use crate::transit::{Journey, Leg};
+
+const UNREACHED: Time = Time::from_seconds(u32::MAX);
+
+/// A trip going from one stop to the next without stopping in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Connection {
+    pub trip: TripID,
+    pub from: StopID,
+    pub to: StopID,
+    pub departure: Time,
+    pub arrival: Time,
+}
+
+/// Connection Scan Algorithm over the same routes, trips and transfers as RAPTOR, with the same rules: walks aren't
+/// chained and changing vehicles at a stop takes its change time. Unlike RAPTOR it has no notion of transfers.
+#[derive(Debug, Clone)]
+pub struct ConnectionScan<'a> {
+    data: &'a RaptorData,
+    /// Sorted by departure, a trip's connections stay in their order.
+    connections: Vec<Connection>,
+    /// Trip IDs are below this.
+    trip_count: usize,
+}
+
+/// Earliest arrival query from any number of sources.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsaQuery {
+    /// Stops the journey may start at, with the seconds it takes to get to each of them after `departure`.
+    pub sources: Vec<(StopID, u32)>,
+    pub departure: Time,
+    /// Stops the scan as soon as no connection can get there any earlier, other stops may then miss their earliest arrival.
+    pub target: Option<StopID>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reach {
+    Unreached,
+    Source,
+    /// On a trip from its connection `enter` up to the end of its connection `exit`.
+    Ride { enter: usize, exit: usize },
+    /// Walked from the end of the leg that got to `from`.
+    Walk { from: StopID },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Label {
+    time: Time,
+    reach: Reach,
+}
+
+const UNREACHED_LABEL: Label = Label { time: UNREACHED, reach: Reach::Unreached };
+
+impl Label {
+    fn improve(&mut self, time: Time, reach: Reach) {
+        if time < self.time {
+            *self = Label { time, reach };
+        }
+    }
+}
+
+impl<'a> ConnectionScan<'a> {
+    pub fn new(data: &'a RaptorData) -> Self {
+        let mut connections = Vec::new();
+        for route in (0..data.route_count()).map(RouteID::from_usize) {
+            let entry = data.route(route);
+            for trip in 0..entry.trip_count {
+                for position in 1..entry.stop_count {
+                    connections.push(Connection {
+                        trip: data.route_trip(entry, trip),
+                        from: data.route_stop(entry, position - 1),
+                        to: data.route_stop(entry, position),
+                        departure: data.event(entry, trip, position - 1).departure,
+                        arrival: data.event(entry, trip, position).arrival,
+                    });
+                }
+            }
+        }
+        // a trip's connections could share departure and arrival, the stable sort keeps them in order
+        connections.sort_by_key(|connection| (connection.departure, connection.arrival));
+        let trip_count = connections.iter().map(|connection| connection.trip.as_usize() + 1).max().unwrap_or(0);
+        Self { data, connections, trip_count }
+    }
+
+    pub fn connections(&self) -> &[Connection] {
+        &self.connections
+    }
+
+    /// Scans the connections leaving at or after the departure once, in order.
+    pub fn query(&self, query: &CsaQuery) -> CsaResult<'_> {
+        let stop_count = self.data.stop_count();
+        let mut labels = Labels {
+            arrival: vec![UNREACHED_LABEL; stop_count],
+            ready: vec![UNREACHED_LABEL; stop_count],
+            leg_end: vec![UNREACHED_LABEL; stop_count],
+        };
+
+        for &(stop, offset) in &query.sources {
+            let time = query.departure + offset;
+            labels.arrival[stop.as_usize()].improve(time, Reach::Source);
+            labels.ready[stop.as_usize()].improve(time, Reach::Source);
+            labels.leg_end[stop.as_usize()].improve(time, Reach::Source);
+        }
+        for &(stop, _) in &query.sources {
+            self.walk_from(&mut labels, stop);
+        }
+
+        // connection of every trip it was boarded with
+        let mut entered: Vec<Option<usize>> = vec![None; self.trip_count];
+        let first = self.connections.partition_point(|connection| connection.departure < query.departure);
+        let mut scanned = 0;
+        for (idx, connection) in self.connections.iter().enumerate().skip(first) {
+            if query.target.is_some_and(|target| connection.departure >= labels.arrival[target.as_usize()].time) {
+                break;
+            }
+            scanned += 1;
+
+            let trip = &mut entered[connection.trip.as_usize()];
+            if trip.is_none() && labels.ready[connection.from.as_usize()].time <= connection.departure {
+                *trip = Some(idx);
+            }
+            let Some(enter) = *trip else {
+                continue;
+            };
+
+            let to = connection.to;
+            if connection.arrival < labels.leg_end[to.as_usize()].time {
+                let reach = Reach::Ride { enter, exit: idx };
+                labels.leg_end[to.as_usize()] = Label { time: connection.arrival, reach };
+                labels.arrival[to.as_usize()].improve(connection.arrival, reach);
+                labels.ready[to.as_usize()].improve(connection.arrival + self.data.min_change(to), reach);
+                self.walk_from(&mut labels, to);
+            }
+        }
+
+        debug!("connection scan from {:?} looked at {scanned} of {} connections", query.sources, self.connections.len());
+        CsaResult { scan: self, labels }
+    }
+
+    /// Walks from the end of the leg that reached `from`.
+    fn walk_from(&self, labels: &mut Labels, from: StopID) {
+        let departure = labels.leg_end[from.as_usize()].time;
+        for &(to, duration) in self.data.transfers_from(from) {
+            labels.arrival[to.as_usize()].improve(departure + duration, Reach::Walk { from });
+            labels.ready[to.as_usize()].improve(departure + duration, Reach::Walk { from });
+        }
+    }
+
+    /// Profile search towards a target: scans the connections leaving at or after `earliest_departure` backwards,
+    /// finding for every stop when to leave it to get to the target how early.
+    pub fn profiles_to(&self, target: StopID, earliest_departure: Time) -> TargetProfiles<'_> {
+        let stop_count = self.data.stop_count();
+        let walk_to_target: Vec<Option<u32>> = (0..stop_count)
+            .map(|stop| self.data.transfers_from(StopID::from_usize(stop)).iter().find(|(to, _)| *to == target).map(|(_, duration)| *duration))
+            .collect();
+
+        let mut profiles = vec![ArrivalProfile::default(); stop_count];
+        // earliest arrival at the target staying on every trip
+        let mut on_trip: Vec<Time> = vec![UNREACHED; self.trip_count];
+        let first = self.connections.partition_point(|connection| connection.departure < earliest_departure);
+
+        for connection in self.connections[first..].iter().rev() {
+            let to = connection.to;
+            let arrival = connection.arrival;
+            let walked = if to == target { Some(arrival) } else { walk_to_target[to.as_usize()].map(|duration| arrival + duration) };
+            let changed = self.data.transfers_from(to).iter()
+                .filter_map(|&(other, duration)| profiles[other.as_usize()].arrival(arrival + duration))
+                .chain(profiles[to.as_usize()].arrival(arrival + self.data.min_change(to)))
+                .min();
+            let best = [walked, changed, Some(on_trip[connection.trip.as_usize()])].into_iter().flatten().min().unwrap_or(UNREACHED);
+            if best == UNREACHED {
+                continue;
+            }
+
+            on_trip[connection.trip.as_usize()] = best;
+            profiles[connection.from.as_usize()].add(connection.departure, best);
+        }
+
+        TargetProfiles { scan: self, target, walk_to_target, profiles }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Labels {
+    /// Earliest arrival by any means.
+    arrival: Vec<Label>,
+    /// Earliest time a trip can be boarded, after the change time when arriving by vehicle.
+    ready: Vec<Label>,
+    /// Earliest arrival at the end of a ride or at a source, walks start from these.
+    leg_end: Vec<Label>,
+}
+
+/// Labels of a query, journeys are reconstructed from them on demand.
+#[derive(Debug, Clone)]
+pub struct CsaResult<'a> {
+    scan: &'a ConnectionScan<'a>,
+    labels: Labels,
+}
+
+impl CsaResult<'_> {
+    pub fn earliest_arrival(&self, stop: StopID) -> Option<Time> {
+        let arrival = self.labels.arrival[stop.as_usize()].time;
+        (arrival != UNREACHED).then_some(arrival)
+    }
+
+    /// Journey with the earliest arrival, no matter how many transfers it takes.
+    pub fn journey_to(&self, stop: StopID) -> Option<Journey> {
+        self.earliest_arrival(stop)?;
+        let mut legs = Vec::new();
+        let (mut stop, mut label) = (stop, self.labels.arrival[stop.as_usize()]);
+
+        loop {
+            match label.reach {
+                Reach::Unreached => unreachable!("journeys are only reconstructed to reached stops"),
+                Reach::Source => {
+                    legs.reverse();
+                    return Some(Journey { source: stop, legs, start: label.time });
+                }
+                Reach::Walk { from } => {
+                    let end = self.labels.leg_end[from.as_usize()];
+                    legs.push(Leg::Transfer { from, to: stop, departure: end.time, arrival: label.time });
+                    (stop, label) = (from, end);
+                }
+                Reach::Ride { enter, exit } => {
+                    let (enter, exit) = (&self.scan.connections[enter], &self.scan.connections[exit]);
+                    legs.push(Leg::Transit { trip: enter.trip, from: enter.from, to: exit.to, departure: enter.departure, arrival: exit.arrival });
+                    (stop, label) = (enter.from, self.labels.ready[enter.from.as_usize()]);
+                }
+            }
+        }
+    }
+}
+
+/// Pareto set of departures from a stop, boarding a vehicle there, and arrivals at the target.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ArrivalProfile {
+    /// By decreasing departure and arrival, in the order the backward scan finds them.
+    entries: Vec<(Time, Time)>,
+}
+
+impl ArrivalProfile {
+    /// Departures with their arrivals, earliest first.
+    pub fn entries(&self) -> impl Iterator<Item = (Time, Time)> + '_ {
+        self.entries.iter().rev().copied()
+    }
+
+    /// Earliest arrival boarding at `departure` or later.
+    pub fn arrival(&self, departure: Time) -> Option<Time> {
+        let later = self.entries.partition_point(|(leaving, _)| *leaving >= departure);
+        later.checked_sub(1).map(|idx| self.entries[idx].1)
+    }
+
+    fn add(&mut self, departure: Time, arrival: Time) {
+        if self.arrival(departure).is_some_and(|best| best <= arrival) {
+            return;
+        }
+        if self.entries.last().is_some_and(|(leaving, _)| *leaving == departure) {
+            self.entries.pop();
+        }
+        self.entries.push((departure, arrival));
+    }
+}
+
+/// Profiles of every stop towards one target.
+#[derive(Debug, Clone)]
+pub struct TargetProfiles<'a> {
+    scan: &'a ConnectionScan<'a>,
+    target: StopID,
+    walk_to_target: Vec<Option<u32>>,
+    profiles: Vec<ArrivalProfile>,
+}
+
+impl TargetProfiles<'_> {
+    pub fn target(&self) -> StopID {
+        self.target
+    }
+
+    /// When to board at `stop`, not counting the ways to the target starting with a walk.
+    pub fn profile(&self, stop: StopID) -> &ArrivalProfile {
+        &self.profiles[stop.as_usize()]
+    }
+
+    /// Earliest arrival at the target leaving `source` at `departure`, the same as a query from it would find.
+    pub fn arrival(&self, source: StopID, departure: Time) -> Option<Time> {
+        if source == self.target {
+            return Some(departure);
+        }
+        let walked = self.walk_to_target[source.as_usize()].map(|duration| departure + duration);
+        let boarded = self.scan.data.transfers_from(source).iter()
+            .filter_map(|&(other, duration)| self.profiles[other.as_usize()].arrival(departure + duration))
+            .chain(self.profiles[source.as_usize()].arrival(departure))
+            .min();
+        walked.into_iter().chain(boarded).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::importer::gtfs::import_gtfs;
+    use crate::transit::raptor::RaptorQuery;
+    use crate::transit::test_timetables::{Lcg, RandomTimetable, RandomTransfers};
+    use crate::transit::timetable::Timetable;
+
+    use super::*;
+
+    #[test]
+    fn test_feed() {
+        let timetable = import_gtfs(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")).unwrap();
+        let data = RaptorData::new(&timetable);
+        let scan = ConnectionScan::new(&data);
+        assert!(scan.connections().windows(2).all(|pair| pair[0].departure <= pair[1].departure));
+        let [a, b, c, d, e] = ["A", "B", "C", "D", "E"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+
+        let result = scan.query(&CsaQuery { sources: vec![(a, 0)], departure: Time::from_hms(7, 55, 0), target: None });
+        assert_eq!(result.earliest_arrival(c), Some(Time::from_hms(8, 15, 0)));
+        assert_eq!(result.earliest_arrival(e), Some(Time::from_hms(8, 40, 0)));
+
+        // the express to B with the change time there, then the tram
+        let journey = result.journey_to(e).unwrap();
+        let express = timetable.trip_by_gtfs_id("R1_0805_EXPRESS").unwrap();
+        let tram = timetable.trip_by_gtfs_id("R2_0815").unwrap();
+        assert_eq!(journey.legs, vec![
+            Leg::Transit { trip: express, from: a, to: b, departure: Time::from_hms(8, 5, 0), arrival: Time::from_hms(8, 9, 0) },
+            Leg::Transit { trip: tram, from: b, to: e, departure: Time::from_hms(8, 15, 0), arrival: Time::from_hms(8, 40, 0) },
+        ]);
+        assert_eq!(journey.start, Time::from_hms(7, 55, 0));
+
+        // C is a walk away from D, quicker than the tram
+        let journey = result.journey_to(d).unwrap();
+        assert!(matches!(journey.legs.as_slice(), [Leg::Transit { trip, .. }, Leg::Transfer { .. }] if *trip == express), "{journey:?}");
+        assert_eq!(journey.arrival(), Time::from_hms(8, 18, 0));
+        let late = scan.query(&CsaQuery { sources: vec![(a, 0)], departure: Time::from_hms(8, 20, 0), target: Some(d) });
+        let journey = late.journey_to(d).unwrap();
+        assert!(matches!(journey.legs.as_slice(), [Leg::Transit { to, .. }, Leg::Transfer { from, .. }] if *to == c && *from == c), "{journey:?}");
+        assert_eq!(journey.arrival(), Time::from_hms(8, 53, 0));
+
+        let profiles = scan.profiles_to(e, Time::from_hms(0, 0, 0));
+        assert_eq!(profiles.profile(b).entries().collect::<Vec<_>>(), vec![
+            (Time::from_hms(8, 15, 0), Time::from_hms(8, 40, 0)),
+            (Time::from_hms(23, 50, 0), Time::from_hms(24, 20, 0)),
+        ]);
+        // the express is the last bus to get to the tram in time
+        assert_eq!(profiles.arrival(a, Time::from_hms(8, 5, 0)), Some(Time::from_hms(8, 40, 0)));
+        assert_eq!(profiles.arrival(a, Time::from_hms(8, 6, 0)), Some(Time::from_hms(24, 20, 0)));
+        assert_eq!(profiles.arrival(e, Time::from_hms(8, 6, 0)), Some(Time::from_hms(8, 6, 0)));
+    }
+
+    #[test]
+    fn matches_raptor_on_random_queries() {
+        let mut random = Lcg::new(49);
+        let mut shape = RandomTimetable {
+            stops: 10,
+            patterns: 8,
+            trips_per_pattern: 6,
+            max_calls: 5,
+            first_departure: Time::from_hms(6, 0, 0),
+            departure_window: 4 * 3600,
+            hop_spread: 900,
+            max_dwell: 60,
+            transfers: RandomTransfers::None,
+        };
+
+        // within these two regimes both algorithms find the earliest arrival
+        for instance in 0..20 {
+            shape.transfers = if instance % 2 == 0 { RandomTransfers::Footpaths } else { RandomTransfers::ChangeTimes };
+            let (timetable, stops) = shape.build(&mut random);
+            let data = RaptorData::new(&timetable);
+            let scan = ConnectionScan::new(&data);
+            let target = stops[random.below(stops.len())];
+            let profiles = scan.profiles_to(target, Time::from_hms(6, 0, 0));
+
+            for _ in 0..20 {
+                let source = stops[random.below(stops.len())];
+                let departure = Time::from_seconds(6 * 3600 + random.below(5 * 3600) as u32);
+                let raptor = data.query(&RaptorQuery { sources: vec![(source, 0)], departure, max_transfers: timetable.trip_count() });
+                let csa = scan.query(&CsaQuery { sources: vec![(source, 0)], departure, target: None });
+
+                for &stop in &stops {
+                    assert_eq!(csa.earliest_arrival(stop), raptor.earliest_arrival(stop), "instance {instance} from {source} at {departure} to {stop}");
+                    if let Some(journey) = csa.journey_to(stop) {
+                        assert_eq!((journey.source, journey.target(), journey.arrival()), (source, stop, csa.earliest_arrival(stop).unwrap()));
+                        assert_consistent(&timetable, &data, &journey);
+                    }
+                }
+
+                let pruned = scan.query(&CsaQuery { sources: vec![(source, 0)], departure, target: Some(target) });
+                assert_eq!(pruned.earliest_arrival(target), raptor.earliest_arrival(target));
+                assert_eq!(profiles.arrival(source, departure), raptor.earliest_arrival(target), "instance {instance} from {source} at {departure}");
+            }
+        }
+    }
+
+    /// Legs connect, follow the timetable and leave time to change vehicles.
+    fn assert_consistent(timetable: &Timetable, data: &RaptorData, journey: &Journey) {
+        let mut at = (journey.source, journey.start, false);
+        for leg in &journey.legs {
+            assert_eq!(leg.from(), at.0, "{journey:?}");
+            match *leg {
+                Leg::Transit { trip, from, to, departure, arrival } => {
+                    let change = if at.2 { data.min_change(from) } else { 0 };
+                    assert!(departure >= at.1 + change, "{journey:?}");
+                    let route = timetable.route(timetable.trip(trip).route);
+                    let events = timetable.trip_events(trip);
+                    let board = route.stops.iter().position(|stop| *stop == from).unwrap();
+                    let alight = route.stops.iter().skip(board + 1).position(|stop| *stop == to).unwrap() + board + 1;
+                    assert_eq!((events[board].departure, events[alight].arrival), (departure, arrival));
+                    at = (to, arrival, true);
+                }
+                Leg::Transfer { to, departure, arrival, .. } => {
+                    assert!(departure >= at.1 && arrival >= departure, "{journey:?}");
+                    at = (to, arrival, false);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use log::info;
+use rayon::prelude::*;
+
+use crate::graph::{GraphRead, IDIntoUSize, NodeID};
+use crate::importer::{GraphWay, HasCoordinates};
+use crate::routing::{ByDistance, isochrone::reachable, nearest::NearestNodes};
+use crate::transit::timetable::{StopID, Timetable, Transfer};
+
+/// Generates footpaths between stops by walking on a pedestrian graph from the node every stop snaps to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Footpaths {
+    /// Longest direct walk in metres, including the way from the stops to the graph and back.
+    pub max_distance: f64,
+    /// Metres per second.
+    pub walking_speed: f64,
+    /// Stops further than this many metres from every node get no walked footpaths.
+    pub max_snap_distance: f64,
+}
+
+impl Default for Footpaths {
+    fn default() -> Self {
+        Self { max_distance: 400.0, walking_speed: 1.3, max_snap_distance: 150.0 }
+    }
+}
+
+impl Footpaths {
+    /// Nearest node of every stop with the distance to it.
+    pub fn snap<G>(&self, graph: &G, timetable: &Timetable) -> Vec<Option<(NodeID, f64)>> where
+        G: GraphRead,
+        G::Node: HasCoordinates + Copy {
+        let index = NearestNodes::new(graph, self.max_snap_distance.max(1.0));
+        timetable.stops().map(|stop| index.nearest(timetable.stop(stop), self.max_snap_distance)).collect()
+    }
+
+    /// Footpaths between distinct stops, walked ones together with those of the timetable, closed under transitivity:
+    /// whenever there's a footpath from a to b and one from b to c, there's one from a to c, as RAPTOR doesn't chain walks.
+    /// The timetable's duration is kept wherever it has a footpath, even if walking is faster.
+    /// Large walking distances make the closure dense, every stop gets a footpath to every other one in its walkable area.
+    pub fn generate<G>(&self, graph: &G, timetable: &Timetable) -> Vec<Transfer> where
+        G: GraphRead<Edge = GraphWay> + Sync,
+        G::Node: HasCoordinates + Copy {
+        let snapped = self.snap(graph, timetable);
+        let mut stops_at: HashMap<NodeID, Vec<(StopID, f64)>> = HashMap::new();
+        for (stop, snap) in timetable.stops().zip(&snapped) {
+            if let Some((node, distance)) = snap {
+                stops_at.entry(*node).or_default().push((stop, *distance));
+            }
+        }
+
+        let mut direct: Vec<HashMap<StopID, u32>> = snapped.par_iter()
+            .enumerate()
+            .map(|(from, snap)| {
+                let mut walks = HashMap::new();
+                let Some((node, snap_distance)) = *snap else {
+                    return walks;
+                };
+                for (reached, distance) in reachable(graph, node, &ByDistance, self.max_distance - snap_distance) {
+                    for &(to, to_distance) in stops_at.get(&reached).into_iter().flatten() {
+                        let total = snap_distance + distance + to_distance;
+                        if to.as_usize() != from && total <= self.max_distance {
+                            let duration = (total / self.walking_speed).ceil() as u32;
+                            walks.entry(to).and_modify(|best: &mut u32| *best = (*best).min(duration)).or_insert(duration);
+                        }
+                    }
+                }
+                walks
+            })
+            .collect();
+
+        for stop in timetable.stops() {
+            for transfer in timetable.transfers_from(stop).filter(|transfer| transfer.to != stop) {
+                direct[stop.as_usize()].insert(transfer.to, transfer.duration);
+            }
+        }
+
+        let transfers: Vec<Transfer> = timetable.stops()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .flat_map_iter(|&from| closure(&direct, from).into_iter().map(move |(to, duration)| Transfer { from, to, duration }))
+            .collect();
+        info!("generated {} footpaths between {} stops", transfers.len(), timetable.stop_count());
+        transfers
+    }
+
+    /// Adds the generated footpaths the timetable doesn't have yet, returns how many.
+    pub fn add_to<G>(&self, graph: &G, timetable: &mut Timetable) -> usize where
+        G: GraphRead<Edge = GraphWay> + Sync,
+        G::Node: HasCoordinates + Copy {
+        let new: Vec<Transfer> = self.generate(graph, timetable)
+            .into_iter()
+            .filter(|transfer| !timetable.transfers_from(transfer.from).any(|existing| existing.to == transfer.to))
+            .collect();
+        let count = new.len();
+        for transfer in new {
+            timetable.add_transfer(transfer);
+        }
+        count
+    }
+}
+
+/// Quickest chain of footpaths from `from` to every stop it reaches, direct ones keep their own duration.
+fn closure(direct: &[HashMap<StopID, u32>], from: StopID) -> Vec<(StopID, u32)> {
+    let mut durations: HashMap<StopID, u32> = HashMap::new();
+    let mut queue = BinaryHeap::from([Reverse((0, from))]);
+    while let Some(Reverse((duration, stop))) = queue.pop() {
+        if durations.contains_key(&stop) {
+            continue;
+        }
+        durations.insert(stop, duration);
+        for (&to, &walk) in &direct[stop.as_usize()] {
+            if !durations.contains_key(&to) {
+                queue.push(Reverse((duration + walk, to)));
+            }
+        }
+    }
+
+    let mut reached: Vec<(StopID, u32)> = durations.into_iter()
+        .filter(|(to, _)| *to != from)
+        .map(|(to, duration)| (to, direct[from.as_usize()].get(&to).copied().unwrap_or(duration)))
+        .collect();
+    reached.sort();
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routing::test_graphs::grid;
+    use crate::transit::raptor::{RaptorData, RaptorQuery};
+    use crate::transit::timetable::{Line, NewTrip, Stop, StopEvent, Time};
+    use crate::transit::Leg;
+
+    use super::*;
+
+    fn stop(timetable: &mut Timetable, id: &str, lat: f64, lon: f64) -> StopID {
+        timetable.add_stop(Stop { gtfs_id: id.into(), name: String::new(), lat, lon })
+    }
+
+    fn durations(transfers: &[Transfer]) -> Vec<(usize, usize, u32)> {
+        let mut durations: Vec<_> = transfers.iter().map(|t| (t.from.as_usize(), t.to.as_usize(), t.duration)).collect();
+        durations.sort();
+        durations
+    }
+
+    #[test]
+    fn walks_on_the_graph_and_closes_transitively() {
+        // the grid's nodes are about 111 m apart, some ways are longer
+        let (graph, nodes) = grid(6, 1);
+        let at = |i: usize| graph.get_node(nodes[i]);
+        let mut timetable = Timetable::new();
+        let a = stop(&mut timetable, "a", at(0).lat(), at(0).lon());
+        let b = stop(&mut timetable, "b", at(2).lat(), at(2).lon());
+        let c = stop(&mut timetable, "c", at(4).lat(), at(4).lon());
+        let far = stop(&mut timetable, "far", 51.0, 21.0);
+
+        let footpaths = Footpaths { max_distance: 260.0, walking_speed: 1.0, max_snap_distance: 50.0 };
+        assert_eq!(footpaths.snap(&graph, &timetable)[far.as_usize()], None);
+
+        let distance = |from: usize, to: usize| {
+            let walks = reachable(&graph, nodes[from], &ByDistance, f64::INFINITY);
+            walks[&nodes[to]]
+        };
+        let (ab, bc) = (distance(0, 2).ceil() as u32, distance(2, 4).ceil() as u32);
+        assert!(ab <= 260 && bc <= 260 && distance(0, 4) > 260.0, "grid changed, {ab} {bc}");
+
+        let transfers = footpaths.generate(&graph, &timetable);
+        let ba = distance(2, 0).ceil() as u32;
+        let cb = distance(4, 2).ceil() as u32;
+        // a and c are too far apart to walk directly, but b is in between
+        assert_eq!(durations(&transfers), vec![
+            (a.as_usize(), b.as_usize(), ab),
+            (a.as_usize(), c.as_usize(), ab + bc),
+            (b.as_usize(), a.as_usize(), ba),
+            (b.as_usize(), c.as_usize(), bc),
+            (c.as_usize(), a.as_usize(), cb + ba),
+            (c.as_usize(), b.as_usize(), cb),
+        ]);
+    }
+
+    #[test]
+    fn timetable_transfers_are_kept_and_extended() {
+        let (graph, nodes) = grid(3, 1);
+        let at = |i: usize| graph.get_node(nodes[i]);
+        let mut timetable = Timetable::new();
+        let a = stop(&mut timetable, "a", at(0).lat(), at(0).lon());
+        let b = stop(&mut timetable, "b", at(1).lat(), at(1).lon());
+        // far from the graph, only connected by the feed
+        let c = stop(&mut timetable, "c", 51.0, 21.0);
+        timetable.add_transfer(Transfer { from: a, to: b, duration: 600 });
+        timetable.add_transfer(Transfer { from: b, to: c, duration: 60 });
+        timetable.add_transfer(Transfer { from: b, to: b, duration: 120 });
+
+        let footpaths = Footpaths { max_distance: 200.0, walking_speed: 1.0, max_snap_distance: 50.0 };
+        let transfers = footpaths.generate(&graph, &timetable);
+        let walked_ba = reachable(&graph, nodes[1], &ByDistance, f64::INFINITY)[&nodes[0]].ceil() as u32;
+        assert_eq!(durations(&transfers), vec![
+            (a.as_usize(), b.as_usize(), 600),
+            (a.as_usize(), c.as_usize(), 660),
+            (b.as_usize(), a.as_usize(), walked_ba),
+            (b.as_usize(), c.as_usize(), 60),
+        ]);
+
+        assert_eq!(footpaths.add_to(&graph, &mut timetable), 2);
+        assert_eq!(footpaths.add_to(&graph, &mut timetable), 0);
+        assert_eq!(timetable.transfers_from(b).count(), 3);
+    }
+
+    #[test]
+    fn raptor_uses_generated_footpaths() {
+        let (graph, nodes) = grid(4, 1);
+        let at = |i: usize| graph.get_node(nodes[i]);
+        let mut timetable = Timetable::new();
+        let stops: Vec<_> = (0..4).map(|i| stop(&mut timetable, &i.to_string(), at(i).lat(), at(i).lon())).collect();
+        let line = timetable.add_line(Line { gtfs_id: "L".into(), short_name: String::new(), long_name: String::new(), route_type: 3 });
+        // the vehicle ends two nodes short of the last stop and is quicker than walking all the way
+        timetable.add_trip(NewTrip {
+            gtfs_id: "T".into(),
+            line,
+            service_id: "S".into(),
+            stops: vec![stops[0], stops[1]],
+            events: [10, 20].map(|time| StopEvent { arrival: Time::from_seconds(time), departure: Time::from_seconds(time) }).to_vec(),
+        });
+
+        let query = RaptorQuery { sources: vec![(stops[0], 0)], departure: Time::from_seconds(0), max_transfers: 0 };
+        assert_eq!(RaptorData::new(&timetable).query(&query).earliest_arrival(stops[3]), None);
+
+        Footpaths { max_distance: 200.0, walking_speed: 1.0, max_snap_distance: 10.0 }.add_to(&graph, &mut timetable);
+        let data = RaptorData::new(&timetable);
+        let journey = data.query(&query).journey_to(stops[3]).unwrap();
+        assert!(matches!(journey.legs.as_slice(), [Leg::Transit { .. }, Leg::Transfer { from, .. }] if *from == stops[1]));
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use log::debug;
+
+use crate::graph::IDIntoUSize;
+use crate::transit::raptor::{RaptorData, RaptorQuery};
+use crate::transit::timetable::{LineID, RouteID, StopID, Time, Timetable};
+use crate::transit::{Journey, Leg};
+
+/// Additional criterion to minimize besides arrival time and transfers, summed up over the legs of a journey.
+/// Closures taking the timetable and a leg implement it.
+pub trait Criterion {
+    /// Must not be negative, routes are pruned assuming criteria only grow along a journey. A discount has to be
+    /// modelled as a lower price of the leg it applies to.
+    fn leg_cost(&self, timetable: &Timetable, leg: &Leg) -> f64;
+}
+
+impl<F: Fn(&Timetable, &Leg) -> f64> Criterion for F {
+    fn leg_cost(&self, timetable: &Timetable, leg: &Leg) -> f64 {
+        self(timetable, leg)
+    }
+}
+
+/// Seconds spent walking between stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WalkingTime;
+
+impl Criterion for WalkingTime {
+    fn leg_cost(&self, _timetable: &Timetable, leg: &Leg) -> f64 {
+        match leg {
+            Leg::Transfer { departure, arrival, .. } => (arrival.seconds() - departure.seconds()) as f64,
+            Leg::Transit { .. } => 0.0,
+        }
+    }
+}
+
+/// Price paid for every vehicle boarded, depending on its line.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Fare {
+    pub by_line: HashMap<LineID, f64>,
+    /// Price on lines without their own.
+    pub default: f64,
+}
+
+impl Criterion for Fare {
+    fn leg_cost(&self, timetable: &Timetable, leg: &Leg) -> f64 {
+        match leg {
+            Leg::Transit { trip, .. } => {
+                let line = timetable.route(timetable.trip(*trip).route).line;
+                self.by_line.get(&line).copied().unwrap_or(self.default)
+            }
+            Leg::Transfer { .. } => 0.0,
+        }
+    }
+}
+
+/// Journey of a Pareto set, with its cost for every criterion in the order they were added.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParetoJourney {
+    pub journey: Journey,
+    pub costs: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct McLabel {
+    stop: StopID,
+    arrival: Time,
+    costs: Vec<f64>,
+    /// Number of trips taken so far.
+    round: usize,
+    by_vehicle: bool,
+    /// Leg that led here and the label it started from, `None` at the sources.
+    parent: Option<(Leg, usize)>,
+}
+
+impl McLabel {
+    /// Doesn't look at rounds, labels of earlier rounds never have more trips.
+    fn dominates(&self, other: &McLabel) -> bool {
+        self.arrival <= other.arrival && self.costs.iter().zip(&other.costs).all(|(own, other)| own <= other)
+    }
+}
+
+/// Labels of a query, bags only point into them so journeys stay reconstructable when labels get dominated.
+#[derive(Debug, Default)]
+struct Bags {
+    labels: Vec<McLabel>,
+    /// Pareto set of arrival time, trips and costs per stop over all rounds so far.
+    bags: Vec<Vec<usize>>,
+}
+
+impl Bags {
+    /// Adds the label unless something in the bag dominates it, and drops what it dominates in its own round.
+    fn insert(&mut self, label: McLabel) -> Option<usize> {
+        let bag = &mut self.bags[label.stop.as_usize()];
+        if bag.iter().any(|idx| self.labels[*idx].dominates(&label)) {
+            return None;
+        }
+        bag.retain(|idx| {
+            let other = &self.labels[*idx];
+            other.round < label.round || !label.dominates(other)
+        });
+        bag.push(self.labels.len());
+        self.labels.push(label);
+        Some(self.labels.len() - 1)
+    }
+}
+
+/// Multi-criteria RAPTOR: keeps a bag of Pareto optimal labels per stop instead of a single arrival time.
+/// Journeys are Pareto optimal in arrival time, transfers and the sum of every added [`Criterion`].
+pub struct McRaptor<'a> {
+    timetable: &'a Timetable,
+    data: &'a RaptorData,
+    criteria: Vec<Box<dyn Criterion + 'a>>,
+}
+
+impl<'a> McRaptor<'a> {
+    /// `data` has to be built from `timetable`.
+    pub fn new(timetable: &'a Timetable, data: &'a RaptorData) -> Self {
+        Self { timetable, data, criteria: Vec::new() }
+    }
+
+    pub fn with_criterion(mut self, criterion: impl Criterion + 'a) -> Self {
+        self.criteria.push(Box::new(criterion));
+        self
+    }
+
+    fn extend(&self, label: &McLabel, idx: usize, leg: Leg, round: usize) -> McLabel {
+        let leg_costs = self.criteria.iter().map(|criterion| criterion.leg_cost(self.timetable, &leg));
+        McLabel {
+            stop: leg.to(),
+            arrival: leg.arrival(),
+            costs: label.costs.iter().zip(leg_costs)
+                .map(|(cost, leg_cost)| {
+                    debug_assert!(leg_cost >= 0.0, "criteria can't have negative costs, {leg:?} costs {leg_cost}");
+                    cost + leg_cost
+                })
+                .collect(),
+            round,
+            by_vehicle: matches!(leg, Leg::Transit { .. }),
+            parent: Some((leg, idx)),
+        }
+    }
+
+    pub fn query(&self, query: &RaptorQuery) -> McRaptorResult {
+        let stop_count = self.data.stop_count();
+        let mut bags = Bags { labels: Vec::new(), bags: vec![Vec::new(); stop_count] };
+
+        // labels of the current round that were added, walks and the next round start from them
+        let mut added = Vec::new();
+        for &(stop, offset) in &query.sources {
+            let label = McLabel { stop, arrival: query.departure + offset, costs: vec![0.0; self.criteria.len()], round: 0, by_vehicle: false, parent: None };
+            added.extend(bags.insert(label).map(|idx| (stop, idx)));
+        }
+        self.relax_transfers(&mut bags, &mut added, 0);
+
+        let mut queue: Vec<Option<usize>> = vec![None; self.data.route_count()];
+        for round in 1..=query.max_transfers + 1 {
+            // labels may have been dominated within their round since
+            added.retain(|(stop, idx)| bags.bags[stop.as_usize()].contains(idx));
+            if added.is_empty() {
+                break;
+            }
+
+            let mut starts: Vec<Vec<usize>> = vec![Vec::new(); stop_count];
+            for &(stop, idx) in &added {
+                starts[stop.as_usize()].push(idx);
+                for &(route, position) in self.data.routes_at(stop) {
+                    let start = &mut queue[route.as_usize()];
+                    *start = Some(start.map_or(position, |start| start.min(position)));
+                }
+            }
+
+            let mut rides = Vec::new();
+            for (route, start) in queue.iter_mut().enumerate() {
+                if let Some(start) = start.take() {
+                    self.scan_route(RouteID::from_usize(route), start, &starts, &mut bags, &mut rides, round);
+                }
+            }
+            added = rides;
+            self.relax_transfers(&mut bags, &mut added, round);
+        }
+
+        debug!("mcraptor query from {:?} created {} labels", query.sources, bags.labels.len());
+        McRaptorResult { bags }
+    }
+
+    fn scan_route(&self, id: RouteID, start: usize, starts: &[Vec<usize>], bags: &mut Bags, rides: &mut Vec<(StopID, usize)>, round: usize) {
+        let route = self.data.route(id);
+        // trip index in the route, the position it was boarded at and the label boarding it
+        let mut boarded: Vec<(usize, usize, usize)> = Vec::new();
+
+        for position in start..route.stop_count {
+            let stop = self.data.route_stop(route, position);
+
+            for &(trip, board, idx) in &boarded {
+                let leg = Leg::Transit {
+                    trip: self.data.route_trip(route, trip),
+                    from: self.data.route_stop(route, board),
+                    to: stop,
+                    departure: self.data.event(route, trip, board).departure,
+                    arrival: self.data.event(route, trip, position).arrival,
+                };
+                let label = self.extend(&bags.labels[idx], idx, leg, round);
+                rides.extend(bags.insert(label).map(|idx| (stop, idx)));
+            }
+
+            for &idx in &starts[stop.as_usize()] {
+                let label = &bags.labels[idx];
+                let ready = if label.by_vehicle { label.arrival + self.data.min_change(stop) } else { label.arrival };
+                let trip = self.data.first_departure(route, position, ready);
+                // a later trip from the same label never pays off, criteria only grow along legs
+                if trip < route.trip_count && !boarded.iter().any(|(other, _, other_idx)| *other_idx == idx && *other <= trip) {
+                    boarded.push((trip, position, idx));
+                }
+            }
+        }
+    }
+
+    /// Walks from every label added in this round, walks aren't chained.
+    fn relax_transfers(&self, bags: &mut Bags, added: &mut Vec<(StopID, usize)>, round: usize) {
+        let mut walks = Vec::new();
+        for &(from, idx) in added.iter() {
+            let departure = bags.labels[idx].arrival;
+            for &(to, duration) in self.data.transfers_from(from) {
+                let leg = Leg::Transfer { from, to, departure, arrival: departure + duration };
+                let label = self.extend(&bags.labels[idx], idx, leg, round);
+                walks.extend(bags.insert(label).map(|idx| (to, idx)));
+            }
+        }
+        added.append(&mut walks);
+    }
+}
+
+/// Labels of a McRAPTOR query.
+#[derive(Debug)]
+pub struct McRaptorResult {
+    bags: Bags,
+}
+
+impl McRaptorResult {
+    /// Pareto optimal journeys to the stop, by arrival time.
+    pub fn journeys_to(&self, stop: StopID) -> Vec<ParetoJourney> {
+        let mut journeys: Vec<_> = self.bags.bags[stop.as_usize()].iter().map(|idx| self.reconstruct(*idx)).collect();
+        journeys.sort_by(|a, b| a.journey.arrival().cmp(&b.journey.arrival()).then(a.journey.legs.len().cmp(&b.journey.legs.len())));
+        journeys
+    }
+
+    fn reconstruct(&self, target: usize) -> ParetoJourney {
+        let mut legs = Vec::new();
+        let mut idx = target;
+        while let Some((leg, parent)) = self.bags.labels[idx].parent {
+            legs.push(leg);
+            idx = parent;
+        }
+        legs.reverse();
+
+        let start = &self.bags.labels[idx];
+        let journey = Journey { source: start.stop, legs, start: start.arrival };
+        ParetoJourney { journey, costs: self.bags.labels[target].costs.clone() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::importer::gtfs::import_gtfs;
+    use crate::transit::test_timetables::{Lcg, RandomTimetable, RandomTransfers};
+    use crate::transit::timetable::{Line, NewTrip, Stop, StopEvent};
+
+    use super::*;
+
+    fn query(source: StopID, departure: Time, max_transfers: usize) -> RaptorQuery {
+        RaptorQuery { sources: vec![(source, 0)], departure, max_transfers }
+    }
+
+    #[test]
+    fn walking_against_transfers_in_test_feed() {
+        let timetable = import_gtfs(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")).unwrap();
+        let data = RaptorData::new(&timetable);
+        let [a, d] = ["A", "D"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+
+        let result = McRaptor::new(&timetable, &data).with_criterion(WalkingTime).query(&query(a, Time::from_hms(7, 55, 0), 2));
+        let journeys = result.journeys_to(d);
+        // the express and a walk from C, or changing at B without walking
+        assert_eq!(
+            journeys.iter().map(|j| (j.journey.arrival(), j.journey.transfers(), j.costs.clone())).collect::<Vec<_>>(),
+            vec![(Time::from_hms(8, 18, 0), 0, vec![180.0]), (Time::from_hms(8, 25, 0), 1, vec![0.0])]
+        );
+        for journey in &journeys {
+            assert_eq!(journey.journey.source, a);
+            assert_eq!(journey.journey.target(), d);
+        }
+    }
+
+    #[test]
+    fn fares_keep_slower_cheaper_journeys() {
+        let mut timetable = Timetable::new();
+        let stops: Vec<_> = (0..2).map(|i| timetable.add_stop(Stop { gtfs_id: i.to_string(), name: String::new(), lat: 0.0, lon: 0.0 })).collect();
+        let mut lines = ["fast", "slow", "slower"].map(|id| timetable.add_line(Line { gtfs_id: id.into(), short_name: String::new(), long_name: String::new(), route_type: 3 })).into_iter();
+        for (line, arrival) in [(lines.next().unwrap(), 200), (lines.next().unwrap(), 300), (lines.next().unwrap(), 400)] {
+            timetable.add_trip(NewTrip {
+                gtfs_id: arrival.to_string(),
+                line,
+                service_id: "S".into(),
+                stops: stops.clone(),
+                events: [100, arrival].map(|time| StopEvent { arrival: Time::from_seconds(time), departure: Time::from_seconds(time) }).to_vec(),
+            });
+        }
+        let fare = Fare { by_line: HashMap::from([(LineID::from_usize(0), 5.0), (LineID::from_usize(1), 2.0)]), default: 3.0 };
+
+        let data = RaptorData::new(&timetable);
+        let result = McRaptor::new(&timetable, &data).with_criterion(fare).query(&query(stops[0], Time::from_seconds(0), 0));
+        let journeys = result.journeys_to(stops[1]);
+        // the slowest is dearer than the slow one
+        assert_eq!(journeys.iter().map(|j| (j.journey.arrival().seconds(), j.costs[0])).collect::<Vec<_>>(), vec![(200, 5.0), (300, 2.0)]);
+        assert_eq!(result.journeys_to(stops[0]).len(), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "negative costs")]
+    fn negative_costs_are_rejected() {
+        let timetable = import_gtfs(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")).unwrap();
+        let data = RaptorData::new(&timetable);
+        let a = timetable.stop_by_gtfs_id("A").unwrap();
+        let discount = |_: &Timetable, _: &Leg| -1.0;
+
+        McRaptor::new(&timetable, &data).with_criterion(discount).query(&query(a, Time::from_hms(7, 55, 0), 2));
+    }
+
+    #[test]
+    fn closures_are_criteria() {
+        let timetable = import_gtfs(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")).unwrap();
+        let data = RaptorData::new(&timetable);
+        let [a, e] = ["A", "E"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+        let legs = |_: &Timetable, _: &Leg| 1.0;
+
+        let result = McRaptor::new(&timetable, &data).with_criterion(legs).query(&query(a, Time::from_hms(7, 55, 0), 2));
+        for journey in result.journeys_to(e) {
+            assert_eq!(journey.costs, vec![journey.journey.legs.len() as f64]);
+        }
+    }
+
+    #[test]
+    fn without_criteria_matches_raptor() {
+        let mut random = Lcg::new(99);
+        let mut shape = RandomTimetable {
+            stops: 8,
+            patterns: 5,
+            trips_per_pattern: 4,
+            max_calls: 4,
+            first_departure: Time::from_hms(7, 0, 0),
+            departure_window: 2 * 3600,
+            hop_spread: 600,
+            max_dwell: 0,
+            transfers: RandomTransfers::None,
+        };
+
+        for instance in 0..10 {
+            shape.transfers = if instance % 2 == 0 { RandomTransfers::Footpaths } else { RandomTransfers::ChangeTimes };
+            let (timetable, stops) = shape.build(&mut random);
+            let data = RaptorData::new(&timetable);
+            let query = query(stops[0], Time::from_hms(7, 30, 0), 4);
+            let raptor = data.query(&query);
+            let mcraptor = McRaptor::new(&timetable, &data).query(&query);
+            for &stop in &stops {
+                let mut expected: Vec<_> = raptor.journeys_to(stop).iter().map(|j| (j.arrival(), j.transfers())).collect();
+                expected.reverse();
+                let found: Vec<_> = mcraptor.journeys_to(stop).iter().map(|j| (j.journey.arrival(), j.journey.transfers())).collect();
+                assert_eq!(found, expected);
+            }
+        }
+    }
+}
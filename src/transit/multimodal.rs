@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+
+use log::debug;
+
+use crate::graph::{GraphRead, IDIntoUSize, NodeID};
+use crate::importer::{GraphWay, HasCoordinates, haversine_distance};
+use crate::routing::{ByDistance, EdgeCost, dijkstra::{DijkstraSearch, dijkstra}, nearest::NearestNodes};
+use crate::transit::raptor::{RaptorData, RaptorQuery};
+use crate::transit::timetable::{StopID, Time, Timetable, TripID};
+use crate::transit::{Journey, Leg};
+
+/// End of a leg of an itinerary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Place {
+    Origin,
+    Destination,
+    Stop(StopID),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItineraryLeg {
+    Walk {
+        from: Place,
+        to: Place,
+        departure: Time,
+        arrival: Time,
+        /// Metres.
+        distance: f64,
+        /// `[lon, lat]` positions along the streets, from the start of the walk to its end.
+        geometry: Vec<[f64; 2]>,
+    },
+    Transit { trip: TripID, from: StopID, to: StopID, departure: Time, arrival: Time },
+}
+
+impl ItineraryLeg {
+    pub fn departure(&self) -> Time {
+        match self {
+            ItineraryLeg::Walk { departure, .. } | ItineraryLeg::Transit { departure, .. } => *departure,
+        }
+    }
+
+    pub fn arrival(&self) -> Time {
+        match self {
+            ItineraryLeg::Walk { arrival, .. } | ItineraryLeg::Transit { arrival, .. } => *arrival,
+        }
+    }
+}
+
+/// Door to door: from the origin to the destination, walking to, between and from stops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Itinerary {
+    /// Never empty.
+    pub legs: Vec<ItineraryLeg>,
+}
+
+impl Itinerary {
+    pub fn departure(&self) -> Time {
+        self.legs[0].departure()
+    }
+
+    pub fn arrival(&self) -> Time {
+        self.legs[self.legs.len() - 1].arrival()
+    }
+
+    pub fn transit_legs(&self) -> usize {
+        self.legs.iter().filter(|leg| matches!(leg, ItineraryLeg::Transit { .. })).count()
+    }
+
+    /// Journey between stops as found by RAPTOR, walks go in a straight line from stop to stop. `None` for a journey
+    /// without legs, the one from a stop to itself.
+    pub fn from_journey(timetable: &Timetable, journey: &Journey) -> Option<Self> {
+        if journey.legs.is_empty() {
+            return None;
+        }
+        let legs = journey.legs.iter().map(|leg| match *leg {
+            Leg::Transit { trip, from, to, departure, arrival } => ItineraryLeg::Transit { trip, from, to, departure, arrival },
+            Leg::Transfer { from, to, departure, arrival } => {
+                let (start, end) = (timetable.stop(from), timetable.stop(to));
+                ItineraryLeg::Walk {
+                    from: Place::Stop(from),
+                    to: Place::Stop(to),
+                    departure,
+                    arrival,
+                    distance: haversine_distance(start, end),
+                    geometry: vec![[start.lon, start.lat], [end.lon, end.lat]],
+                }
+            }
+        }).collect();
+        Some(Self { legs })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DoorToDoor {
+    /// Longest walk to the first stop and from the last one, in metres.
+    pub max_walk_distance: f64,
+    /// Metres per second.
+    pub walking_speed: f64,
+    /// Points further than this many metres from every node of the graph can't be routed from or to.
+    pub max_snap_distance: f64,
+    pub max_transfers: usize,
+}
+
+impl Default for DoorToDoor {
+    fn default() -> Self {
+        Self { max_walk_distance: 1000.0, walking_speed: 1.3, max_snap_distance: 200.0, max_transfers: 5 }
+    }
+}
+
+/// Way to the destination found by a query, built into an itinerary only if it's Pareto optimal.
+struct Candidate {
+    arrival: Time,
+    vehicles: usize,
+    /// `None` for walking all the way.
+    transit: Option<TransitPart>,
+}
+
+/// Journey between two stops with the walks to and from it, in metres.
+struct TransitPart {
+    journey: Journey,
+    access_distance: f64,
+    egress_distance: f64,
+}
+
+/// Routes between arbitrary points: the first and last mile are walked on the road graph, the stops reached
+/// that way are the sources and targets of a RAPTOR query in between.
+pub struct MultimodalRouter<'a, G: GraphRead> {
+    graph: &'a G,
+    timetable: &'a Timetable,
+    data: &'a RaptorData,
+    options: DoorToDoor,
+    index: NearestNodes<G::Node>,
+    stop_nodes: Vec<Option<(NodeID, f64)>>,
+}
+
+impl<'a, G> MultimodalRouter<'a, G> where
+    G: GraphRead<Edge = GraphWay>,
+    G::Node: HasCoordinates + Copy {
+    /// `data` has to be built from `timetable`, stops get snapped to the graph right away.
+    pub fn new(graph: &'a G, timetable: &'a Timetable, data: &'a RaptorData, options: DoorToDoor) -> Self {
+        let index = NearestNodes::new(graph, options.max_snap_distance.max(1.0));
+        let stop_nodes = timetable.stops().map(|stop| index.nearest(timetable.stop(stop), options.max_snap_distance)).collect();
+        Self { graph, timetable, data, options, index, stop_nodes }
+    }
+
+    fn walking_time(&self, distance: f64) -> u32 {
+        (distance / self.options.walking_speed).ceil() as u32
+    }
+
+    fn position(point: &impl HasCoordinates) -> [f64; 2] {
+        [point.lon(), point.lat()]
+    }
+
+    /// Itineraries leaving at `departure` that are Pareto optimal in arrival time and number of vehicles, by arrival.
+    /// Walking all the way is one of them if it's within the walking distance. Empty if nothing gets there.
+    pub fn route(&self, origin: &impl HasCoordinates, destination: &impl HasCoordinates, departure: Time) -> Vec<Itinerary> {
+        let (Some(origin_snap), Some(destination_snap)) = (
+            self.index.nearest(origin, self.options.max_snap_distance),
+            self.index.nearest(destination, self.options.max_snap_distance),
+        ) else {
+            return Vec::new();
+        };
+
+        let access = self.walk_from(self.graph, origin_snap);
+        let reversed = self.graph.reversed();
+        let egress = self.walk_from(&reversed, destination_snap);
+
+        let access_stops = self.reached_stops(&access, origin_snap);
+        let egress_stops = self.reached_stops(&egress, destination_snap);
+
+        let result = self.data.query(&RaptorQuery {
+            sources: access_stops.iter().map(|(stop, distance)| (*stop, self.walking_time(*distance))).collect(),
+            departure,
+            max_transfers: self.options.max_transfers,
+        });
+
+        let mut candidates = Vec::new();
+        let direct = origin_snap.1 + access.distance(destination_snap.0) + destination_snap.1;
+        if access.is_settled(destination_snap.0) && direct <= self.options.max_walk_distance {
+            candidates.push(Candidate { arrival: departure + self.walking_time(direct), vehicles: 0, transit: None });
+        }
+        let access_distances: HashMap<StopID, f64> = access_stops.iter().copied().collect();
+        for &(stop, egress_distance) in &egress_stops {
+            for journey in result.journeys_to(stop) {
+                let vehicles = journey.legs.iter().filter(|leg| matches!(leg, Leg::Transit { .. })).count();
+                if vehicles > 0 {
+                    let arrival = journey.arrival() + self.walking_time(egress_distance);
+                    let access_distance = access_distances[&journey.source];
+                    candidates.push(Candidate { arrival, vehicles, transit: Some(TransitPart { journey, access_distance, egress_distance }) });
+                }
+            }
+        }
+
+        candidates.sort_by_key(|candidate| (candidate.vehicles, candidate.arrival));
+        let mut best = None;
+        let mut itineraries = Vec::new();
+        for candidate in candidates {
+            if best.is_some_and(|best| best <= candidate.arrival) {
+                continue;
+            }
+            best = Some(candidate.arrival);
+            let itinerary = match candidate.transit {
+                None => Itinerary { legs: vec![self.walk_leg(Place::Origin, Place::Destination, departure, direct, self.street_geometry(origin, &access, destination_snap.0, destination, false))] },
+                Some(transit) => self.itinerary(origin, destination, departure, &access, &egress, &transit),
+            };
+            itineraries.push(itinerary);
+        }
+        itineraries.sort_by_key(Itinerary::arrival);
+
+        debug!("found {} itineraries from {} access and {} egress stops", itineraries.len(), access_stops.len(), egress_stops.len());
+        itineraries
+    }
+
+    /// Walks from the snapped node as far as the access and egress walks can go.
+    fn walk_from<'b, R: GraphRead>(&self, graph: &'b R, snap: (NodeID, f64)) -> DijkstraSearch<'b, R, ByDistance> where ByDistance: EdgeCost<R::Edge> {
+        let mut search = DijkstraSearch::new(graph, &ByDistance);
+        search.add_source(snap.0, 0.0, 0.0);
+        while search.peek_key().is_some_and(|key| key <= self.options.max_walk_distance - snap.1) {
+            search.settle_next(|_| 0.0);
+        }
+        search
+    }
+
+    /// Stops within walking distance, with the distance to them.
+    fn reached_stops<R: GraphRead>(&self, search: &DijkstraSearch<R, ByDistance>, snap: (NodeID, f64)) -> Vec<(StopID, f64)> where ByDistance: EdgeCost<R::Edge> {
+        self.timetable.stops()
+            .filter_map(|stop| {
+                let (stop_node, stop_snap) = self.stop_nodes[stop.as_usize()]?;
+                let distance = snap.1 + search.distance(stop_node) + stop_snap;
+                (search.is_settled(stop_node) && distance <= self.options.max_walk_distance).then_some((stop, distance))
+            })
+            .collect()
+    }
+
+    fn walk_leg(&self, from: Place, to: Place, departure: Time, distance: f64, geometry: Vec<[f64; 2]>) -> ItineraryLeg {
+        ItineraryLeg::Walk { from, to, departure, arrival: departure + self.walking_time(distance), distance, geometry }
+    }
+
+    /// From `start` along the search's path to `node` and on to `end`, the other way round for searches on the reversed graph.
+    fn street_geometry<R: GraphRead>(&self, start: &impl HasCoordinates, search: &DijkstraSearch<R, ByDistance>, node: NodeID, end: &impl HasCoordinates, reversed: bool) -> Vec<[f64; 2]> where
+        ByDistance: EdgeCost<R::Edge> {
+        let mut nodes = search.path_to(node).map(|path| path.nodes).unwrap_or_default();
+        if reversed {
+            nodes.reverse();
+        }
+        let mut geometry = vec![Self::position(start)];
+        geometry.extend(nodes.iter().map(|node| Self::position(self.graph.get_node(*node))));
+        geometry.push(Self::position(end));
+        geometry
+    }
+
+    fn itinerary<A: GraphRead, E: GraphRead>(
+        &self,
+        origin: &impl HasCoordinates,
+        destination: &impl HasCoordinates,
+        departure: Time,
+        access: &DijkstraSearch<A, ByDistance>,
+        egress: &DijkstraSearch<E, ByDistance>,
+        transit: &TransitPart,
+    ) -> Itinerary where
+        ByDistance: EdgeCost<A::Edge> + EdgeCost<E::Edge> {
+        let journey = &transit.journey;
+        let mut legs = Vec::new();
+        let source = journey.source;
+        let (source_node, _) = self.stop_nodes[source.as_usize()].expect("access stops are snapped");
+        legs.push(self.walk_leg(Place::Origin, Place::Stop(source), departure, transit.access_distance,
+            self.street_geometry(origin, access, source_node, self.timetable.stop(source), false)));
+
+        for leg in &journey.legs {
+            legs.push(match *leg {
+                Leg::Transit { trip, from, to, departure, arrival } => ItineraryLeg::Transit { trip, from, to, departure, arrival },
+                Leg::Transfer { from, to, departure, arrival } => {
+                    let (distance, geometry) = self.transfer_geometry(from, to);
+                    ItineraryLeg::Walk { from: Place::Stop(from), to: Place::Stop(to), departure, arrival, distance, geometry }
+                }
+            });
+        }
+
+        let target = journey.target();
+        let (target_node, _) = self.stop_nodes[target.as_usize()].expect("egress stops are snapped");
+        legs.push(self.walk_leg(Place::Stop(target), Place::Destination, journey.arrival(), transit.egress_distance,
+            self.street_geometry(self.timetable.stop(target), egress, target_node, destination, true)));
+
+        Itinerary { legs }
+    }
+
+    /// Shortest way between the stops on the graph, or a straight line for footpaths the graph doesn't connect.
+    fn transfer_geometry(&self, from: StopID, to: StopID) -> (f64, Vec<[f64; 2]>) {
+        let (from_stop, to_stop) = (self.timetable.stop(from), self.timetable.stop(to));
+        let path = match (self.stop_nodes[from.as_usize()], self.stop_nodes[to.as_usize()]) {
+            (Some((from_node, from_snap)), Some((to_node, to_snap))) => dijkstra(self.graph, from_node, to_node, &ByDistance).path
+                .map(|path| (from_snap + path.cost + to_snap, path.nodes)),
+            _ => None,
+        };
+
+        match path {
+            Some((distance, nodes)) => {
+                let mut geometry = vec![Self::position(from_stop)];
+                geometry.extend(nodes.iter().map(|node| Self::position(self.graph.get_node(*node))));
+                geometry.push(Self::position(to_stop));
+                (distance, geometry)
+            }
+            None => (haversine_distance(from_stop, to_stop), vec![Self::position(from_stop), Self::position(to_stop)]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::Graph;
+    use crate::importer::GraphNode;
+    use crate::routing::test_graphs::grid;
+    use crate::transit::timetable::{Line, NewTrip, Stop, StopEvent, Transfer};
+
+    use super::*;
+
+    /// A street of ten nodes about 111 m apart, stops at the second and the second to last node with a quick trip between them.
+    fn street() -> (Graph<GraphNode, GraphWay>, Vec<NodeID>, Timetable, [StopID; 2]) {
+        let (graph, nodes) = grid(10, 1);
+        let mut timetable = Timetable::new();
+        let [first, last] = [1, 8].map(|i| {
+            let node = graph.get_node(nodes[i]);
+            timetable.add_stop(Stop { gtfs_id: i.to_string(), name: String::new(), lat: node.lat(), lon: node.lon() + 0.0001 })
+        });
+        let line = timetable.add_line(Line { gtfs_id: "L".into(), short_name: String::new(), long_name: String::new(), route_type: 3 });
+        timetable.add_trip(NewTrip {
+            gtfs_id: "T".into(),
+            line,
+            service_id: "S".into(),
+            stops: vec![first, last],
+            events: [600, 700].map(|time| StopEvent { arrival: Time::from_seconds(time), departure: Time::from_seconds(time) }).to_vec(),
+        });
+        (graph, nodes, timetable, [first, last])
+    }
+
+    fn near(graph: &Graph<GraphNode, GraphWay>, node: NodeID) -> GraphNode {
+        let node = graph.get_node(node);
+        GraphNode::new(node.lat() + 0.0001, node.lon())
+    }
+
+    #[test]
+    fn walks_to_and_from_the_stops() {
+        let (graph, nodes, timetable, [first, last]) = street();
+        let data = RaptorData::new(&timetable);
+        let options = DoorToDoor { max_walk_distance: 300.0, walking_speed: 1.0, max_snap_distance: 50.0, max_transfers: 2 };
+        let router = MultimodalRouter::new(&graph, &timetable, &data, options);
+        let (origin, destination) = (near(&graph, nodes[0]), near(&graph, nodes[9]));
+
+        let itineraries = router.route(&origin, &destination, Time::from_seconds(0));
+        assert_eq!(itineraries.len(), 1);
+        let legs = &itineraries[0].legs;
+        assert_eq!(legs.len(), 3);
+
+        let ItineraryLeg::Walk { from: Place::Origin, to: Place::Stop(stop), departure, arrival, distance, geometry } = &legs[0] else {
+            panic!("{legs:?}");
+        };
+        assert_eq!((*stop, *departure), (first, Time::from_seconds(0)));
+        assert!(*distance > 111.0 && *distance < 300.0);
+        assert_eq!(arrival.seconds(), distance.ceil() as u32);
+        assert_eq!(geometry.first(), Some(&[origin.lon(), origin.lat()]));
+        assert_eq!(geometry.last(), Some(&[timetable.stop(first).lon, timetable.stop(first).lat]));
+        assert_eq!(geometry.len(), 4);
+
+        assert!(matches!(legs[1], ItineraryLeg::Transit { from, to, .. } if from == first && to == last));
+
+        let ItineraryLeg::Walk { from: Place::Stop(stop), to: Place::Destination, departure, geometry, .. } = &legs[2] else {
+            panic!("{legs:?}");
+        };
+        assert_eq!((*stop, *departure), (last, Time::from_seconds(700)));
+        assert_eq!(geometry.first(), Some(&[timetable.stop(last).lon, timetable.stop(last).lat]));
+        assert_eq!(geometry[1], [graph.get_node(nodes[8]).lon(), graph.get_node(nodes[8]).lat()]);
+        assert_eq!(geometry.last(), Some(&[destination.lon(), destination.lat()]));
+        assert_eq!(itineraries[0].transit_legs(), 1);
+    }
+
+    #[test]
+    fn walking_all_the_way_when_close() {
+        let (graph, nodes, timetable, _) = street();
+        let data = RaptorData::new(&timetable);
+        let options = DoorToDoor { max_walk_distance: 300.0, walking_speed: 1.0, max_snap_distance: 50.0, max_transfers: 2 };
+        let router = MultimodalRouter::new(&graph, &timetable, &data, options);
+
+        let itineraries = router.route(&near(&graph, nodes[0]), &near(&graph, nodes[2]), Time::from_seconds(0));
+        assert_eq!(itineraries.len(), 1);
+        assert!(matches!(itineraries[0].legs.as_slice(), [ItineraryLeg::Walk { from: Place::Origin, to: Place::Destination, .. }]));
+
+        assert!(router.route(&GraphNode::new(51.0, 21.0), &near(&graph, nodes[2]), Time::from_seconds(0)).is_empty());
+        // the trip has left by the time the stop is reached
+        assert!(router.route(&near(&graph, nodes[0]), &near(&graph, nodes[9]), Time::from_seconds(590)).is_empty());
+    }
+
+    #[test]
+    fn transfers_get_street_geometry() {
+        let (graph, nodes, mut timetable, [first, last]) = street();
+        let node = graph.get_node(nodes[6]);
+        let middle = timetable.add_stop(Stop { gtfs_id: "6".into(), name: String::new(), lat: node.lat(), lon: node.lon() });
+        let line = timetable.add_line(Line { gtfs_id: "M".into(), short_name: String::new(), long_name: String::new(), route_type: 3 });
+        // gets off two nodes short of the last stop and walks on
+        timetable.add_trip(NewTrip {
+            gtfs_id: "early".into(),
+            line,
+            service_id: "S".into(),
+            stops: vec![first, middle],
+            events: [300, 350].map(|time| StopEvent { arrival: Time::from_seconds(time), departure: Time::from_seconds(time) }).to_vec(),
+        });
+        timetable.add_transfer(Transfer { from: middle, to: last, duration: 250 });
+        let data = RaptorData::new(&timetable);
+        let options = DoorToDoor { max_walk_distance: 150.0, walking_speed: 1.0, max_snap_distance: 50.0, max_transfers: 2 };
+        let router = MultimodalRouter::new(&graph, &timetable, &data, options);
+
+        let itineraries = router.route(&near(&graph, nodes[1]), &near(&graph, nodes[8]), Time::from_seconds(0));
+        let walk = itineraries.iter()
+            .flat_map(|itinerary| &itinerary.legs)
+            .find_map(|leg| match leg {
+                ItineraryLeg::Walk { from: Place::Stop(from), to: Place::Stop(to), geometry, .. } if (*from, *to) == (middle, last) => Some(geometry),
+                _ => None,
+            })
+            .expect("walks from the middle stop");
+        let positions: Vec<[f64; 2]> = [6, 7, 8].map(|i| [graph.get_node(nodes[i]).lon(), graph.get_node(nodes[i]).lat()]).to_vec();
+        assert_eq!(&walk[1..4], positions.as_slice());
+    }
+
+    #[test]
+    fn journeys_between_stops_become_itineraries() {
+        let (graph, nodes, mut timetable, [first, last]) = street();
+        let node = graph.get_node(nodes[9]);
+        let end = timetable.add_stop(Stop { gtfs_id: "9".into(), name: String::new(), lat: node.lat(), lon: node.lon() });
+        timetable.add_transfer(Transfer { from: last, to: end, duration: 120 });
+        let data = RaptorData::new(&timetable);
+
+        let journey = data.query(&RaptorQuery { sources: vec![(first, 0)], departure: Time::from_seconds(0), max_transfers: 1 }).journey_to(end).unwrap();
+        let itinerary = Itinerary::from_journey(&timetable, &journey).unwrap();
+        assert_eq!((itinerary.departure(), itinerary.arrival(), itinerary.transit_legs()), (Time::from_seconds(600), Time::from_seconds(820), 1));
+        let ItineraryLeg::Walk { from, to, distance, geometry, .. } = &itinerary.legs[1] else {
+            panic!("{itinerary:?}");
+        };
+        assert_eq!((*from, *to), (Place::Stop(last), Place::Stop(end)));
+        assert!((*distance - haversine_distance(timetable.stop(last), timetable.stop(end))).abs() < 1e-9);
+        assert_eq!(geometry, &vec![[timetable.stop(last).lon, timetable.stop(last).lat], [node.lon(), node.lat()]]);
+
+        let journey = data.query(&RaptorQuery { sources: vec![(first, 0)], departure: Time::from_seconds(0), max_transfers: 1 }).journey_to(first).unwrap();
+        assert_eq!(Itinerary::from_journey(&timetable, &journey), None);
+    }
+}
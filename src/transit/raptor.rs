@@ -0,0 +1,571 @@
+pub mod range;
+
+use log::debug;
+
+use crate::graph::IDIntoUSize;
+use crate::transit::timetable::{RouteID, StopEvent, StopID, Time, Timetable, TripID};
+use crate::transit::{Journey, Leg};
+
+const UNREACHED: Time = Time::from_seconds(u32::MAX);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct RouteEntry {
+    first_stop: usize,
+    pub(super) stop_count: usize,
+    first_trip: usize,
+    pub(super) trip_count: usize,
+    /// Stop events of the route's trips are stored trip by trip, each with `stop_count` events.
+    first_event: usize,
+}
+
+/// Timetable flattened into the arrays of the RAPTOR paper: routes point into consecutive runs of their stops, trips and
+/// stop events, stops point into runs of the routes calling at them and of their transfers.
+/// It's a snapshot, changes to the timetable need a new one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaptorData {
+    routes: Vec<RouteEntry>,
+    route_stops: Vec<StopID>,
+    route_trips: Vec<TripID>,
+    stop_times: Vec<StopEvent>,
+    /// Routes at stop `s` are `stop_routes[stop_route_offsets[s]..stop_route_offsets[s + 1]]`, with the stop's position in them.
+    stop_routes: Vec<(RouteID, usize)>,
+    stop_route_offsets: Vec<usize>,
+    /// Walking to other stops, laid out like `stop_routes`.
+    transfers: Vec<(StopID, u32)>,
+    transfer_offsets: Vec<usize>,
+    /// Seconds needed to change vehicles at a stop, from transfers that start and end there.
+    min_change: Vec<u32>,
+}
+
+/// Earliest arrival query, every additional round allows one more transfer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RaptorQuery {
+    /// Stops the journey may start at, with the seconds it takes to get to each of them after `departure`.
+    pub sources: Vec<(StopID, u32)>,
+    pub departure: Time,
+    pub max_transfers: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Unreached,
+    Source,
+    /// Arrived on the `trip`-th trip of `route`, boarded at position `board` and left at `alight`.
+    Ride { route: RouteID, trip: usize, board: usize, alight: usize },
+    /// Walked from where a ride of the same round ended.
+    Walk { from: StopID },
+    /// Reached in an earlier round, not improved since.
+    Earlier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Label {
+    arrival: Time,
+    step: Step,
+    /// Whether leaving on another vehicle needs the stop's change time.
+    by_vehicle: bool,
+}
+
+const UNREACHED_LABEL: Label = Label { arrival: UNREACHED, step: Step::Unreached, by_vehicle: false };
+
+#[derive(Debug, Clone)]
+struct Round {
+    /// Best arrival with at most this round's number of trips.
+    best: Vec<Label>,
+    /// Arrivals at the end of a ride of this round, or at the sources in round 0. Transfers start from these.
+    leg_end: Vec<Label>,
+}
+
+impl RaptorData {
+    pub fn new(timetable: &Timetable) -> Self {
+        let mut data = Self {
+            routes: Vec::with_capacity(timetable.route_count()),
+            route_stops: Vec::new(),
+            route_trips: Vec::with_capacity(timetable.trip_count()),
+            stop_times: Vec::new(),
+            stop_routes: Vec::new(),
+            stop_route_offsets: Vec::with_capacity(timetable.stop_count() + 1),
+            transfers: Vec::new(),
+            transfer_offsets: Vec::with_capacity(timetable.stop_count() + 1),
+            min_change: vec![0; timetable.stop_count()],
+        };
+
+        for id in timetable.routes() {
+            let route = timetable.route(id);
+            data.routes.push(RouteEntry {
+                first_stop: data.route_stops.len(),
+                stop_count: route.stops.len(),
+                first_trip: data.route_trips.len(),
+                trip_count: route.trips.len(),
+                first_event: data.stop_times.len(),
+            });
+            data.route_stops.extend(&route.stops);
+            for &trip in &route.trips {
+                data.route_trips.push(trip);
+                data.stop_times.extend(timetable.trip_events(trip));
+            }
+        }
+
+        for stop in timetable.stops() {
+            data.stop_route_offsets.push(data.stop_routes.len());
+            data.stop_routes.extend(timetable.routes_at(stop));
+
+            data.transfer_offsets.push(data.transfers.len());
+            for transfer in timetable.transfers_from(stop) {
+                if transfer.to == stop {
+                    data.min_change[stop.as_usize()] = data.min_change[stop.as_usize()].max(transfer.duration);
+                } else {
+                    data.transfers.push((transfer.to, transfer.duration));
+                }
+            }
+        }
+        data.stop_route_offsets.push(data.stop_routes.len());
+        data.transfer_offsets.push(data.transfers.len());
+
+        data
+    }
+
+    pub fn stop_count(&self) -> usize {
+        self.min_change.len()
+    }
+
+    pub(super) fn route_count(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub(super) fn route(&self, id: RouteID) -> &RouteEntry {
+        &self.routes[id.as_usize()]
+    }
+
+    pub(super) fn route_stop(&self, route: &RouteEntry, position: usize) -> StopID {
+        self.route_stops[route.first_stop + position]
+    }
+
+    pub(super) fn route_trip(&self, route: &RouteEntry, trip: usize) -> TripID {
+        self.route_trips[route.first_trip + trip]
+    }
+
+    pub(super) fn min_change(&self, stop: StopID) -> u32 {
+        self.min_change[stop.as_usize()]
+    }
+
+    pub(super) fn routes_at(&self, stop: StopID) -> &[(RouteID, usize)] {
+        &self.stop_routes[self.stop_route_offsets[stop.as_usize()]..self.stop_route_offsets[stop.as_usize() + 1]]
+    }
+
+    pub(super) fn transfers_from(&self, stop: StopID) -> &[(StopID, u32)] {
+        &self.transfers[self.transfer_offsets[stop.as_usize()]..self.transfer_offsets[stop.as_usize() + 1]]
+    }
+
+    pub(super) fn event(&self, route: &RouteEntry, trip: usize, position: usize) -> StopEvent {
+        self.stop_times[route.first_event + trip * route.stop_count + position]
+    }
+
+    /// Index of the first trip leaving the route's `position` at or after `time`, binary search over the trips' departures there.
+    pub(super) fn first_departure(&self, route: &RouteEntry, position: usize, time: Time) -> usize {
+        let (mut low, mut high) = (0, route.trip_count);
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.event(route, mid, position).departure < time { low = mid + 1 } else { high = mid }
+        }
+        low
+    }
+
+    /// Arrivals at the end of a ride have to wait for the stop's change time before boarding again.
+    fn ready_time(&self, stop: StopID, label: &Label) -> Time {
+        if label.by_vehicle { label.arrival + self.min_change(stop) } else { label.arrival }
+    }
+
+    /// Runs rounds until no stop improves or the transfer limit is hit. Like in the paper, labels are pruned by arrival time alone,
+    /// so a later arrival on foot never replaces an earlier one by vehicle even if the change time would make it the better one to continue from.
+    pub fn query(&self, query: &RaptorQuery) -> RaptorResult<'_> {
+        let mut rounds = Vec::new();
+        self.run(&mut rounds, &query.sources, query.departure, query.max_transfers);
+        debug!("raptor query from {:?} finished after {} rounds", query.sources, rounds.len() - 1);
+        RaptorResult { data: self, rounds }
+    }
+
+    /// Runs the rounds on top of the labels of earlier runs, which are only ever improved, so runs for decreasing departures share their work.
+    /// Returns the stops every round found a new journey to, arrivals merely copied from the round before aren't included.
+    fn run(&self, rounds: &mut Vec<Round>, sources: &[(StopID, u32)], departure: Time, max_transfers: usize) -> Vec<Vec<StopID>> {
+        let stop_count = self.stop_count();
+        let empty_round = || Round { best: vec![UNREACHED_LABEL; stop_count], leg_end: vec![UNREACHED_LABEL; stop_count] };
+        // stops to board at in the next round, and stops whose arrival improved in any way and has to be copied to the next round
+        let mut marked = vec![false; stop_count];
+        let mut improved = vec![false; stop_count];
+
+        if rounds.is_empty() {
+            rounds.push(empty_round());
+        }
+        let round = &mut rounds[0];
+        for &(stop, offset) in sources {
+            let arrival = departure + offset;
+            if arrival < round.best[stop.as_usize()].arrival {
+                let label = Label { arrival, step: Step::Source, by_vehicle: false };
+                round.best[stop.as_usize()] = label;
+                round.leg_end[stop.as_usize()] = label;
+                marked[stop.as_usize()] = true;
+            }
+        }
+        self.relax_transfers(round, &mut marked);
+        improved.copy_from_slice(&marked);
+        let mut found = vec![marked_stops(&marked)];
+
+        let mut queue: Vec<Option<usize>> = vec![None; self.routes.len()];
+        for k in 1..=max_transfers + 1 {
+            if !marked.contains(&true) && (k == rounds.len() || !improved.contains(&true)) {
+                break;
+            }
+            if k == rounds.len() {
+                rounds.push(empty_round());
+            }
+            let (done, rest) = rounds.split_at_mut(k);
+            let (previous, round) = (&done[k - 1], &mut rest[0]);
+
+            // earliest position of a marked stop on every route calling at one
+            for stop in marked_stops(&marked) {
+                for &(route, position) in self.routes_at(stop) {
+                    let start = &mut queue[route.as_usize()];
+                    *start = Some(start.map_or(position, |start| start.min(position)));
+                }
+            }
+            marked.fill(false);
+
+            // a round's labels are the best with at most that many trips
+            for (idx, improved) in improved.iter_mut().enumerate() {
+                let label = previous.best[idx];
+                *improved = *improved && label.arrival < round.best[idx].arrival;
+                if *improved {
+                    round.best[idx] = Label { step: Step::Earlier, ..label };
+                }
+            }
+
+            for (route, start) in queue.iter_mut().enumerate() {
+                if let Some(start) = start.take() {
+                    self.scan_route(RouteID::from_usize(route), start, previous, round, &mut marked);
+                }
+            }
+            self.relax_transfers(round, &mut marked);
+            improved.iter_mut().zip(&marked).for_each(|(improved, marked)| *improved |= marked);
+            found.push(marked_stops(&marked));
+        }
+
+        found
+    }
+
+    fn scan_route(&self, id: RouteID, start: usize, previous: &Round, round: &mut Round, marked: &mut [bool]) {
+        let route = &self.routes[id.as_usize()];
+        // trip index in the route and the position it was boarded at
+        let mut current: Option<(usize, usize)> = None;
+
+        for position in start..route.stop_count {
+            let stop = self.route_stop(route, position);
+
+            if let Some((trip, board)) = current {
+                let arrival = self.event(route, trip, position).arrival;
+                if arrival < round.best[stop.as_usize()].arrival {
+                    let label = Label { arrival, step: Step::Ride { route: id, trip, board, alight: position }, by_vehicle: true };
+                    round.best[stop.as_usize()] = label;
+                    round.leg_end[stop.as_usize()] = label;
+                    marked[stop.as_usize()] = true;
+                }
+            }
+
+            let label = &previous.best[stop.as_usize()];
+            if label.arrival == UNREACHED {
+                continue;
+            }
+
+            // trips of a route never overtake each other, so departures at every position are sorted
+            let ready = self.ready_time(stop, label);
+            let earliest_trip = self.first_departure(route, position, ready);
+            if earliest_trip < route.trip_count && current.is_none_or(|(trip, _)| earliest_trip < trip) {
+                current = Some((earliest_trip, position));
+            }
+        }
+    }
+
+    /// Walks from every stop a ride of this round ended at, walks aren't chained.
+    fn relax_transfers(&self, round: &mut Round, marked: &mut [bool]) {
+        for from in marked_stops(marked) {
+            let departure = round.leg_end[from.as_usize()].arrival;
+            for &(to, duration) in self.transfers_from(from) {
+                let arrival = departure + duration;
+                if arrival < round.best[to.as_usize()].arrival {
+                    round.best[to.as_usize()] = Label { arrival, step: Step::Walk { from }, by_vehicle: false };
+                    marked[to.as_usize()] = true;
+                }
+            }
+        }
+    }
+}
+
+fn marked_stops(marked: &[bool]) -> Vec<StopID> {
+    (0..marked.len()).filter(|idx| marked[*idx]).map(StopID::from_usize).collect()
+}
+
+/// Labels of every round of a query, journeys are reconstructed from them on demand.
+#[derive(Debug, Clone)]
+pub struct RaptorResult<'a> {
+    data: &'a RaptorData,
+    rounds: Vec<Round>,
+}
+
+impl RaptorResult<'_> {
+    pub fn earliest_arrival(&self, stop: StopID) -> Option<Time> {
+        let arrival = self.rounds.last().expect("round 0 is always there").best[stop.as_usize()].arrival;
+        (arrival != UNREACHED).then_some(arrival)
+    }
+
+    /// Journey with the earliest arrival, taking the fewest transfers among those arriving then.
+    pub fn journey_to(&self, stop: StopID) -> Option<Journey> {
+        let arrival = self.earliest_arrival(stop)?;
+        let round = self.rounds.iter().position(|round| round.best[stop.as_usize()].arrival == arrival).expect("the last round has it");
+        Some(self.data.journey(&self.rounds, round, stop))
+    }
+
+    /// Pareto set of arrival time and transfers: a journey for every round that arrives earlier than with fewer transfers.
+    pub fn journeys_to(&self, stop: StopID) -> Vec<Journey> {
+        let mut journeys = Vec::new();
+        let mut previous = UNREACHED;
+        for (idx, round) in self.rounds.iter().enumerate() {
+            let arrival = round.best[stop.as_usize()].arrival;
+            if arrival < previous {
+                journeys.push(self.data.journey(&self.rounds, idx, stop));
+                previous = arrival;
+            }
+        }
+        journeys
+    }
+}
+
+impl RaptorData {
+    /// Follows the labels back from a stop's label in a round to the source.
+    fn journey(&self, rounds: &[Round], mut round: usize, mut stop: StopID) -> Journey {
+        let mut legs = Vec::new();
+        let mut after_walk = false;
+
+        loop {
+            let labels = &rounds[round];
+            let label = if after_walk { labels.leg_end[stop.as_usize()] } else { labels.best[stop.as_usize()] };
+            after_walk = false;
+
+            match label.step {
+                Step::Unreached => unreachable!("journeys are only reconstructed to reached stops"),
+                Step::Source => {
+                    legs.reverse();
+                    return Journey { source: stop, legs, start: label.arrival };
+                }
+                Step::Earlier => round -= 1,
+                Step::Walk { from } => {
+                    let departure = labels.leg_end[from.as_usize()].arrival;
+                    legs.push(Leg::Transfer { from, to: stop, departure, arrival: label.arrival });
+                    stop = from;
+                    after_walk = true;
+                }
+                Step::Ride { route, trip, board, alight } => {
+                    let entry = &self.routes[route.as_usize()];
+                    let from = self.route_stop(entry, board);
+                    legs.push(Leg::Transit {
+                        trip: self.route_trip(entry, trip),
+                        from,
+                        to: stop,
+                        departure: self.event(entry, trip, board).departure,
+                        arrival: self.event(entry, trip, alight).arrival,
+                    });
+                    stop = from;
+                    round -= 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+    use std::path::Path;
+
+    use crate::importer::gtfs::import_gtfs;
+    use crate::transit::test_timetables::{Lcg, RandomTimetable, RandomTransfers};
+    use crate::transit::timetable::{Line, NewTrip, Stop, Transfer};
+
+    use super::*;
+
+    fn testdata() -> Timetable {
+        import_gtfs(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")).unwrap()
+    }
+
+    fn query(source: StopID, departure: Time, max_transfers: usize) -> RaptorQuery {
+        RaptorQuery { sources: vec![(source, 0)], departure, max_transfers }
+    }
+
+    /// Checks that legs connect, don't go back in time and match the timetable.
+    fn assert_consistent(timetable: &Timetable, journey: &Journey) {
+        let mut at = (journey.source, journey.start);
+        for leg in &journey.legs {
+            assert_eq!(leg.from(), at.0, "{journey:?}");
+            assert!(leg.departure() >= at.1, "{journey:?}");
+            assert!(leg.arrival() >= leg.departure(), "{journey:?}");
+            if let Leg::Transit { trip, from, to, departure, arrival } = *leg {
+                let route = timetable.route(timetable.trip(trip).route);
+                let events = timetable.trip_events(trip);
+                let board = route.stops.iter().position(|stop| *stop == from).unwrap();
+                let alight = route.stops.iter().skip(board + 1).position(|stop| *stop == to).unwrap() + board + 1;
+                assert_eq!((events[board].departure, events[alight].arrival), (departure, arrival));
+            }
+            at = (leg.to(), leg.arrival());
+        }
+    }
+
+    #[test]
+    fn earliest_arrivals_in_test_feed() {
+        let timetable = testdata();
+        let data = RaptorData::new(&timetable);
+        let [a, c, d, e] = ["A", "C", "D", "E"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+
+        let result = data.query(&query(a, Time::from_hms(7, 55, 0), 1));
+        // the express overtakes the 8:00 and gets to C at 8:15, D is three minutes away on foot
+        assert_eq!(result.earliest_arrival(c), Some(Time::from_hms(8, 15, 0)));
+        assert_eq!(result.earliest_arrival(d), Some(Time::from_hms(8, 18, 0)));
+        assert_eq!(result.earliest_arrival(e), Some(Time::from_hms(8, 40, 0)));
+
+        let journey = result.journey_to(d).unwrap();
+        let express = timetable.trip_by_gtfs_id("R1_0805_EXPRESS").unwrap();
+        assert_eq!(journey.legs, vec![
+            Leg::Transit { trip: express, from: a, to: c, departure: Time::from_hms(8, 5, 0), arrival: Time::from_hms(8, 15, 0) },
+            Leg::Transfer { from: c, to: d, departure: Time::from_hms(8, 15, 0), arrival: Time::from_hms(8, 18, 0) },
+        ]);
+
+        let journey = result.journey_to(e).unwrap();
+        assert_eq!(journey.transfers(), 1);
+        assert_eq!(journey.arrival(), Time::from_hms(8, 40, 0));
+        assert_consistent(&timetable, &journey);
+    }
+
+    #[test]
+    fn transfer_limit_is_respected() {
+        let timetable = testdata();
+        let data = RaptorData::new(&timetable);
+        let [a, e] = ["A", "E"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+
+        let result = data.query(&query(a, Time::from_hms(7, 55, 0), 0));
+        assert_eq!(result.earliest_arrival(e), None);
+        assert!(result.journey_to(e).is_none());
+    }
+
+    #[test]
+    fn change_time_makes_tight_connections_miss() {
+        let mut timetable = Timetable::new();
+        let stops: Vec<_> = (0..3).map(|i| timetable.add_stop(Stop { gtfs_id: i.to_string(), name: String::new(), lat: 0.0, lon: 0.0 })).collect();
+        let line = timetable.add_line(Line { gtfs_id: "L".into(), short_name: String::new(), long_name: String::new(), route_type: 3 });
+        let mut trip = |id: &str, from: usize, to: usize, departure: u32, arrival: u32| timetable.add_trip(NewTrip {
+            gtfs_id: id.into(),
+            line,
+            service_id: "S".into(),
+            stops: vec![stops[from], stops[to]],
+            events: vec![
+                StopEvent { arrival: Time::from_seconds(departure), departure: Time::from_seconds(departure) },
+                StopEvent { arrival: Time::from_seconds(arrival), departure: Time::from_seconds(arrival) },
+            ],
+        });
+        trip("in", 0, 1, 100, 200);
+        trip("tight", 1, 2, 230, 300);
+        trip("relaxed", 1, 2, 400, 500);
+
+        let without_change = RaptorData::new(&timetable).query(&query(stops[0], Time::from_seconds(0), 1)).earliest_arrival(stops[2]);
+        assert_eq!(without_change, Some(Time::from_seconds(300)));
+
+        timetable.add_transfer(Transfer { from: stops[1], to: stops[1], duration: 60 });
+        let with_change = RaptorData::new(&timetable).query(&query(stops[0], Time::from_seconds(0), 1)).earliest_arrival(stops[2]);
+        assert_eq!(with_change, Some(Time::from_seconds(500)));
+    }
+
+    #[test]
+    fn pareto_journeys_over_rounds() {
+        let mut timetable = Timetable::new();
+        let stops: Vec<_> = (0..3).map(|i| timetable.add_stop(Stop { gtfs_id: i.to_string(), name: String::new(), lat: 0.0, lon: 0.0 })).collect();
+        let line = timetable.add_line(Line { gtfs_id: "L".into(), short_name: String::new(), long_name: String::new(), route_type: 3 });
+        let mut trip = |id: &str, calls: &[(usize, u32)]| timetable.add_trip(NewTrip {
+            gtfs_id: id.into(),
+            line,
+            service_id: "S".into(),
+            stops: calls.iter().map(|(stop, _)| stops[*stop]).collect(),
+            events: calls.iter().map(|(_, time)| StopEvent { arrival: Time::from_seconds(*time), departure: Time::from_seconds(*time) }).collect(),
+        });
+        trip("slow", &[(0, 100), (2, 1000)]);
+        trip("first", &[(0, 110), (1, 200)]);
+        trip("second", &[(1, 250), (2, 400)]);
+
+        let data = RaptorData::new(&timetable);
+        let result = data.query(&query(stops[0], Time::from_seconds(0), 3));
+        let journeys = result.journeys_to(stops[2]);
+        assert_eq!(journeys.iter().map(|j| (j.arrival().seconds(), j.transfers())).collect::<Vec<_>>(), vec![(1000, 0), (400, 1)]);
+        assert_eq!(result.journey_to(stops[2]).unwrap(), journeys[1]);
+    }
+
+    /// Earliest arrivals by a time dependent Dijkstra over stops, with any number of transfers.
+    fn reference_arrivals(timetable: &Timetable, source: StopID, departure: Time) -> Vec<Option<Time>> {
+        let mut arrivals = vec![None; timetable.stop_count()];
+        let mut queue = BinaryHeap::from([Reverse((departure, source))]);
+
+        while let Some(Reverse((time, stop))) = queue.pop() {
+            if arrivals[stop.as_usize()].is_some() {
+                continue;
+            }
+            arrivals[stop.as_usize()] = Some(time);
+
+            for &(route, position) in timetable.routes_at(stop) {
+                let route = timetable.route(route);
+                for &trip in &route.trips {
+                    let events = timetable.trip_events(trip);
+                    if events[position].departure >= time {
+                        for (event, stop) in events.iter().zip(&route.stops).skip(position + 1) {
+                            queue.push(Reverse((event.arrival, *stop)));
+                        }
+                    }
+                }
+            }
+            for transfer in timetable.transfers_from(stop) {
+                queue.push(Reverse((time + transfer.duration, transfer.to)));
+            }
+        }
+
+        arrivals
+    }
+
+    #[test]
+    fn matches_time_dependent_dijkstra_on_random_timetables() {
+        let mut random = Lcg::new(2024);
+        let shape = RandomTimetable {
+            stops: 10,
+            patterns: 6,
+            trips_per_pattern: 5,
+            max_calls: 4,
+            first_departure: Time::from_hms(6, 0, 0),
+            departure_window: 3 * 3600,
+            hop_spread: 600,
+            max_dwell: 30,
+            // closed footpaths, the reference chains walks which RAPTOR doesn't
+            transfers: RandomTransfers::Footpaths,
+        };
+
+        for _ in 0..20 {
+            let (timetable, stops) = shape.build(&mut random);
+
+            let data = RaptorData::new(&timetable);
+            let departure = Time::from_hms(7, 0, 0);
+            let result = data.query(&query(stops[0], departure, stops.len()));
+            let expected = reference_arrivals(&timetable, stops[0], departure);
+
+            for &stop in &stops {
+                assert_eq!(result.earliest_arrival(stop), expected[stop.as_usize()], "to {stop}");
+                if let Some(journey) = result.journey_to(stop) {
+                    assert_eq!(journey.arrival(), result.earliest_arrival(stop).unwrap());
+                    assert_consistent(&timetable, &journey);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use log::debug;
+
+use crate::graph::IDIntoUSize;
+use crate::transit::Journey;
+use crate::transit::raptor::{RaptorData, Step};
+use crate::transit::timetable::{StopID, Time};
+
+/// Profile query: all optimal journeys leaving the sources between two times.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeQuery {
+    /// Stops the journey may start at, with the seconds it takes to get to each of them after departing.
+    pub sources: Vec<(StopID, u32)>,
+    pub earliest_departure: Time,
+    pub latest_departure: Time,
+    pub max_transfers: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileEntry {
+    /// Latest time to leave for the journey.
+    pub departure: Time,
+    pub arrival: Time,
+    pub journey: Journey,
+}
+
+/// Journeys to one stop that are Pareto optimal in departure, arrival and transfers, by departure and then arrival.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Profile {
+    entries: Vec<ProfileEntry>,
+}
+
+impl Profile {
+    pub fn entries(&self) -> &[ProfileEntry] {
+        &self.entries
+    }
+
+    /// Earliest arrival when leaving at `departure` or later, with any number of transfers.
+    pub fn arrival(&self, departure: Time) -> Option<Time> {
+        let first = self.entries.partition_point(|entry| entry.departure < departure);
+        self.entries[first..].iter().map(|entry| entry.arrival).min()
+    }
+}
+
+/// Profiles of every stop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeResult {
+    profiles: Vec<Profile>,
+}
+
+impl RangeResult {
+    pub fn profile(&self, stop: StopID) -> &Profile {
+        &self.profiles[stop.as_usize()]
+    }
+}
+
+impl RaptorData {
+    /// rRAPTOR: runs a RAPTOR query for every time a trip can be caught at the sources, from the latest to the earliest.
+    /// Labels are kept between the runs, a run only finds the journeys that are better than all leaving later.
+    pub fn range_query(&self, query: &RangeQuery) -> RangeResult {
+        let departures = self.departures(query);
+        let mut profiles = vec![Profile::default(); self.stop_count()];
+        let mut rounds = Vec::new();
+
+        for &departure in &departures {
+            let found = self.run(&mut rounds, &query.sources, departure, query.max_transfers);
+            for (round, stops) in found.iter().enumerate() {
+                for &stop in stops {
+                    if rounds[round].best[stop.as_usize()].step == Step::Source {
+                        continue;
+                    }
+                    let journey = self.journey(&rounds, round, stop);
+                    profiles[stop.as_usize()].entries.push(ProfileEntry { departure, arrival: journey.arrival(), journey });
+                }
+            }
+        }
+
+        // entries were found by decreasing departure and, within a run, decreasing arrival
+        for profile in &mut profiles {
+            profile.entries.reverse();
+        }
+        debug!("range query from {:?} ran for {} departures", query.sources, departures.len());
+        RangeResult { profiles }
+    }
+
+    /// Times within the window at which leaving catches a trip at a source or at a stop one walk away from it, latest first.
+    fn departures(&self, query: &RangeQuery) -> Vec<Time> {
+        let mut departures = Vec::new();
+        for &(source, offset) in &query.sources {
+            for (stop, walk) in std::iter::once((source, 0)).chain(self.transfers_from(source).iter().copied()) {
+                for &(route, position) in self.routes_at(stop) {
+                    let route = self.route(route);
+                    for trip in 0..route.trip_count {
+                        let Some(departure) = self.event(route, trip, position).departure.seconds().checked_sub(offset + walk) else {
+                            continue;
+                        };
+                        let departure = Time::from_seconds(departure);
+                        if query.earliest_departure <= departure && departure <= query.latest_departure {
+                            departures.push(departure);
+                        }
+                    }
+                }
+            }
+        }
+        departures.sort_unstable_by(|a, b| b.cmp(a));
+        departures.dedup();
+        departures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::importer::gtfs::import_gtfs;
+    use crate::transit::raptor::RaptorQuery;
+    use crate::transit::test_timetables::{Lcg, RandomTimetable, RandomTransfers};
+
+    use super::*;
+
+    #[test]
+    fn profile_of_test_feed() {
+        let timetable = import_gtfs(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")).unwrap();
+        let data = RaptorData::new(&timetable);
+        let [a, c, e] = ["A", "C", "E"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+
+        let result = data.range_query(&RangeQuery {
+            sources: vec![(a, 0)],
+            earliest_departure: Time::from_hms(7, 55, 0),
+            latest_departure: Time::from_hms(8, 35, 0),
+            max_transfers: 2,
+        });
+
+        // the 8:00 is overtaken by the express and never worth taking to C
+        let entries: Vec<_> = result.profile(c).entries().iter().map(|entry| (entry.departure, entry.arrival)).collect();
+        assert_eq!(entries, vec![(Time::from_hms(8, 5, 0), Time::from_hms(8, 15, 0)), (Time::from_hms(8, 30, 0), Time::from_hms(8, 50, 0))]);
+        assert_eq!(result.profile(c).arrival(Time::from_hms(7, 0, 0)), Some(Time::from_hms(8, 15, 0)));
+        assert_eq!(result.profile(c).arrival(Time::from_hms(8, 6, 0)), Some(Time::from_hms(8, 50, 0)));
+        assert_eq!(result.profile(c).arrival(Time::from_hms(8, 31, 0)), None);
+
+        // the tram at 8:15 needs the express, leaving later only catches the night tram
+        let entries = result.profile(e).entries();
+        assert_eq!(
+            entries.iter().map(|entry| (entry.departure, entry.arrival)).collect::<Vec<_>>(),
+            vec![(Time::from_hms(8, 5, 0), Time::from_hms(8, 40, 0)), (Time::from_hms(8, 30, 0), Time::from_hms(24, 20, 0))]
+        );
+        assert_eq!(entries[0].journey.start, Time::from_hms(8, 5, 0));
+        assert_eq!(entries[0].journey.transfers(), 1);
+    }
+
+    #[test]
+    fn matches_raptor_at_every_departure() {
+        let mut random = Lcg::new(7);
+        let mut shape = RandomTimetable {
+            stops: 8,
+            patterns: 6,
+            trips_per_pattern: 5,
+            max_calls: 4,
+            first_departure: Time::from_hms(7, 0, 0),
+            departure_window: 2 * 3600,
+            hop_spread: 900,
+            max_dwell: 0,
+            transfers: RandomTransfers::None,
+        };
+
+        for instance in 0..10 {
+            shape.transfers = if instance % 2 == 0 { RandomTransfers::Footpaths } else { RandomTransfers::ChangeTimes };
+            let (timetable, stops) = shape.build(&mut random);
+
+            let data = RaptorData::new(&timetable);
+            let query = RangeQuery { sources: vec![(stops[0], 0)], earliest_departure: Time::from_hms(0, 0, 0), latest_departure: Time::from_hms(23, 0, 0), max_transfers: 3 };
+            let result = data.range_query(&query);
+
+            for departure in data.departures(&query) {
+                for max_transfers in 0..=query.max_transfers {
+                    let raptor = data.query(&RaptorQuery { sources: query.sources.clone(), departure, max_transfers });
+                    for &stop in &stops[1..] {
+                        let profile = result.profile(stop);
+                        let arrival = profile.entries().iter()
+                            .filter(|entry| entry.departure >= departure && entry.journey.transfers() <= max_transfers)
+                            .map(|entry| entry.arrival)
+                            .min();
+                        assert_eq!(arrival, raptor.earliest_arrival(stop), "to {stop} leaving at {departure} with {max_transfers} transfers");
+                    }
+                }
+            }
+
+            for stop in &stops[1..] {
+                for entry in result.profile(*stop).entries() {
+                    assert_eq!(entry.journey.start, entry.departure);
+                    assert_eq!(entry.journey.target(), *stop);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use log::{debug, info};
+
+use crate::importer::gtfs_rt::{FeedMessage, StopRelationship, StopTimeEvent, StopTimeUpdate, TripRelationship, TripUpdate};
+use crate::transit::calendar::Date;
+use crate::transit::timetable::{NewTrip, StopEvent, StopID, Time, Timetable, TripID};
+
+/// The day a timetable of `Timetable::for_date` is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServiceDay {
+    pub date: Date,
+    /// POSIX time of the day's 00:00:00, i.e. noon minus 12 hours in the feed's timezone.
+    pub start: i64,
+}
+
+impl ServiceDay {
+    fn time(&self, posix: i64) -> i64 {
+        posix - self.start
+    }
+}
+
+/// How many trip updates of a feed changed the timetable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AppliedUpdates {
+    /// Trips with new times or skipped stops.
+    pub updated: usize,
+    pub cancelled: usize,
+    pub added: usize,
+    /// Updates for other days, for unknown trips and stops, or of kinds that aren't supported.
+    pub ignored: usize,
+}
+
+enum Applied {
+    Updated,
+    Cancelled,
+    Added,
+}
+
+/// Applies the trip updates of a feed to the timetable of `day` in place, routing on it afterwards sees the live service.
+/// Delays are relative to the planned times, so applying a newer feed replaces the delays of the trips it has.
+/// Trips are matched by `trip_id` and service date, trips of `frequencies.txt` by their `start_time` too, and calls by
+/// `stop_id`. Calls given only by `stop_sequence` aren't updated as the timetable doesn't keep it. Trips of the day before that are still running after midnight aren't
+/// updated either.
+/// Trips with skipped stops are replaced by one calling at the other stops, which keeps the plan of the trip so that a
+/// newer feed can bring the stops back. Added trips belong to no service.
+pub fn apply_trip_updates(timetable: &mut Timetable, feed: &FeedMessage, day: ServiceDay) -> AppliedUpdates {
+    let mut applied = AppliedUpdates::default();
+    for entity in feed.entities.iter().filter(|entity| !entity.is_deleted) {
+        let Some(update) = &entity.trip_update else {
+            continue;
+        };
+        match apply(timetable, update, day) {
+            Ok(Applied::Updated) => applied.updated += 1,
+            Ok(Applied::Cancelled) => applied.cancelled += 1,
+            Ok(Applied::Added) => applied.added += 1,
+            Err(reason) => {
+                debug!("ignoring trip update {:?}: {reason}", entity.id);
+                applied.ignored += 1;
+            }
+        }
+    }
+    info!("applied trip updates for {}: {applied:?}", day.date);
+    applied
+}
+
+fn apply(timetable: &mut Timetable, update: &TripUpdate, day: ServiceDay) -> Result<Applied, String> {
+    let trip_id = update.trip.trip_id.as_deref().ok_or("it has no trip_id")?;
+    if let Some(start_date) = &update.trip.start_date
+        && Date::parse(start_date) != Some(day.date) {
+        return Err(format!("it's for {start_date}"));
+    }
+    // the importer names every departure of a trip running at a frequency after its start time
+    let frequency_trip_id = update.trip.start_time.as_deref().and_then(Time::parse).map(|start| format!("{trip_id}@{start}"));
+    let trip = || frequency_trip_id.iter().map(String::as_str).chain([trip_id])
+        .find_map(|id| timetable.trip_on_date(id, day.date))
+        .ok_or_else(|| format!("trip {trip_id:?} isn't running"));
+
+    match update.trip.schedule_relationship {
+        TripRelationship::Scheduled => {
+            let trip = trip()?;
+            update_times(timetable, trip, update, day);
+            Ok(Applied::Updated)
+        }
+        TripRelationship::Canceled | TripRelationship::Deleted => {
+            timetable.cancel_trip(trip()?);
+            Ok(Applied::Cancelled)
+        }
+        TripRelationship::Added | TripRelationship::New => {
+            if trip().is_ok() {
+                return Err(format!("trip {trip_id:?} exists already"));
+            }
+            add_trip(timetable, trip_id, update, day)?;
+            Ok(Applied::Added)
+        }
+        other => Err(format!("{other:?} trips aren't supported")),
+    }
+}
+
+/// Delays of a call carry over to the following calls without a prediction, calls before the first one keep the
+/// trip's delay or their planned times. Vehicles never leave a call before arriving at it.
+fn update_times(timetable: &mut Timetable, trip: TripID, update: &TripUpdate, day: ServiceDay) {
+    let stops = timetable.scheduled_stops(trip).to_vec();
+    let scheduled = timetable.scheduled_events(trip).to_vec();
+    let calls = match_calls(timetable, &stops, &update.stop_time_updates);
+
+    let predicted = |event: Option<StopTimeEvent>, planned: Time| event.and_then(|event| {
+        event.time.map(|time| day.time(time) - i64::from(planned.seconds())).or(event.delay.map(i64::from))
+    });
+    let mut delay = update.delay.map_or(0, i64::from);
+    let mut skipped = vec![false; stops.len()];
+    let mut events = Vec::with_capacity(stops.len());
+    let mut earliest = 0;
+    for (position, planned) in scheduled.iter().enumerate() {
+        let (mut arrival_delay, mut departure_delay) = (delay, delay);
+        if let Some(call) = calls[position] {
+            match call.schedule_relationship {
+                StopRelationship::Skipped => skipped[position] = true,
+                StopRelationship::NoData => (arrival_delay, departure_delay, delay) = (0, 0, 0),
+                _ => {
+                    arrival_delay = predicted(call.arrival, planned.arrival).unwrap_or(delay);
+                    departure_delay = predicted(call.departure, planned.departure).unwrap_or(arrival_delay);
+                    delay = departure_delay;
+                }
+            }
+        }
+
+        let arrival = (i64::from(planned.arrival.seconds()) + arrival_delay).max(earliest);
+        let departure = (i64::from(planned.departure.seconds()) + departure_delay).max(arrival);
+        earliest = departure;
+        events.push(StopEvent { arrival: time(arrival), departure: time(departure) });
+    }
+
+    let (stops, events): (Vec<StopID>, Vec<StopEvent>) = stops.iter().zip(events).zip(&skipped)
+        .filter(|(_, skipped)| !**skipped)
+        .map(|((stop, event), _)| (*stop, event))
+        .unzip();
+    if stops == timetable.route(timetable.trip(trip).route).stops {
+        timetable.set_trip_events(trip, &events);
+    } else if stops.len() >= 2 {
+        timetable.replace_trip(trip, stops, events);
+    } else {
+        timetable.cancel_trip(trip);
+    }
+}
+
+/// Update of every call of the route, in order, updates with an unknown or out of order `stop_id` are dropped.
+fn match_calls<'a>(timetable: &Timetable, stops: &[StopID], updates: &'a [StopTimeUpdate]) -> Vec<Option<&'a StopTimeUpdate>> {
+    let mut calls = vec![None; stops.len()];
+    let mut next = 0;
+    for update in updates {
+        let stop = update.stop_id.as_deref().and_then(|id| timetable.stop_by_gtfs_id(id));
+        match stop.and_then(|stop| stops[next..].iter().position(|other| *other == stop)) {
+            Some(position) => {
+                calls[next + position] = Some(update);
+                next += position + 1;
+            }
+            None => debug!("no call at stop {:?} with sequence {:?}", update.stop_id, update.stop_sequence),
+        }
+    }
+    calls
+}
+
+/// Added trips need the POSIX time of every call, calls with only one of them stay there for no time.
+fn add_trip(timetable: &mut Timetable, trip_id: &str, update: &TripUpdate, day: ServiceDay) -> Result<(), String> {
+    let route_id = update.trip.route_id.as_deref().ok_or("added trip has no route_id")?;
+    let line = timetable.line_by_gtfs_id(route_id).ok_or_else(|| format!("unknown route_id {route_id:?}"))?;
+
+    let mut trip = NewTrip { gtfs_id: trip_id.to_string(), line, service_id: String::new(), stops: Vec::new(), events: Vec::new() };
+    for call in update.stop_time_updates.iter().filter(|call| call.schedule_relationship != StopRelationship::Skipped) {
+        let stop_id = call.stop_id.as_deref().ok_or("added trip has a call without stop_id")?;
+        let stop = timetable.stop_by_gtfs_id(stop_id).ok_or_else(|| format!("unknown stop_id {stop_id:?}"))?;
+        let posix = |event: Option<StopTimeEvent>| event.and_then(|event| event.time).map(|time| day.time(time));
+        let (arrival, departure) = match (posix(call.arrival), posix(call.departure)) {
+            (Some(arrival), Some(departure)) => (arrival, departure),
+            (Some(time), None) | (None, Some(time)) => (time, time),
+            (None, None) => return Err(format!("added trip has no time at {stop_id:?}")),
+        };
+        let earliest = trip.events.last().map_or(0, |event: &StopEvent| i64::from(event.departure.seconds()));
+        if arrival < earliest || departure < arrival || departure > i64::from(u32::MAX) {
+            return Err(format!("added trip goes back in time at {stop_id:?}"));
+        }
+        trip.stops.push(stop);
+        trip.events.push(StopEvent { arrival: time(arrival), departure: time(departure) });
+    }
+
+    if trip.stops.len() < 2 {
+        return Err("added trip calls at less than two stops".into());
+    }
+    timetable.add_trip_on_date(trip, day.date);
+    Ok(())
+}
+
+fn time(seconds: i64) -> Time {
+    Time::from_seconds(seconds.clamp(0, i64::from(u32::MAX)) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::importer::gtfs::import_gtfs;
+    use crate::importer::gtfs_rt::{FeedEntity, TripDescriptor, read_feed};
+    use crate::transit::raptor::{RaptorData, RaptorQuery};
+
+    use super::*;
+
+    /// Monday 2026-05-04 in a timezone two hours ahead of UTC.
+    const MONDAY: i64 = 1_777_845_600;
+
+    fn monday() -> (Timetable, ServiceDay) {
+        let timetable = import_gtfs(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")).unwrap();
+        let date = Date::parse("20260504").unwrap();
+        (timetable.for_date(date), ServiceDay { date, start: MONDAY })
+    }
+
+    fn feed() -> FeedMessage {
+        read_feed(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-rt-testdata/trip_updates.pb")).unwrap()
+    }
+
+    fn times(timetable: &Timetable, trip: &str) -> Vec<(Time, Time)> {
+        let trip = timetable.trip_by_gtfs_id(trip).unwrap();
+        timetable.trip_events(trip).iter().map(|event| (event.arrival, event.departure)).collect()
+    }
+
+    fn hm(hours: u32, minutes: u32) -> Time {
+        Time::from_hms(hours, minutes, 0)
+    }
+
+    #[test]
+    fn applies_recorded_feed() {
+        let (mut timetable, day) = monday();
+        let applied = apply_trip_updates(&mut timetable, &feed(), day);
+        assert_eq!(applied, AppliedUpdates { updated: 2, cancelled: 1, added: 1, ignored: 3 });
+
+        // the delay carries over to the end of the trip, which now gets overtaken by the next one
+        assert_eq!(times(&timetable, "R1_0800"), vec![(hm(8, 0), hm(8, 0)), (hm(8, 45), hm(8, 46)), (hm(8, 55), hm(8, 55))]);
+        let late = timetable.trip_by_gtfs_id("R1_0800").unwrap();
+        let next = timetable.trip_by_gtfs_id("R1_0830").unwrap();
+        assert_ne!(timetable.trip(late).route, timetable.trip(next).route);
+        assert_eq!(timetable.scheduled_events(late)[1].arrival, hm(8, 10));
+
+        assert_eq!(timetable.trip_by_gtfs_id("R1_0805_EXPRESS"), None);
+        // the trip's delay holds until the first stop with a prediction
+        assert_eq!(times(&timetable, "R2_0815"), vec![(hm(8, 14), hm(8, 14)), (hm(8, 24), hm(8, 30)), (hm(8, 43), hm(8, 43))]);
+        assert_eq!(times(&timetable, "R2_EXTRA"), vec![(hm(9, 0), hm(9, 0)), (hm(9, 10), hm(9, 11)), (hm(9, 20), hm(9, 20))]);
+        assert_eq!(times(&timetable, "R2_NIGHT")[0], (hm(23, 50), hm(23, 50)));
+    }
+
+    #[test]
+    fn routing_sees_live_service() {
+        let (mut timetable, day) = monday();
+        let [a, c, e] = ["A", "C", "E"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+        let query = RaptorQuery { sources: vec![(a, 0)], departure: hm(7, 55), max_transfers: 2 };
+        let data = RaptorData::new(&timetable);
+        let planned = data.query(&query);
+        assert_eq!((planned.earliest_arrival(c), planned.earliest_arrival(e)), (Some(hm(8, 15)), Some(hm(8, 40))));
+
+        apply_trip_updates(&mut timetable, &feed(), day);
+        // without the express the tram at 8:15 is gone before any bus gets to B, the added one is the next
+        let data = RaptorData::new(&timetable);
+        let live = data.query(&query);
+        assert_eq!((live.earliest_arrival(c), live.earliest_arrival(e)), (Some(hm(8, 50)), Some(hm(9, 20))));
+    }
+
+    #[test]
+    fn updates_replace_earlier_ones() {
+        let (mut timetable, day) = monday();
+        apply_trip_updates(&mut timetable, &feed(), day);
+        let after_first = timetable.clone();
+
+        // the cancelled and the added trips can't be changed again
+        let applied = apply_trip_updates(&mut timetable, &feed(), day);
+        assert_eq!(applied, AppliedUpdates { updated: 2, cancelled: 0, added: 0, ignored: 5 });
+        // the late bus may end up in another route than the first time
+        for trip in timetable.trips() {
+            assert_eq!(timetable.trip_events(trip), after_first.trip_events(trip));
+            assert_eq!(timetable.trip(trip).is_cancelled(), after_first.trip(trip).is_cancelled());
+        }
+    }
+
+    #[test]
+    fn trips_of_the_day_before_are_left_alone() {
+        let timetable = import_gtfs(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")).unwrap();
+        let date = Date::parse("20260505").unwrap();
+        let (mut timetable, day) = (timetable.for_date(date), ServiceDay { date, start: MONDAY + 24 * 3600 });
+        let trip = TripDescriptor { trip_id: Some("R2_NIGHT".into()), schedule_relationship: TripRelationship::Canceled, ..Default::default() };
+        let entity = FeedEntity { id: "1".into(), is_deleted: false, trip_update: Some(TripUpdate { trip, ..Default::default() }) };
+        let feed = FeedMessage { entities: vec![entity], ..Default::default() };
+
+        assert_eq!(apply_trip_updates(&mut timetable, &feed, day), AppliedUpdates { cancelled: 1, ..Default::default() });
+        assert_eq!(apply_trip_updates(&mut timetable, &feed, day), AppliedUpdates { ignored: 1, ..Default::default() });
+        // Monday's night tram is still on its way
+        let monday = timetable.trip_by_gtfs_id("R2_NIGHT").unwrap();
+        assert_eq!(timetable.trip(monday).service_date(), Date::parse("20260504"));
+    }
+
+    #[test]
+    fn frequency_trips_are_matched_by_start_time() {
+        let timetable = import_gtfs(&Path::new(env!("CARGO_MANIFEST_DIR")).join("gtfs-testdata")).unwrap();
+        let date = Date::parse("20260503").unwrap();
+        let (mut timetable, day) = (timetable.for_date(date), ServiceDay { date, start: MONDAY - 24 * 3600 });
+
+        let applied = apply_trip_updates(&mut timetable, &feed(), day);
+        assert_eq!(applied.updated, 1);
+        assert_eq!(times(&timetable, "R1_BACK_FREQ@10:20:00"), vec![(hm(10, 18), hm(10, 20)), (hm(10, 34), hm(10, 34)), (hm(10, 44), hm(10, 44))]);
+        assert_eq!(times(&timetable, "R1_BACK_FREQ@10:00:00")[1], (hm(10, 10), hm(10, 10)));
+    }
+
+    #[test]
+    fn skipped_stops_change_the_pattern() {
+        let (mut timetable, day) = monday();
+        let mut feed = feed();
+        let update = feed.entities[2].trip_update.as_mut().unwrap();
+        update.stop_time_updates[0].schedule_relationship = StopRelationship::Skipped;
+
+        apply_trip_updates(&mut timetable, &feed, day);
+        let tram = timetable.trip_by_gtfs_id("R2_0815").unwrap();
+        let [b, e] = ["B", "E"].map(|id| timetable.stop_by_gtfs_id(id).unwrap());
+        assert_eq!(timetable.route(timetable.trip(tram).route).stops, vec![b, e]);
+        assert_eq!(times(&timetable, "R2_0815"), vec![(hm(8, 14), hm(8, 14)), (hm(8, 43), hm(8, 43))]);
+        let replacement = timetable.trip_by_gtfs_id("R2_0815").unwrap();
+        assert_eq!(timetable.scheduled_stops(replacement).len(), 3);
+
+        // the replacement starts from the plan again
+        apply_trip_updates(&mut timetable, &feed, day);
+        assert_eq!(times(&timetable, "R2_0815"), vec![(hm(8, 14), hm(8, 14)), (hm(8, 43), hm(8, 43))]);
+
+        // and calls at all stops once they aren't skipped anymore
+        let update = feed.entities[2].trip_update.as_mut().unwrap();
+        update.stop_time_updates[0].schedule_relationship = StopRelationship::Scheduled;
+        apply_trip_updates(&mut timetable, &feed, day);
+        assert_eq!(times(&timetable, "R2_0815"), vec![(hm(8, 14), hm(8, 14)), (hm(8, 24), hm(8, 30)), (hm(8, 43), hm(8, 43))]);
+    }
+}
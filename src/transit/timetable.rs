@@ -0,0 +1,561 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Add;
+
+use derive_more::Display;
+
+use crate::graph::IDIntoUSize;
+use crate::importer::HasCoordinates;
+use crate::transit::calendar::{Calendar, Date};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Display)]
+pub struct StopID(usize);
+
+impl IDIntoUSize for StopID {
+    fn as_usize(&self) -> usize { self.0 }
+    fn from_usize(id: usize) -> Self { StopID(id) }
+}
+
+/// A GTFS route, i.e. the line passengers know by its name. Its trips are split into `Route`s by stop pattern.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Display)]
+pub struct LineID(usize);
+
+impl IDIntoUSize for LineID {
+    fn as_usize(&self) -> usize { self.0 }
+    fn from_usize(id: usize) -> Self { LineID(id) }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Display)]
+pub struct RouteID(usize);
+
+impl IDIntoUSize for RouteID {
+    fn as_usize(&self) -> usize { self.0 }
+    fn from_usize(id: usize) -> Self { RouteID(id) }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Display)]
+pub struct TripID(usize);
+
+impl IDIntoUSize for TripID {
+    fn as_usize(&self) -> usize { self.0 }
+    fn from_usize(id: usize) -> Self { TripID(id) }
+}
+
+const SECONDS_PER_DAY: u32 = 24 * 3600;
+
+/// Seconds since midnight of the service day, GTFS allows going past 24:00 for trips running after midnight.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Default)]
+pub struct Time(u32);
+
+impl Time {
+    pub const fn from_seconds(seconds: u32) -> Self {
+        Self(seconds)
+    }
+
+    pub const fn from_hms(hours: u32, minutes: u32, seconds: u32) -> Self {
+        Self(hours * 3600 + minutes * 60 + seconds)
+    }
+
+    pub const fn seconds(self) -> u32 {
+        self.0
+    }
+
+    /// Parses GTFS `H:MM:SS` or `HH:MM:SS`, hours may go past 23.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.split(':');
+        let hours: u32 = parts.next()?.parse().ok()?;
+        let minutes: u32 = parts.next()?.parse().ok()?;
+        let seconds: u32 = parts.next()?.parse().ok()?;
+        if parts.next().is_some() || minutes >= 60 || seconds >= 60 {
+            return None;
+        }
+
+        Some(Self::from_hms(hours, minutes, seconds))
+    }
+}
+
+impl Add<u32> for Time {
+    type Output = Time;
+
+    fn add(self, seconds: u32) -> Time {
+        Time(self.0 + seconds)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.0 / 3600, self.0 / 60 % 60, self.0 % 60)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stop {
+    pub gtfs_id: String,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl HasCoordinates for Stop {
+    fn lat(&self) -> f64 {
+        self.lat
+    }
+
+    fn lon(&self) -> f64 {
+        self.lon
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub gtfs_id: String,
+    pub short_name: String,
+    pub long_name: String,
+    /// GTFS `route_type`, e.g. 0 for trams and 3 for buses.
+    pub route_type: u16,
+}
+
+/// Trip pattern: trips of one line calling at the same stops, none of them overtaking another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub line: LineID,
+    pub stops: Vec<StopID>,
+    /// Ordered by departure, which thanks to no overtaking is also the order at every other stop.
+    pub trips: Vec<TripID>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trip {
+    pub gtfs_id: String,
+    pub route: RouteID,
+    pub service_id: String,
+    /// Index of the event at the route's first stop, the others follow it in `Timetable::stop_events`.
+    first_event: usize,
+    service_date: Option<Date>,
+    cancelled: bool,
+}
+
+impl Trip {
+    /// Cancelled trips keep their ID but are in no route's trips.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// Day of the service the trip runs for, only known in the timetables of `Timetable::for_date`. A trip that started
+    /// the day before has that day's date.
+    pub fn service_date(&self) -> Option<Date> {
+        self.service_date
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopEvent {
+    pub arrival: Time,
+    pub departure: Time,
+}
+
+/// Walking between two stops, or the minimum time to change vehicles if both are the same stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transfer {
+    pub from: StopID,
+    pub to: StopID,
+    /// In seconds.
+    pub duration: u32,
+}
+
+/// Stops and times of a trip before any changes to it.
+#[derive(Debug, Clone, PartialEq)]
+struct Planned {
+    stops: Vec<StopID>,
+    events: Vec<StopEvent>,
+}
+
+/// Everything needed to add a trip, `stops` and `events` go together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewTrip {
+    pub gtfs_id: String,
+    pub line: LineID,
+    pub service_id: String,
+    pub stops: Vec<StopID>,
+    pub events: Vec<StopEvent>,
+}
+
+/// Scheduled public transport laid out for RAPTOR style scans: stops know the routes calling at them
+/// and the stop events of all trips live in one flat vector.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Timetable {
+    stops: Vec<Stop>,
+    lines: Vec<Line>,
+    routes: Vec<Route>,
+    trips: Vec<Trip>,
+    stop_events: Vec<StopEvent>,
+    transfers: Vec<Transfer>,
+    /// `transfers_from[stop]` indexes `transfers`.
+    transfers_from: Vec<Vec<usize>>,
+    /// `routes_at[stop]` lists the routes calling at `stop` with the stop's position in them.
+    routes_at: Vec<Vec<(RouteID, usize)>>,
+    calendar: Calendar,
+    /// Plans of trips whose times were changed after adding them, and of trips replacing others.
+    planned: HashMap<TripID, Planned>,
+    /// Lookups by GTFS ID, of stops and lines the first one with an ID.
+    stops_by_gtfs_id: HashMap<String, StopID>,
+    lines_by_gtfs_id: HashMap<String, LineID>,
+    /// All trips with an ID, cancelled ones and the ones of other service dates too.
+    trips_by_gtfs_id: HashMap<String, Vec<TripID>>,
+    /// Routes of every line and stop pattern, trips are placed in the first one they fit into.
+    routes_by_pattern: HashMap<(LineID, Vec<StopID>), Vec<RouteID>>,
+}
+
+impl Timetable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_stop(&mut self, stop: Stop) -> StopID {
+        let id = StopID::from_usize(self.stops.len());
+        self.stops_by_gtfs_id.entry(stop.gtfs_id.clone()).or_insert(id);
+        self.stops.push(stop);
+        self.transfers_from.push(Vec::new());
+        self.routes_at.push(Vec::new());
+        id
+    }
+
+    pub fn add_line(&mut self, line: Line) -> LineID {
+        let id = LineID::from_usize(self.lines.len());
+        self.lines_by_gtfs_id.entry(line.gtfs_id.clone()).or_insert(id);
+        self.lines.push(line);
+        id
+    }
+
+    pub fn add_transfer(&mut self, transfer: Transfer) {
+        debug_assert!(transfer.from.as_usize() < self.stops.len(), "invalid 'from' StopID: {:?}", transfer.from);
+        debug_assert!(transfer.to.as_usize() < self.stops.len(), "invalid 'to' StopID: {:?}", transfer.to);
+        self.transfers_from[transfer.from.as_usize()].push(self.transfers.len());
+        self.transfers.push(transfer);
+    }
+
+    /// Puts the trip into the first route of its line with the same stops that it doesn't overtake or get overtaken in,
+    /// or into a new route if there's none.
+    pub fn add_trip(&mut self, trip: NewTrip) -> TripID {
+        self.insert_trip(trip, None)
+    }
+
+    /// Adds a trip running for the service of `date`, see `add_trip`.
+    pub fn add_trip_on_date(&mut self, trip: NewTrip, date: Date) -> TripID {
+        self.insert_trip(trip, Some(date))
+    }
+
+    fn insert_trip(&mut self, trip: NewTrip, service_date: Option<Date>) -> TripID {
+        assert_eq!(trip.stops.len(), trip.events.len(), "trip {} has {} stops but {} events", trip.gtfs_id, trip.stops.len(), trip.events.len());
+        assert!(!trip.stops.is_empty(), "trip {} has no stops", trip.gtfs_id);
+        debug_assert!(trip.events.iter().all(|event| event.arrival <= event.departure), "trip {} departs before arriving", trip.gtfs_id);
+        debug_assert!(trip.events.windows(2).all(|pair| pair[0].departure <= pair[1].arrival), "trip {} goes back in time", trip.gtfs_id);
+
+        let id = TripID::from_usize(self.trips.len());
+        let route = self.place(id, trip.line, &trip.stops, &trip.events);
+        self.trips_by_gtfs_id.entry(trip.gtfs_id.clone()).or_default().push(id);
+        self.trips.push(Trip { gtfs_id: trip.gtfs_id, route, service_id: trip.service_id, first_event: self.stop_events.len(), service_date, cancelled: false });
+        self.stop_events.extend(trip.events);
+        id
+    }
+
+    /// Inserts the trip into the trips of a route it fits into, see `add_trip`.
+    fn place(&mut self, id: TripID, line: LineID, stops: &[StopID], events: &[StopEvent]) -> RouteID {
+        let pattern = (line, stops.to_vec());
+        let placement = self.routes_by_pattern.get(&pattern).into_iter().flatten()
+            .find_map(|&route| self.fifo_position(route, events).map(|position| (route, position)));
+
+        let (route, position) = placement.unwrap_or_else(|| {
+            let route = RouteID::from_usize(self.routes.len());
+            for (position, stop) in stops.iter().enumerate() {
+                self.routes_at[stop.as_usize()].push((route, position));
+            }
+            self.routes.push(Route { line, stops: stops.to_vec(), trips: Vec::new() });
+            self.routes_by_pattern.entry(pattern).or_default().push(route);
+            (route, 0)
+        });
+
+        self.routes[route.as_usize()].trips.insert(position, id);
+        route
+    }
+
+    /// Takes the trip out of its route, routing no longer finds it.
+    pub fn cancel_trip(&mut self, id: TripID) {
+        let trip = &mut self.trips[id.as_usize()];
+        if !trip.cancelled {
+            trip.cancelled = true;
+            self.routes[trip.route.as_usize()].trips.retain(|other| *other != id);
+        }
+    }
+
+    /// Changes the times of a trip in place, e.g. for delays. A trip that overtakes or gets overtaken by another one
+    /// moves to a route it fits into, see `add_trip`. The planned times stay available as `scheduled_events`.
+    pub fn set_trip_events(&mut self, id: TripID, events: &[StopEvent]) {
+        let trip = &self.trips[id.as_usize()];
+        assert!(!trip.cancelled, "trip {} is cancelled", trip.gtfs_id);
+        let (route, first_event) = (trip.route, trip.first_event);
+        assert_eq!(events.len(), self.routes[route.as_usize()].stops.len(), "trip {} got {} events", trip.gtfs_id, events.len());
+        debug_assert!(events.iter().all(|event| event.arrival <= event.departure), "trip {} departs before arriving", trip.gtfs_id);
+        debug_assert!(events.windows(2).all(|pair| pair[0].departure <= pair[1].arrival), "trip {} goes back in time", trip.gtfs_id);
+
+        let (line, stops) = (self.routes[route.as_usize()].line, self.routes[route.as_usize()].stops.clone());
+        let current = &mut self.stop_events[first_event..first_event + events.len()];
+        self.planned.entry(id).or_insert_with(|| Planned { stops: stops.clone(), events: current.to_vec() });
+        current.copy_from_slice(events);
+
+        self.routes[route.as_usize()].trips.retain(|other| *other != id);
+        self.trips[id.as_usize()].route = self.place(id, line, &stops, events);
+    }
+
+    /// Cancels the trip and adds one with the same IDs calling at other stops, e.g. when some of them are skipped.
+    /// The replacement keeps the plan of the trip it replaces as its `scheduled_stops` and `scheduled_events`.
+    pub fn replace_trip(&mut self, id: TripID, stops: Vec<StopID>, events: Vec<StopEvent>) -> TripID {
+        let planned = Planned { stops: self.scheduled_stops(id).to_vec(), events: self.scheduled_events(id).to_vec() };
+        let trip = &self.trips[id.as_usize()];
+        let replacement = NewTrip {
+            gtfs_id: trip.gtfs_id.clone(),
+            line: self.routes[trip.route.as_usize()].line,
+            service_id: trip.service_id.clone(),
+            stops,
+            events,
+        };
+        let service_date = trip.service_date;
+
+        self.cancel_trip(id);
+        let replacement = self.insert_trip(replacement, service_date);
+        self.planned.insert(replacement, planned);
+        replacement
+    }
+
+    /// Stops the trip was planned with, before any `replace_trip`.
+    pub fn scheduled_stops(&self, id: TripID) -> &[StopID] {
+        self.planned.get(&id).map_or_else(|| self.route(self.trip(id).route).stops.as_slice(), |planned| &planned.stops)
+    }
+
+    /// Times the trip was planned with, before any `set_trip_events` or `replace_trip`, one per `scheduled_stops`.
+    pub fn scheduled_events(&self, id: TripID) -> &[StopEvent] {
+        self.planned.get(&id).map_or_else(|| self.trip_events(id), |planned| &planned.events)
+    }
+
+    /// Where the events would go in the route's trips without breaking their order at any stop.
+    fn fifo_position(&self, route: RouteID, events: &[StopEvent]) -> Option<usize> {
+        let trips = &self.routes[route.as_usize()].trips;
+        let position = trips.partition_point(|trip| self.trip_events(*trip)[0].departure <= events[0].departure);
+
+        let after = |earlier: &[StopEvent], later: &[StopEvent]| earlier.iter().zip(later)
+            .all(|(earlier, later)| earlier.arrival <= later.arrival && earlier.departure <= later.departure);
+        let fits_after = position == 0 || after(self.trip_events(trips[position - 1]), events);
+        let fits_before = position == trips.len() || after(events, self.trip_events(trips[position]));
+
+        (fits_after && fits_before).then_some(position)
+    }
+
+    pub fn stop_count(&self) -> usize {
+        self.stops.len()
+    }
+
+    pub fn stops(&self) -> impl Iterator<Item = StopID> {
+        (0..self.stops.len()).map(StopID::from_usize)
+    }
+
+    pub fn stop(&self, id: StopID) -> &Stop {
+        &self.stops[id.as_usize()]
+    }
+
+    pub fn stop_by_gtfs_id(&self, gtfs_id: &str) -> Option<StopID> {
+        self.stops_by_gtfs_id.get(gtfs_id).copied()
+    }
+
+    pub fn line(&self, id: LineID) -> &Line {
+        &self.lines[id.as_usize()]
+    }
+
+    pub fn line_by_gtfs_id(&self, gtfs_id: &str) -> Option<LineID> {
+        self.lines_by_gtfs_id.get(gtfs_id).copied()
+    }
+
+    pub fn route_count(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn routes(&self) -> impl Iterator<Item = RouteID> {
+        (0..self.routes.len()).map(RouteID::from_usize)
+    }
+
+    pub fn route(&self, id: RouteID) -> &Route {
+        &self.routes[id.as_usize()]
+    }
+
+    pub fn trip_count(&self) -> usize {
+        self.trips.len()
+    }
+
+    pub fn trips(&self) -> impl Iterator<Item = TripID> {
+        (0..self.trips.len()).map(TripID::from_usize)
+    }
+
+    pub fn trip(&self, id: TripID) -> &Trip {
+        &self.trips[id.as_usize()]
+    }
+
+    /// The first trip with the ID that isn't cancelled.
+    pub fn trip_by_gtfs_id(&self, gtfs_id: &str) -> Option<TripID> {
+        self.trips_by_gtfs_id.get(gtfs_id)?.iter().copied().find(|trip| !self.trips[trip.as_usize()].cancelled)
+    }
+
+    /// The trip with the ID running for the service of `date` that isn't cancelled, see `Trip::service_date`.
+    pub fn trip_on_date(&self, gtfs_id: &str, date: Date) -> Option<TripID> {
+        self.trips_by_gtfs_id.get(gtfs_id)?.iter().copied().find(|trip| {
+            let trip = &self.trips[trip.as_usize()];
+            trip.service_date == Some(date) && !trip.cancelled
+        })
+    }
+
+    /// One event per stop of the trip's route.
+    pub fn trip_events(&self, id: TripID) -> &[StopEvent] {
+        let trip = &self.trips[id.as_usize()];
+        let len = self.routes[trip.route.as_usize()].stops.len();
+        &self.stop_events[trip.first_event..trip.first_event + len]
+    }
+
+    /// Routes calling at `stop`, with the position of `stop` in each of them.
+    pub fn routes_at(&self, stop: StopID) -> &[(RouteID, usize)] {
+        &self.routes_at[stop.as_usize()]
+    }
+
+    pub fn transfers_from(&self, stop: StopID) -> impl Iterator<Item = &Transfer> {
+        self.transfers_from[stop.as_usize()].iter().map(|idx| &self.transfers[*idx])
+    }
+
+    /// Days the trips' services run on.
+    pub fn calendar(&self) -> &Calendar {
+        &self.calendar
+    }
+
+    pub fn calendar_mut(&mut self) -> &mut Calendar {
+        &mut self.calendar
+    }
+
+    /// Trips running on `date`, with times since its midnight: the trips of services active on the day, and the
+    /// parts after midnight of trips of the days before, shifted back by a day for every day. Stops, lines and transfers
+    /// keep their IDs, trips and routes get new ones.
+    pub fn for_date(&self, date: Date) -> Timetable {
+        let mut day = Timetable {
+            stops: self.stops.clone(),
+            lines: self.lines.clone(),
+            transfers: self.transfers.clone(),
+            transfers_from: self.transfers_from.clone(),
+            routes_at: vec![Vec::new(); self.stops.len()],
+            calendar: self.calendar.clone(),
+            stops_by_gtfs_id: self.stops_by_gtfs_id.clone(),
+            lines_by_gtfs_id: self.lines_by_gtfs_id.clone(),
+            ..Timetable::default()
+        };
+
+        let latest = self.stop_events.iter().map(|event| event.arrival.seconds()).max().unwrap_or(0);
+        for days_back in 0..=latest / SECONDS_PER_DAY {
+            let service_date = date.add_days(-(days_back as i32));
+            let shift = days_back * SECONDS_PER_DAY;
+
+            for id in self.trips() {
+                let trip = &self.trips[id.as_usize()];
+                if trip.cancelled || !self.calendar.is_active(&trip.service_id, service_date) {
+                    continue;
+                }
+
+                // calls from the first one that can still be left after the shift, ending at the last one
+                let events = self.trip_events(id);
+                let first = events.iter().position(|event| event.departure.seconds() >= shift).unwrap_or(events.len() - 1);
+                if events.len() - first < 2 {
+                    continue;
+                }
+                let shifted = |time: Time| Time(time.0.max(shift) - shift);
+                day.add_trip_on_date(NewTrip {
+                    gtfs_id: trip.gtfs_id.clone(),
+                    line: self.routes[trip.route.as_usize()].line,
+                    service_id: trip.service_id.clone(),
+                    stops: self.routes[trip.route.as_usize()].stops[first..].to_vec(),
+                    events: events[first..].iter().map(|event| StopEvent { arrival: shifted(event.arrival), departure: shifted(event.departure) }).collect(),
+                }, service_date);
+            }
+        }
+
+        day
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(times: &[(u32, u32)]) -> Vec<StopEvent> {
+        times.iter().map(|(arrival, departure)| StopEvent { arrival: Time::from_seconds(*arrival), departure: Time::from_seconds(*departure) }).collect()
+    }
+
+    #[test]
+    fn time_parsing_and_display() {
+        assert_eq!(Time::parse("08:05:30"), Some(Time::from_hms(8, 5, 30)));
+        assert_eq!(Time::parse("8:05:30"), Some(Time::from_hms(8, 5, 30)));
+        assert_eq!(Time::parse("25:10:00").map(Time::seconds), Some(25 * 3600 + 600));
+        assert_eq!(Time::parse("08:65:00"), None);
+        assert_eq!(Time::parse("08:05"), None);
+        assert_eq!(Time::parse(""), None);
+        assert_eq!(Time::from_hms(26, 1, 2).to_string(), "26:01:02");
+    }
+
+    #[test]
+    fn overtaking_trips_get_their_own_route() {
+        let mut timetable = Timetable::new();
+        let stops: Vec<_> = (0..3).map(|i| timetable.add_stop(Stop { gtfs_id: i.to_string(), name: String::new(), lat: 0.0, lon: 0.0 })).collect();
+        let line = timetable.add_line(Line { gtfs_id: "L".into(), short_name: "L".into(), long_name: String::new(), route_type: 3 });
+        let mut add = |id: &str, times: &[(u32, u32)]| timetable.add_trip(NewTrip {
+            gtfs_id: id.into(),
+            line,
+            service_id: "S".into(),
+            stops: stops.clone(),
+            events: events(times),
+        });
+
+        let late = add("late", &[(200, 200), (300, 300), (400, 400)]);
+        let early = add("early", &[(100, 100), (200, 200), (300, 300)]);
+        let express = add("express", &[(150, 150), (210, 210), (250, 250)]);
+
+        assert_eq!(timetable.route_count(), 2);
+        let first = timetable.trip(late).route;
+        assert_eq!(timetable.trip(early).route, first);
+        assert_eq!(timetable.route(first).trips, vec![early, late]);
+        assert_ne!(timetable.trip(express).route, first);
+        assert_eq!(timetable.trip_events(express)[2].arrival, Time::from_seconds(250));
+        assert_eq!(timetable.routes_at(stops[1]), &[(first, 1), (timetable.trip(express).route, 1)]);
+    }
+
+    #[test]
+    fn delayed_trips_move_to_a_route_they_fit_into() {
+        let mut timetable = Timetable::new();
+        let stops: Vec<_> = (0..2).map(|i| timetable.add_stop(Stop { gtfs_id: i.to_string(), name: String::new(), lat: 0.0, lon: 0.0 })).collect();
+        let line = timetable.add_line(Line { gtfs_id: "L".into(), short_name: "L".into(), long_name: String::new(), route_type: 3 });
+        let mut add = |id: &str, times: &[(u32, u32)]| timetable.add_trip(NewTrip {
+            gtfs_id: id.into(),
+            line,
+            service_id: "S".into(),
+            stops: stops.clone(),
+            events: events(times),
+        });
+        let first = add("first", &[(100, 100), (200, 200)]);
+        let second = add("second", &[(150, 150), (250, 250)]);
+        let route = timetable.trip(first).route;
+
+        timetable.set_trip_events(first, &events(&[(100, 100), (300, 300)]));
+        assert_eq!(timetable.route_count(), 2);
+        assert_eq!(timetable.route(route).trips, vec![second]);
+        assert_eq!(timetable.trip_events(first)[1].arrival, Time::from_seconds(300));
+        assert_eq!(timetable.scheduled_events(first)[1].arrival, Time::from_seconds(200));
+        assert_eq!(timetable.scheduled_events(second)[1].arrival, Time::from_seconds(250));
+
+        timetable.cancel_trip(second);
+        assert!(timetable.route(route).trips.is_empty());
+        assert_eq!(timetable.trip_by_gtfs_id("second"), None);
+        timetable.set_trip_events(first, &events(&[(100, 100), (200, 200)]));
+        assert_eq!(timetable.scheduled_events(first)[1].arrival, Time::from_seconds(200));
+    }
+}